@@ -4,15 +4,34 @@ use super::types::{Role, PieceName};
 use super::cell::Cell;
 use super::Move;
 use super::piece::{Piece, Bishop, King, Knight, Pawn, Queen, Rook};
+use super::zobrist;
 
+// King and rook of each castling right, in the order Board keeps the rights
+const CASTLING: [(bool, PieceName, &str, &str); 4] = [
+    (true, PieceName::Krook, "e1", "h1"),
+    (true, PieceName::Qrook, "e1", "a1"),
+    (false, PieceName::Krook, "e8", "h8"),
+    (false, PieceName::Qrook, "e8", "a8")
+];
 
 pub struct Board {
     pub pieces: Vec<Box<dyn Piece>>,
+    // Zobrist hash of the position, kept up to date by move_pieces
+    hash: u64,
+    white_to_move: bool,
+    castling: [bool; 4],
+    // The cell a pawn just skipped, when an enemy pawn stands beside it ready to take
+    en_passant: Option<Cell>
 }
 
 impl Board {
-    #[allow(dead_code)]
     pub fn dump(&self) {
+        println!("{}", self.diagram());
+    }
+
+    // One line per row from the eighth down, white pieces in upper case.
+    pub fn diagram(&self) -> String {
+        let mut the_diagram = String::new();
         for row in 1..=8 {
             let chess_row = 9-row;
             let mut the_line = String::new();
@@ -23,96 +42,153 @@ impl Board {
                     None => the_line.push('.')
                 }
             }
-            println!("{:?} {}", chess_row, the_line);
+            the_diagram.push_str(&format!("{:?} {}\n", chess_row, the_line));
         }
-        println!("\n  abcdefgh");
+        the_diagram.push_str("\n  abcdefgh");
+        the_diagram
     }
 
     #[cfg(test)]
     pub fn new_king_test() -> Board {
-        Board {
-            pieces: vec![
-                Box::new(King::new(true)),
-                Box::new(King::new(false)),
-            ]
-        }
+        Board::with_pieces(vec![
+            Box::new(King::new(true)),
+            Box::new(King::new(false)),
+        ])
     }
 
     #[cfg(test)]
     pub fn new_queen_test() -> Board {
-        Board {
-            pieces: vec![
-                Box::new(Queen::new(true)),
-                Box::new(Queen::new(false)),
-            ]
-        }
+        Board::with_pieces(vec![
+            Box::new(Queen::new(true)),
+            Box::new(Queen::new(false)),
+        ])
     }
 
     #[cfg(test)]
     pub fn new_rook_test() -> Board {
-        Board {
-            pieces: vec![
-                Box::new(Rook::new(true, PieceName::Qrook)),
-                Box::new(Rook::new(true, PieceName::Krook)),
-                Box::new(Rook::new(false, PieceName::Qrook)),
-                Box::new(Rook::new(false, PieceName::Krook)),
-            ]
-        }
+        Board::with_pieces(vec![
+            Box::new(Rook::new(true, PieceName::Qrook)),
+            Box::new(Rook::new(true, PieceName::Krook)),
+            Box::new(Rook::new(false, PieceName::Qrook)),
+            Box::new(Rook::new(false, PieceName::Krook)),
+        ])
     }
 
     #[cfg(test)]
     pub fn new_bishop_test() -> Board {
-        Board {
-            pieces: vec![
-                Box::new(Bishop::new(true, PieceName::Qbishop)),
-                Box::new(Bishop::new(true, PieceName::Kbishop)),
-                Box::new(Bishop::new(false, PieceName::Qbishop)),
-                Box::new(Bishop::new(false, PieceName::Kbishop)),
-            ]
-        }
+        Board::with_pieces(vec![
+            Box::new(Bishop::new(true, PieceName::Qbishop)),
+            Box::new(Bishop::new(true, PieceName::Kbishop)),
+            Box::new(Bishop::new(false, PieceName::Qbishop)),
+            Box::new(Bishop::new(false, PieceName::Kbishop)),
+        ])
     }
 
     pub fn new() -> Board {
-        Board {
-            pieces: vec![
-                // White pieces
-                Box::new(Pawn::new(true, PieceName::Apawn)),
-                Box::new(Pawn::new(true, PieceName::Bpawn)),
-                Box::new(Pawn::new(true, PieceName::Cpawn)),
-                Box::new(Pawn::new(true, PieceName::Dpawn)),
-                Box::new(Pawn::new(true, PieceName::Epawn)),
-                Box::new(Pawn::new(true, PieceName::Fpawn)),
-                Box::new(Pawn::new(true, PieceName::Gpawn)),
-                Box::new(Pawn::new(true, PieceName::Hpawn)),
-                Box::new(Rook::new(true, PieceName::Qrook)),
-                Box::new(Knight::new(true, PieceName::Qknight)),
-                Box::new(Bishop::new(true, PieceName::Qbishop)),
-                Box::new(Queen::new(true)),
-                Box::new(King::new(true)),
-                Box::new(Bishop::new(true, PieceName::Kbishop)),
-                Box::new(Knight::new(true, PieceName::Kknight)),
-                Box::new(Rook::new(true, PieceName::Krook)),
-                
-                // Black pieces
-                Box::new(Pawn::new(false, PieceName::Apawn)),
-                Box::new(Pawn::new(false, PieceName::Bpawn)),
-                Box::new(Pawn::new(false, PieceName::Cpawn)),
-                Box::new(Pawn::new(false, PieceName::Dpawn)),
-                Box::new(Pawn::new(false, PieceName::Epawn)),
-                Box::new(Pawn::new(false, PieceName::Fpawn)),
-                Box::new(Pawn::new(false, PieceName::Gpawn)),
-                Box::new(Pawn::new(false, PieceName::Hpawn)),
-                Box::new(Rook::new(false, PieceName::Qrook)),
-                Box::new(Knight::new(false, PieceName::Qknight)),
-                Box::new(Bishop::new(false, PieceName::Qbishop)),
-                Box::new(Queen::new(false)),
-                Box::new(King::new(false)),
-                Box::new(Bishop::new(false, PieceName::Kbishop)),
-                Box::new(Knight::new(false, PieceName::Kknight)),
-                Box::new(Rook::new(false, PieceName::Krook)),
-                
-            ]
+        Board::with_pieces(vec![
+            // White pieces
+            Box::new(Pawn::new(true, PieceName::Apawn)),
+            Box::new(Pawn::new(true, PieceName::Bpawn)),
+            Box::new(Pawn::new(true, PieceName::Cpawn)),
+            Box::new(Pawn::new(true, PieceName::Dpawn)),
+            Box::new(Pawn::new(true, PieceName::Epawn)),
+            Box::new(Pawn::new(true, PieceName::Fpawn)),
+            Box::new(Pawn::new(true, PieceName::Gpawn)),
+            Box::new(Pawn::new(true, PieceName::Hpawn)),
+            Box::new(Rook::new(true, PieceName::Qrook)),
+            Box::new(Knight::new(true, PieceName::Qknight)),
+            Box::new(Bishop::new(true, PieceName::Qbishop)),
+            Box::new(Queen::new(true)),
+            Box::new(King::new(true)),
+            Box::new(Bishop::new(true, PieceName::Kbishop)),
+            Box::new(Knight::new(true, PieceName::Kknight)),
+            Box::new(Rook::new(true, PieceName::Krook)),
+            
+            // Black pieces
+            Box::new(Pawn::new(false, PieceName::Apawn)),
+            Box::new(Pawn::new(false, PieceName::Bpawn)),
+            Box::new(Pawn::new(false, PieceName::Cpawn)),
+            Box::new(Pawn::new(false, PieceName::Dpawn)),
+            Box::new(Pawn::new(false, PieceName::Epawn)),
+            Box::new(Pawn::new(false, PieceName::Fpawn)),
+            Box::new(Pawn::new(false, PieceName::Gpawn)),
+            Box::new(Pawn::new(false, PieceName::Hpawn)),
+            Box::new(Rook::new(false, PieceName::Qrook)),
+            Box::new(Knight::new(false, PieceName::Qknight)),
+            Box::new(Bishop::new(false, PieceName::Qbishop)),
+            Box::new(Queen::new(false)),
+            Box::new(King::new(false)),
+            Box::new(Bishop::new(false, PieceName::Kbishop)),
+            Box::new(Knight::new(false, PieceName::Kknight)),
+            Box::new(Rook::new(false, PieceName::Krook)),
+            
+        ])
+    }
+
+    // White to move, with the castling rights of the kings and rooks still at home.
+    fn with_pieces(pieces: Vec<Box<dyn Piece>>) -> Board {
+        let mut board = Board {pieces, hash: 0, white_to_move: true, castling: [false; 4], en_passant: None};
+        board.castling = board.castling_rights();
+        board.hash = board.compute_hash();
+        board
+    }
+
+    // The same for the same position, however the game got there.
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    // Whether the king and the rook are still at home and unmoved, not whether castling is legal now
+    pub fn can_castle(&self, white: bool, king_side: bool) -> bool {
+        let rook = if king_side {PieceName::Krook} else {PieceName::Qrook};
+        CASTLING.iter().zip(self.castling.iter())
+            .any(|((right_white, right_rook, _, _), held)| *held && *right_white == white && *right_rook == rook)
+    }
+
+    pub fn white_to_move(&self) -> bool {
+        self.white_to_move
+    }
+
+    // Pieces of the same roles and colors on the same cells, whichever pieces they are, with the
+    // same side to move, castling rights and en passant capture.
+    pub fn same_position(&self, other: &Board) -> bool {
+        let piece_at = |board: &Board, cell: &Cell| board.get_piece_at_cell(cell).map(|piece| (piece.get_role(), piece.is_white()));
+        self.hash == other.hash
+            && self.white_to_move == other.white_to_move
+            && self.castling == other.castling
+            && self.en_passant == other.en_passant
+            && (1..=8).all(|row| ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'].iter()
+                .all(|file| piece_at(self, &Cell {file: *file, row}) == piece_at(other, &Cell {file: *file, row})))
+    }
+
+    // The whole hash worked out from scratch, move_pieces only applies what changed.
+    fn compute_hash(&self) -> u64 {
+        let mut hash = self.pieces.iter()
+            .filter_map(|piece| piece.get_curr_cell().map(|cell| zobrist::piece(piece.get_role(), piece.is_white(), &cell)))
+            .fold(0, |hash, key| hash ^ key);
+        if !self.white_to_move {
+            hash ^= zobrist::black_to_move();
         }
+        for (right, held) in self.castling.iter().enumerate() {
+            if *held {
+                hash ^= zobrist::castling(right);
+            }
+        }
+        if let Some(cell) = self.en_passant {
+            hash ^= zobrist::en_passant(&cell);
+        }
+        hash
+    }
+
+    // A right lasts while neither its king nor its rook has moved or been taken.
+    fn castling_rights(&self) -> [bool; 4] {
+        let at_home = |name: PieceName, white: bool, cell: &str| self.get_live_piece_with_name(name, white)
+            .is_some_and(|piece| !piece.has_moved() && piece.get_curr_cell() == Some(Cell::new(cell)));
+        let mut rights = [false; 4];
+        for (right, (white, rook, king_cell, rook_cell)) in CASTLING.iter().enumerate() {
+            rights[right] = at_home(PieceName::King, *white, king_cell) && at_home(*rook, *white, rook_cell);
+        }
+        rights
     }
 
     pub fn get_piece_at_cell(&self, cell: &Cell) -> Option<&Box<dyn Piece>> {
@@ -159,29 +235,89 @@ impl Board {
         }).collect();
     }
 
-    fn capture_piece_at_cell(&mut self, cell: &Cell) {
-        if let Some(piece) = self.get_mut_piece_at_cell(&cell) {
-            piece.set_captured()
-        }
+    fn capture_piece_at_cell(&mut self, cell: &Cell) -> Option<PieceName> {
+        let piece = self.get_mut_piece_at_cell(cell)?;
+        piece.set_captured();
+        Some(piece.get_name())
+    }
+
+    // Returns the captured piece, if any
+    pub fn move_piece(&mut self, name: PieceName, white: bool, the_move: &Move) -> Option<PieceName> {
+        self.move_pieces(white, &[(name, the_move)])
     }
 
-    pub fn move_piece(&mut self, name: PieceName, white: bool, the_move: &Move) {
-        self.capture_piece_at_cell(&the_move.cell);        
+    // Moves several pieces in a single ply (castling), so every piece history
+    // still gets exactly one entry per ply.
+    pub fn move_pieces(&mut self, white: bool, moves: &[(PieceName, &Move)]) -> Option<PieceName> {
+        let mut captured = None;
+        let mut double_step = None;
+        for (name, the_move) in moves.iter() {
+            // A pawn taking en passant lands behind the pawn it takes
+            let en_passant = self.get_live_piece_with_name(*name, white)
+                .filter(|piece| piece.get_role() == Role::Pawn && Some(the_move.cell) == self.en_passant)
+                .and_then(|piece| piece.get_curr_cell())
+                .map(|from| Cell {file: the_move.cell.file, row: from.row});
+            let taken_cell = en_passant.unwrap_or(the_move.cell);
+            if let Some(piece) = self.get_piece_at_cell(&taken_cell) {
+                self.hash ^= zobrist::piece(piece.get_role(), piece.is_white(), &taken_cell);
+            }
+            captured = captured.or(self.capture_piece_at_cell(&taken_cell));
+
+            let piece_to_move = self.get_mut_live_piece_with_name(*name, white).expect("unable to move piece");
+            let (role, from) = (piece_to_move.get_role(), piece_to_move.get_curr_cell().unwrap());
+            piece_to_move.move_(Some(the_move));
+            if let Some(promotion) = the_move.promotion.filter(|_| role == Role::Pawn) {
+                piece_to_move.promote(promotion);
+            }
+            self.hash ^= zobrist::piece(role, white, &from) ^ zobrist::piece(piece_to_move.get_role(), white, &the_move.cell);
+            if role == Role::Pawn && (the_move.cell.row - from.row).abs() == 2 {
+                double_step = Some((Cell {file: from.file, row: (from.row + the_move.cell.row) / 2}, the_move.cell));
+            }
+        }
 
-        let piece_to_move = self.get_mut_live_piece_with_name(name, white).expect("unable to move piece");
-        piece_to_move.move_(Some(the_move));
-        
         for piece in self.pieces.iter_mut().filter(|piece| piece.is_live()) {
-            if piece.get_name() != name || piece.is_white() != white {
+            let moved = moves.iter().any(|(name, _)| piece.get_name() == *name && piece.is_white() == white);
+            if !moved {
                 piece.move_(None);
             }
         }
+        self.update_state(white, double_step);
+        captured
+    }
+
+    // Side to move, castling rights and en passant after a ply, double_step is the cell a pawn
+    // skipped and the one it landed on.
+    fn update_state(&mut self, white: bool, double_step: Option<(Cell, Cell)>) {
+        if self.white_to_move == white {
+            self.hash ^= zobrist::black_to_move();
+            self.white_to_move = !white;
+        }
+
+        let rights = self.castling_rights();
+        for (right, held) in rights.iter().enumerate() {
+            if *held != self.castling[right] {
+                self.hash ^= zobrist::castling(right);
+            }
+        }
+        self.castling = rights;
+
+        // Only when an enemy pawn could take, whether or not the capture would be legal
+        let en_passant = double_step.filter(|(_, landed)| [-1, 1].iter()
+            .filter_map(|offset| Cell::new_from_cell(landed, *offset, 0))
+            .filter_map(|cell| self.get_piece_at_cell(&cell))
+            .any(|piece| piece.get_role() == Role::Pawn && piece.is_white() != white))
+            .map(|(skipped, _)| skipped);
+        for cell in self.en_passant.iter().chain(en_passant.iter()) {
+            self.hash ^= zobrist::en_passant(cell);
+        }
+        self.en_passant = en_passant;
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::game::Game;
 
     #[test]
     fn test_get_piece_at_cell() {
@@ -251,6 +387,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_move_pieces_in_one_ply() {
+        let mut board = Board::new();
+        board.capture_piece_at_cell(&Cell::new("f1"));
+        board.capture_piece_at_cell(&Cell::new("g1"));
+        board.move_pieces(true, &[(PieceName::King, &Move::new_with_cell_name("g1")), (PieceName::Krook, &Move::new_with_cell_name("f1"))]);
+
+        assert_eq!(board.get_piece_at_cell(&Cell::new("g1")).unwrap().get_name(), PieceName::King);
+        assert_eq!(board.get_piece_at_cell(&Cell::new("f1")).unwrap().get_name(), PieceName::Krook);
+        for piece in board.pieces.iter().filter(|piece| piece.is_live()) {
+            assert_eq!(piece.get_move_history().len(), 1);
+        }
+    }
+
     #[test]
     fn test_remove_piece() {
         let mut board = Board::new();
@@ -261,4 +411,85 @@ mod tests {
             None => assert!(true)
         }
     }
-}
\ No newline at end of file
+
+    fn board_after(moves: &[(bool, PieceName, &str)]) -> Board {
+        let mut board = Board::new();
+        for (white, name, cell) in moves.iter() {
+            board.move_piece(*name, *white, &Move::new_with_cell_name(cell));
+            assert_eq!(board.zobrist(), board.compute_hash());
+        }
+        board
+    }
+
+    #[test]
+    fn test_zobrist_transposition() {
+        let knights = board_after(&[(true, PieceName::Kknight, "f3"), (false, PieceName::Kknight, "f6"), (true, PieceName::Qknight, "c3"), (false, PieceName::Qknight, "c6")]);
+        let other_order = board_after(&[(true, PieceName::Qknight, "c3"), (false, PieceName::Qknight, "c6"), (true, PieceName::Kknight, "f3"), (false, PieceName::Kknight, "f6")]);
+        assert_eq!(knights.zobrist(), other_order.zobrist());
+        assert!(knights.same_position(&other_order));
+        assert_ne!(knights.zobrist(), Board::new().zobrist());
+
+        // Each knight where the other one stands is still the same position
+        let swapped = board_after(&[(true, PieceName::Qknight, "f3"), (false, PieceName::Kknight, "f6"), (true, PieceName::Kknight, "c3"), (false, PieceName::Qknight, "c6")]);
+        assert!(knights.same_position(&swapped));
+
+        // There and back again repeats the start, with black to move it does not
+        let back = board_after(&[(true, PieceName::Kknight, "f3"), (false, PieceName::Kknight, "f6"), (true, PieceName::Kknight, "g1"), (false, PieceName::Kknight, "g8")]);
+        assert!(back.same_position(&Board::new()));
+        let half_way = board_after(&[(true, PieceName::Kknight, "f3"), (false, PieceName::Kknight, "f6"), (true, PieceName::Kknight, "g1")]);
+        assert!(!half_way.white_to_move());
+        assert!(!half_way.same_position(&board_after(&[(true, PieceName::Kknight, "f3")])));
+    }
+
+    #[test]
+    fn test_zobrist_castling_rights() {
+        // The rooks are home again but can no longer castle
+        let rooks = board_after(&[(true, PieceName::Kknight, "f3"), (false, PieceName::Kknight, "f6"),
+            (true, PieceName::Krook, "g1"), (false, PieceName::Krook, "g8"), (true, PieceName::Krook, "h1"), (false, PieceName::Krook, "h8"),
+            (true, PieceName::Kknight, "g1"), (false, PieceName::Kknight, "g8")]);
+        assert_eq!(rooks.castling, [false, true, false, true]);
+        assert_ne!(rooks.zobrist(), Board::new().zobrist());
+        assert!(!rooks.same_position(&Board::new()));
+    }
+
+    #[test]
+    fn test_zobrist_en_passant() {
+        // d5 next to the e5 pawn can be taken en passant, a move later it can't
+        let double_step = board_after(&[(true, PieceName::Epawn, "e4"), (false, PieceName::Apawn, "a6"), (true, PieceName::Epawn, "e5"), (false, PieceName::Dpawn, "d5")]);
+        let single_steps = board_after(&[(true, PieceName::Epawn, "e4"), (false, PieceName::Dpawn, "d5"), (true, PieceName::Epawn, "e5"), (false, PieceName::Apawn, "a6")]);
+        assert_eq!(double_step.en_passant, Some(Cell::new("d6")));
+        assert_eq!(single_steps.en_passant, None);
+        assert_ne!(double_step.zobrist(), single_steps.zobrist());
+        assert!(!double_step.same_position(&single_steps));
+
+        let mut later = double_step;
+        later.move_piece(PieceName::Kknight, true, &Move::new_with_cell_name("f3"));
+        let mut other_later = single_steps;
+        other_later.move_piece(PieceName::Kknight, true, &Move::new_with_cell_name("f3"));
+        assert!(later.same_position(&other_later));
+        assert_eq!(later.zobrist(), later.compute_hash());
+    }
+
+    #[test]
+    fn test_zobrist_capture() {
+        let board = board_after(&[(true, PieceName::Epawn, "e4"), (false, PieceName::Dpawn, "d5"), (true, PieceName::Epawn, "d5")]);
+        assert_eq!(board.pieces.iter().filter(|piece| piece.is_live()).count(), 31);
+    }
+
+    #[test]
+    fn test_zobrist_promotion() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["h4", "g5", "hxg5", "h6", "gxh6", "Bg7", "hxg7", "Nf6", "gxh8=N"]));
+        assert_eq!(game.board.get_piece_at_cell(&Cell::new("h8")).unwrap().get_role(), Role::Knight);
+        assert_eq!(game.board.zobrist(), game.board.compute_hash());
+    }
+
+    #[test]
+    fn test_en_passant_capture() {
+        let board = board_after(&[(true, PieceName::Epawn, "e4"), (false, PieceName::Dpawn, "d5"), (true, PieceName::Epawn, "e5"),
+            (false, PieceName::Fpawn, "f5"), (true, PieceName::Epawn, "f6")]);
+        assert_eq!(board.pieces.iter().filter(|piece| piece.is_live()).count(), 31);
+        assert!(board.get_piece_at_cell(&Cell::new("f5")).is_none());
+        assert_eq!(board.get_piece_at_cell(&Cell::new("f6")).unwrap().get_name(), PieceName::Epawn);
+        assert_eq!(board.en_passant, None);
+    }
+}
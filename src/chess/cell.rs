@@ -8,7 +8,6 @@ pub struct Cell {
 }
 
 impl Cell {
-    #[allow(dead_code)]
     pub fn new(cell_name: &str) -> Cell {
         Cell {
             file: cell_name.chars().nth(0).unwrap(),
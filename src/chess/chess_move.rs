@@ -1,6 +1,7 @@
 use super::types::{Role, MoveType, role_char_to_role};
 use super::cell::Cell;
 
+use std::error::Error;
 use std::fmt;
 
 #[derive(Clone, Debug)]
@@ -9,7 +10,10 @@ pub struct Move {
     pub move_type: MoveType,
     pub file_hint: char,
     check: bool,
-    pub cell: Cell
+    mate: bool,
+    pub cell: Cell,
+    // What a pawn reaching the last row becomes
+    pub promotion: Option<Role>
 }
 
 impl Move {
@@ -19,7 +23,9 @@ impl Move {
             move_type: MoveType::None,
             file_hint: ' ',
             check: false,
-            cell: Cell {file: ' ', row: 0}
+            mate: false,
+            cell: Cell {file: ' ', row: 0},
+            promotion: None
         }
     }
 
@@ -29,7 +35,9 @@ impl Move {
             move_type: MoveType::None,
             file_hint: ' ',
             check: false,
-            cell: Cell::new(cell_name)
+            mate: false,
+            cell: Cell::new(cell_name),
+            promotion: None
         }
     }
 
@@ -39,14 +47,17 @@ impl Move {
             move_type: MoveType::None,
             file_hint: ' ',
             check: false,
-            cell: cell
+            mate: false,
+            cell,
+            promotion: None
         }
     }
 
     fn parse_castle_move(move_str: &str) -> Move {
         let clean_move_str: String = move_str.chars().filter(|&x| x != '+' && x != '#').collect();
         let mut the_move: Move = Move::new();
-        the_move.check = move_str.contains("+");
+        the_move.check = move_str.contains("+") || move_str.contains("#");
+        the_move.mate = move_str.contains("#");
         if clean_move_str == "O-O" {
             the_move.role = Role::King;
             the_move.move_type = MoveType::CastleKing;
@@ -59,15 +70,16 @@ impl Move {
         return the_move;
     }
 
-    // TODO: promotion ("=") and check mate ("#")
-    fn parse_non_castle_move(move_str: &str) -> Move {
+    // Promotion as in e8=Q, or e8Q as some exports write it
+    fn parse_non_castle_move(move_str: &str) -> Option<Move> {
         if move_str == "" {
-            return Move::new();
+            return Some(Move::new());
         }
 
         let clean_move_str: String = move_str.chars().filter(|&x| x != 'x' && x != '+' && x != '#').collect();
         let mut the_move: Move = Move::new();
-        the_move.check = move_str.contains("+");
+        the_move.check = move_str.contains("+") || move_str.contains("#");
+        the_move.mate = move_str.contains("#");
         if move_str.contains("x") {
             the_move.move_type = MoveType::Take;
         }
@@ -75,8 +87,8 @@ impl Move {
             the_move.move_type = MoveType::Simple;
         }
         
-        let re = regex::Regex::new(r"([RNBQK]?)([a-h]?)([a-h])(\d)").unwrap();
-        let caps = re.captures(&clean_move_str).expect("Failed to capture moves");
+        let re = regex::Regex::new(r"([RNBQK]?)([a-h]?)([a-h])(\d)=?([QRBN])?").unwrap();
+        let caps = re.captures(&clean_move_str)?;
         the_move.role = caps.get(1).map_or(Role::Pawn, |m| role_char_to_role(m.as_str()));
 
         if let Some(m) = caps.get(2) {
@@ -90,21 +102,64 @@ impl Move {
             file: caps.get(3).map_or(' ', |m| m.as_str().chars().next().unwrap()),
             row: caps.get(4).map_or(0, |m| m.as_str().parse::<i32>().unwrap())
         };
-        return the_move;
+        the_move.promotion = caps.get(5).map(|m| role_char_to_role(m.as_str()));
+        Some(the_move)
     }
 
+    // Panics on text that isn't a move, see try_parse
     pub fn parse(move_str: &str) -> Move {
+        Move::try_parse(move_str).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_parse(move_str: &str) -> Result<Move, Box<dyn Error>> {
         if move_str.contains("O-O") {
-            return Move::parse_castle_move(move_str);
+            Ok(Move::parse_castle_move(move_str))
         }
         else {
-            return Move::parse_non_castle_move(move_str);
+            Ok(Move::parse_non_castle_move(move_str).ok_or(format!("{} is not a move", move_str))?)
         }
     }
 
+    // Check mate counts as a check
+    pub fn is_check(&self) -> bool {
+        self.check
+    }
+
+    pub fn is_mate(&self) -> bool {
+        self.mate
+    }
+
     pub fn parse_moves(moves: &[&str]) -> Vec<Move> {
         moves.iter().map(|x| Move::parse(x)).collect()
     }
+
+    pub fn try_parse_moves(moves: &[&str]) -> Result<Vec<Move>, Box<dyn Error>> {
+        moves.iter().map(|x| Move::try_parse(x)).collect()
+    }
+}
+
+// Back in standard algebraic notation, as far as the move knows it: Nbd2, exd5, O-O+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let check = if self.mate {"#"} else if self.check {"+"} else {""};
+        match self.move_type {
+            MoveType::CastleKing => return write!(f, "O-O{}", check),
+            MoveType::CastleQueen => return write!(f, "O-O-O{}", check),
+            _ => {}
+        }
+        let letter = |role: Role| match role {
+            Role::Pawn => "",
+            Role::Knight => "N",
+            Role::Bishop => "B",
+            Role::Rook => "R",
+            Role::Queen => "Q",
+            Role::King => "K"
+        };
+        let hint = if self.file_hint == ' ' {String::new()} else {self.file_hint.to_string()};
+        let take = if self.move_type == MoveType::Take {"x"} else {""};
+        let promotion = self.promotion.map_or(String::new(), |role| format!("={}", letter(role)));
+        write!(f, "{}{}{}{}{}{}{}", letter(self.role), hint, take, self.cell.file, self.cell.row, promotion, check)
+    }
 }
 
 #[cfg(test)]
@@ -148,6 +203,17 @@ mod tests {
         assert_eq!(the_move.cell, Cell {file: 'd', row: 4});
     }
 
+    #[test]
+    fn test_parse_move_with_check_mate() {
+        let the_move = Move::parse("Qxg2#");
+        assert_eq!(the_move.role, Role::Queen);
+        assert_eq!(the_move.move_type, MoveType::Take);
+        assert!(the_move.is_check());
+        assert!(the_move.is_mate());
+        assert_eq!(the_move.cell, Cell::new("g2"));
+        assert!(!Move::parse("d4+").is_mate());
+    }
+
     #[test]
     fn test_parse_bishop_move() {
         let the_move = Move::parse("Be4");
@@ -196,4 +262,31 @@ mod tests {
         assert_eq!(the_move.check, false);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_try_parse() {
+        assert!(Move::try_parse("Nf3").is_ok());
+        assert_eq!(Move::try_parse("Zz").unwrap_err().to_string(), "Zz is not a move");
+        assert!(Move::try_parse_moves(&["e4", "e5", "??"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_promotion() {
+        let the_move = Move::parse("exd8=Q+");
+        assert_eq!(the_move.role, Role::Pawn);
+        assert_eq!(the_move.move_type, MoveType::Take);
+        assert_eq!(the_move.file_hint, 'e');
+        assert_eq!(the_move.cell, Cell::new("d8"));
+        assert_eq!(the_move.promotion, Some(Role::Queen));
+        assert!(the_move.is_check());
+        assert_eq!(Move::parse("a1R").promotion, Some(Role::Rook));
+        assert_eq!(Move::parse("a1").promotion, None);
+        assert_eq!(Move::parse("Qa1").promotion, None);
+    }
+
+    #[test]
+    fn test_display() {
+        let moves = ["e4", "Nbd2", "exd5", "Qxg2#", "Bb5+", "O-O", "O-O-O+", "e8=Q", "bxa1=N+"];
+        let shown: Vec<String> = moves.iter().map(|the_move| Move::parse(the_move).to_string()).collect();
+        assert_eq!(shown, moves);
+    }
+}
@@ -0,0 +1,176 @@
+use super::board::Board;
+use super::cell::Cell;
+use super::types::Role;
+
+const DOUBLED_PAWN: i32 = -20;
+const ISOLATED_PAWN: i32 = -15;
+const PASSED_PAWN: i32 = 20;
+const PASSED_PAWN_PER_ROW: i32 = 10;
+const KING_SHIELD_PAWN: i32 = 10;
+const KING_OPEN_FILE: i32 = -25;
+
+// Mates are worth more than any material
+const MATE: i32 = 10_000;
+
+// An engine's opinion of the position after a ply, from Lichess' [%eval 0.17] and [%eval #-3]
+// comments. Positive when white is better, mates count the moves to mate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EngineEval {
+    Centipawns(i32),
+    Mate(i32)
+}
+
+impl EngineEval {
+    pub fn centipawns(&self) -> i32 {
+        match self {
+            EngineEval::Centipawns(centipawns) => *centipawns,
+            EngineEval::Mate(moves) if *moves < 0 => -MATE,
+            EngineEval::Mate(_) => MATE
+        }
+    }
+}
+
+// A rough static evaluation in centipawns, positive when white is better.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Evaluation {
+    pub material: i32,
+    pub pawn_structure: i32,
+    pub king_safety: i32
+}
+
+impl Evaluation {
+    pub fn new(board: &Board) -> Evaluation {
+        let side = |white: bool| -> (i32, i32, i32) {
+            let pawns = Evaluation::pawn_cells(board, white);
+            let enemy_pawns = Evaluation::pawn_cells(board, !white);
+            (
+                Evaluation::material(board, white),
+                Evaluation::pawn_structure(&pawns, &enemy_pawns, white),
+                Evaluation::king_safety(board, &pawns, white)
+            )
+        };
+        let (white, black) = (side(true), side(false));
+        Evaluation {
+            material: white.0 - black.0,
+            pawn_structure: white.1 - black.1,
+            king_safety: white.2 - black.2
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.material + self.pawn_structure + self.king_safety
+    }
+
+    fn role_value(role: Role) -> i32 {
+        match role {
+            Role::Pawn => 100,
+            Role::Knight => 300,
+            Role::Bishop => 300,
+            Role::Rook => 500,
+            Role::Queen => 900,
+            Role::King => 0
+        }
+    }
+
+    fn material(board: &Board, white: bool) -> i32 {
+        board.pieces.iter()
+            .filter(|piece| piece.is_live() && piece.is_white() == white)
+            .map(|piece| Evaluation::role_value(piece.get_role()))
+            .sum()
+    }
+
+    fn pawn_cells(board: &Board, white: bool) -> Vec<Cell> {
+        board.get_live_pieces_with_role(Role::Pawn, white).iter()
+            .filter_map(|pawn| pawn.get_curr_cell())
+            .collect()
+    }
+
+    fn files_apart(first: char, second: char) -> i32 {
+        (first as i32 - second as i32).abs()
+    }
+
+    // Doubled and isolated pawns are weaknesses, passed pawns get stronger as they advance.
+    fn pawn_structure(pawns: &[Cell], enemy_pawns: &[Cell], white: bool) -> i32 {
+        let mut score = 0;
+        for pawn in pawns.iter() {
+            if pawns.iter().filter(|other| other.file == pawn.file).count() > 1 {
+                // Counted once for each pawn on the file, so halve it
+                score += DOUBLED_PAWN / 2;
+            }
+            if !pawns.iter().any(|other| Evaluation::files_apart(other.file, pawn.file) == 1) {
+                score += ISOLATED_PAWN;
+            }
+            let blocked = enemy_pawns.iter().any(|enemy| {
+                Evaluation::files_apart(enemy.file, pawn.file) <= 1
+                    && if white {enemy.row > pawn.row} else {enemy.row < pawn.row}
+            });
+            if !blocked {
+                let advanced = if white {pawn.row - 2} else {7 - pawn.row};
+                score += PASSED_PAWN + PASSED_PAWN_PER_ROW * advanced;
+            }
+        }
+        score
+    }
+
+    // Pawns right in front of the king shelter it, no pawn on its file leaves it open.
+    fn king_safety(board: &Board, pawns: &[Cell], white: bool) -> i32 {
+        let king = match board.get_live_pieces_with_role(Role::King, white).first().and_then(|king| king.get_curr_cell()) {
+            Some(cell) => cell,
+            None => return 0
+        };
+        let forward = if white {1} else {-1};
+        let shield = pawns.iter().filter(|pawn| {
+            let rows_ahead = (pawn.row - king.row) * forward;
+            Evaluation::files_apart(pawn.file, king.file) <= 1 && (1..=2).contains(&rows_ahead)
+        }).count() as i32;
+        let mut score = shield * KING_SHIELD_PAWN;
+        if !pawns.iter().any(|pawn| pawn.file == king.file) {
+            score += KING_OPEN_FILE;
+        }
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{Game, Move};
+
+    fn evaluate(moves: &[&str]) -> Evaluation {
+        Evaluation::new(&Game::new_with_moves(&Move::parse_moves(moves)).board)
+    }
+
+    #[test]
+    fn test_starting_position_is_equal() {
+        assert_eq!(Evaluation::new(&Board::new()), Evaluation::default());
+        assert_eq!(evaluate(&["e4", "e5", "Nf3", "Nc6"]).total(), 0);
+    }
+
+    #[test]
+    fn test_material() {
+        let evaluation = evaluate(&["e4", "d5", "exd5", "Qxd5", "Nc3"]);
+        assert_eq!(evaluation.material, 0);
+        let evaluation = evaluate(&["e4", "d5", "exd5", "Nf6", "Nc3", "Nxd5", "Nxd5"]);
+        assert_eq!(evaluation.material, 300);
+    }
+
+    #[test]
+    fn test_pawn_structure() {
+        // White's pawns on d2 and d5 are doubled
+        let evaluation = evaluate(&["e4", "d5", "exd5", "Nf6"]);
+        assert_eq!(evaluation.pawn_structure, DOUBLED_PAWN);
+        assert_eq!(evaluation.material, 100);
+
+        // Black's pawn on d6 is isolated
+        let evaluation = evaluate(&["d4", "e5", "dxe5", "d6", "exd6", "cxd6"]);
+        assert_eq!(evaluation.pawn_structure, -ISOLATED_PAWN);
+    }
+
+    #[test]
+    fn test_king_safety() {
+        let shielded = evaluate(&["Nf3", "Nf6", "g3", "g6", "Bg2", "Bg7", "O-O", "e5"]);
+        assert!(shielded.king_safety > 0);
+        let open = evaluate(&["e4", "e5", "Ke2"]);
+        assert!(open.king_safety < 0);
+    }
+}
@@ -1,37 +1,42 @@
-use super::types::{MoveType, PieceName};
+use super::types::{MoveType, PieceName, Role};
 use super::chess_move::Move;
 use super::cell::Cell;
 use super::board::Board;
+use super::ply::Ply;
+use super::evaluation::{Evaluation, EngineEval};
 
 use std::error::Error;
-
-#[cfg(test)]
-use super::types::{Role};
+use std::time::Duration;
 
 pub struct Game {
-    pub board: Board
+    pub board: Board,
+    pub plies: Vec<Ply>
 }
 
 impl Game {
     pub fn new() -> Game {
         Game {
-            board: Board::new()
+            board: Board::new(),
+            plies: Vec::new()
         }
     }
 
+    // Panics on a move that can't be played, see try_new_with_moves
     pub fn new_with_moves(moves: &[Move]) -> Game {
-        let mut game = Game {
-            board: Board::new()
-        };
-        game.load_moves(moves);
-        game
+        Game::try_new_with_moves(moves).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_new_with_moves(moves: &[Move]) -> Result<Game, Box<dyn Error>> {
+        let mut game = Game::new();
+        game.load_moves(moves)?;
+        Ok(game)
     }
 
     #[cfg(test)]
     pub fn new_test(role: Role) -> Game {
         match role {
-            Role::Rook => Game {board: Board::new_rook_test()},
-            Role::Bishop => Game {board: Board::new_bishop_test()},
+            Role::Rook => Game {board: Board::new_rook_test(), plies: Vec::new()},
+            Role::Bishop => Game {board: Board::new_bishop_test(), plies: Vec::new()},
             _ => panic!("no test board for specified role")
         }
     }
@@ -39,14 +44,15 @@ impl Game {
     fn get_piece_for_move(&self, white: bool, the_move: &Move) -> Result<PieceName, Box<dyn Error>> {
         let role = the_move.role;
         let pieces_with_role = self.board.get_live_pieces_with_role(role, white);
+        let color = if white {"white"} else {"black"};
         if the_move.file_hint != ' ' {
-            let piece = pieces_with_role.iter().filter(|piece| {
+            let piece = pieces_with_role.iter().find(|piece| {
                 if let Some(curr_cell) = piece.get_curr_cell() {
                     curr_cell.file == the_move.file_hint
                 } else {
                     false
                 }
-            }).next().unwrap();
+            }).ok_or(format!("{} has no piece on the {} file to play {}", color, the_move.file_hint, the_move))?;
             return Ok(piece.get_name());
         }
         else {
@@ -57,39 +63,106 @@ impl Game {
             }
         }
 
-        return Err("Did not find piece")?;
+        return Err(format!("{} has no piece that can play {}", color, the_move))?;
     }
 
-    fn add_move(&mut self, white: bool, the_move: &Move) {
+    fn add_move(&mut self, white: bool, the_move: &Move) -> Result<(), Box<dyn Error>> {
         if the_move.move_type == MoveType::CastleKing || the_move.move_type == MoveType::CastleQueen {
-            let rook_name = if the_move.move_type == MoveType::CastleKing {PieceName::Krook} else {PieceName::Qrook};
+            let king_side = the_move.move_type == MoveType::CastleKing;
+            let color = if white {"white"} else {"black"};
             let row = if white {1} else {8};
+            if !self.board.can_castle(white, king_side) {
+                Err(format!("{} can no longer play {}", color, the_move))?;
+            }
+            let between: &[char] = if king_side {&['f', 'g']} else {&['b', 'c', 'd']};
+            if between.iter().any(|file| self.board.get_piece_at_cell(&Cell {file: *file, row}).is_some()) {
+                Err(format!("{} can't play {} with pieces in the way", color, the_move))?;
+            }
+            let rook_name = if the_move.move_type == MoveType::CastleKing {PieceName::Krook} else {PieceName::Qrook};
             let king_file = if the_move.move_type == MoveType::CastleKing {'g'} else {'c'};
             let rook_file = if the_move.move_type == MoveType::CastleKing {'f'} else {'d'};
 
-            self.board.move_piece(PieceName::King, white, &Move::new_with_cell_name(&format!("{}{}", king_file, row)));
-            self.board.move_piece(rook_name, white, &Move::new_with_cell_name(&format!("{}{}", rook_file, row)));
+            // Both moves keep the castle move type and check flag of the original move.
+            let mut king_move = the_move.clone();
+            king_move.cell = Cell {file: king_file, row};
+            let mut rook_move = the_move.clone();
+            rook_move.cell = Cell {file: rook_file, row};
+            self.board.move_pieces(white, &[(PieceName::King, &king_move), (rook_name, &rook_move)]);
+            self.plies.push(Ply {white, the_move: the_move.clone(), moved: vec![PieceName::King, rook_name], captured: None, think_time: None, clock: None, evaluation: Evaluation::new(&self.board), zobrist: self.board.zobrist(), engine_eval: None});
         }
         else {
-            let name = match self.get_piece_for_move(white, &the_move) {
-                Ok(name) => name,
-                Err(the_error) => {
-                    let color = if white {"white"} else {"black"};
-                    println!("Failed to get name for color: {} and move: {:?}", color, the_move);
-                    panic!("{}", the_error.to_string());
-                }
-            };
-            self.board.move_piece(name, white, &the_move);
+            let last_row = if white {8} else {1};
+            if the_move.promotion.is_some() && (the_move.role != Role::Pawn || the_move.cell.row != last_row) {
+                let color = if white {"white"} else {"black"};
+                Err(format!("{} can't promote with {}", color, the_move))?;
+            }
+            let name = self.get_piece_for_move(white, the_move)?;
+            self.move_named_piece(white, the_move, name);
         }
+        Ok(())
     }
 
-    fn add_move_pair(&mut self, white_move: &Move, black_move: &Move) {
-        self.add_move(true, white_move);
+    fn move_named_piece(&mut self, white: bool, the_move: &Move, name: PieceName) {
+        let captured = self.board.move_piece(name, white, the_move);
+        self.plies.push(Ply {white, the_move: the_move.clone(), moved: vec![name], captured, think_time: None, clock: None, evaluation: Evaluation::new(&self.board), zobrist: self.board.zobrist(), engine_eval: None});
+    }
+
+    // Moves as the Lichess streams send them, e.g. "e2e4", "e1g1" or "e7e8q".
+    // The piece on the from cell is the one moved, so there is nothing to disambiguate.
+    pub fn add_uci_move(&mut self, uci: &str) -> Result<(), Box<dyn Error>> {
+        let is_cell = |cell: &[char]| ('a'..='h').contains(&cell[0]) && ('1'..='8').contains(&cell[1]);
+        let chars: Vec<char> = uci.chars().collect();
+        if chars.len() < 4 || chars.len() > 5 || !is_cell(&chars[0..2]) || !is_cell(&chars[2..4]) {
+            Err(format!("Invalid UCI move {}", uci))?;
+        }
+        let promotion = match chars.get(4) {
+            Some('q') => Some(Role::Queen),
+            Some('r') => Some(Role::Rook),
+            Some('b') => Some(Role::Bishop),
+            Some('n') => Some(Role::Knight),
+            Some(_) => Err(format!("Invalid UCI move {}", uci))?,
+            None => None
+        };
+        let from = Cell::new(&uci[0..2]);
+        let to = Cell::new(&uci[2..4]);
+        let white = !matches!(self.plies.last(), Some(ply) if ply.white);
+        let (name, role) = match self.board.get_piece_at_cell(&from) {
+            Some(piece) if piece.is_white() == white => (piece.get_name(), piece.get_role()),
+            _ => Err(format!("No piece to move for {}", uci))?
+        };
+
+        let mut the_move = Move::new_with_cell(to);
+        the_move.role = role;
+        the_move.promotion = promotion;
+        let (x_diff, _) = from.get_cell_diff(&to);
+        the_move.move_type = if role == Role::King && x_diff.abs() == 2 {
+            if x_diff > 0 {MoveType::CastleKing} else {MoveType::CastleQueen}
+        } else if self.board.get_piece_at_cell(&to).is_some() || (role == Role::Pawn && x_diff != 0) {
+            MoveType::Take
+        } else {
+            MoveType::Simple
+        };
+
+        let last_row = if white {8} else {1};
+        if promotion.is_some() && (role != Role::Pawn || to.row != last_row) {
+            Err(format!("Can't promote with {}", uci))?;
+        }
+
+        match the_move.move_type {
+            MoveType::CastleKing | MoveType::CastleQueen => self.add_move(white, &the_move)?,
+            _ => self.move_named_piece(white, &the_move, name)
+        }
+        Ok(())
+    }
+
+    fn add_move_pair(&mut self, white_move: &Move, black_move: &Move) -> Result<(), Box<dyn Error>> {
+        self.add_move(true, white_move)?;
 
         // Black move might not exist if the last move of the game is white's move.
         if black_move.move_type != MoveType::None {
-            self.add_move(false, black_move); 
+            self.add_move(false, black_move)?;
         }
+        Ok(())
     }
 
     // Note: does not include starting cell
@@ -99,12 +172,35 @@ impl Game {
         cells
     }
 
-    pub fn load_moves(&mut self, moves: &[Move]) {
+    // think_times[n] is the time spent on ply n
+    pub fn set_think_times(&mut self, think_times: &[Option<Duration>]) {
+        for (ply, think_time) in self.plies.iter_mut().zip(think_times.iter()) {
+            ply.think_time = *think_time;
+        }
+    }
+
+    // clocks[n] is the time left after ply n
+    pub fn set_clocks(&mut self, clocks: &[Option<Duration>]) {
+        for (ply, clock) in self.plies.iter_mut().zip(clocks.iter()) {
+            ply.clock = *clock;
+        }
+    }
+
+    pub fn set_engine_evals(&mut self, evals: &[Option<EngineEval>]) {
+        for (ply, eval) in self.plies.iter_mut().zip(evals.iter()) {
+            ply.engine_eval = *eval;
+        }
+    }
+
+    // Stops at the first move that can't be played, the game keeps the plies before it.
+    pub fn load_moves(&mut self, moves: &[Move]) -> Result<(), Box<dyn Error>> {
         self.board = Board::new();
+        self.plies.clear();
         for (idx, the_move) in moves.iter().enumerate() {
             let white = idx % 2 == 0;
-            self.add_move(white, the_move);
+            self.add_move(white, the_move).map_err(|error| format!("Ply {}: {}", idx + 1, error))?;
         }
+        Ok(())
     }
 }
 
@@ -131,7 +227,7 @@ mod tests {
     #[test]
     fn test_get_piece_for_pawn_take() {
         let mut game = Game::new();
-        game.add_move_pair(&Move::parse("d4"), &Move::parse("e5"));
+        game.add_move_pair(&Move::parse("d4"), &Move::parse("e5")).unwrap();
         let name = game.get_piece_for_move(true, &Move::parse("dxe5")).unwrap();
         assert_eq!(name, PieceName::Dpawn);
     }
@@ -239,62 +335,62 @@ mod tests {
         // 1. d4 Nf6 2. Bf4 Nc6 3. e3 d5 4. Nf3 Bf5 5. Nbd2 e6 6. c3 Bd6 7. Bg5 h6 8. Bh4 g5 9. Bg3 Ne4 10. Nxe4 Bxe4 
         let mut game = Game::new();
 
-        game.add_move_pair(&Move::parse("d4"), &Move::parse("Nf6"));
+        game.add_move_pair(&Move::parse("d4"), &Move::parse("Nf6")).unwrap();
         game.board.dump();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Dpawn, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Kknight, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("d4"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("f6"));
 
-        game.add_move_pair(&Move::parse("Bf4"), &Move::parse("Nc6"));
+        game.add_move_pair(&Move::parse("Bf4"), &Move::parse("Nc6")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Qbishop, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Qknight, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("f4"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("c6"));
 
-        game.add_move_pair(&Move::parse("e3"), &Move::parse("d5"));
+        game.add_move_pair(&Move::parse("e3"), &Move::parse("d5")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Epawn, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Dpawn, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("e3"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("d5"));
 
-        game.add_move_pair(&Move::parse("Nf3"), &Move::parse("Bf5"));
+        game.add_move_pair(&Move::parse("Nf3"), &Move::parse("Bf5")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Kknight, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Qbishop, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("f3"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("f5"));
 
-        game.add_move_pair(&Move::parse("Nbd2"), &Move::parse("e6"));
+        game.add_move_pair(&Move::parse("Nbd2"), &Move::parse("e6")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Qknight, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Epawn, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("d2"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("e6"));
 
-        game.add_move_pair(&Move::parse("c3"), &Move::parse("Bd6"));
+        game.add_move_pair(&Move::parse("c3"), &Move::parse("Bd6")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Cpawn, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Kbishop, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("c3"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("d6"));
 
-        game.add_move_pair(&Move::parse("Bg5"), &Move::parse("h6"));
+        game.add_move_pair(&Move::parse("Bg5"), &Move::parse("h6")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Qbishop, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Hpawn, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("g5"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("h6"));
 
-        game.add_move_pair(&Move::parse("Bh4"), &Move::parse("g5"));
+        game.add_move_pair(&Move::parse("Bh4"), &Move::parse("g5")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Qbishop, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Gpawn, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("h4"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("g5"));
 
-        game.add_move_pair(&Move::parse("Bg3"), &Move::parse("Ne4"));
+        game.add_move_pair(&Move::parse("Bg3"), &Move::parse("Ne4")).unwrap();
         let white_piece = game.board.get_live_piece_with_name(PieceName::Qbishop, true).unwrap();
         let black_piece = game.board.get_live_piece_with_name(PieceName::Kknight, false).unwrap();
         assert_eq!(white_piece.get_curr_cell().unwrap(), Cell::new("g3"));
         assert_eq!(black_piece.get_curr_cell().unwrap(), Cell::new("e4"));
 
-        game.add_move_pair(&Move::parse("Nxe4"), &Move::parse("Bxe4"));
+        game.add_move_pair(&Move::parse("Nxe4"), &Move::parse("Bxe4")).unwrap();
         match game.board.get_live_piece_with_name(PieceName::Qknight, true) {
             Some(_piece) => panic!("This piece was just taken, did not expect to find it."),
             None => ()
@@ -339,4 +435,126 @@ mod tests {
         assert_eq!(white_q_bishop_history[18], Cell::new("g3"));
         assert_eq!(white_q_bishop_history[19], Cell::new("g3"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_castle_is_a_single_ply() {
+        let moves = Move::parse_moves(&["e4", "e5", "Nf3", "Nc6", "Bc4", "Bc5", "O-O+", "d6"]);
+        let game = Game::new_with_moves(&moves);
+        assert_eq!(game.plies.len(), 8);
+        assert_eq!(game.plies[6].moved, vec![PieceName::King, PieceName::Krook]);
+        assert!(game.plies[6].moved_piece(PieceName::Krook, true));
+        assert!(!game.plies[6].moved_piece(PieceName::Krook, false));
+
+        let king_history = game.get_piece_history(PieceName::King, true);
+        assert_eq!(king_history.len(), 8);
+        assert_eq!(king_history[6], Cell::new("g1"));
+        assert_eq!(game.get_piece_history(PieceName::Krook, true)[6], Cell::new("f1"));
+
+        let king = game.board.get_piece_with_name(PieceName::King, true);
+        let castle = king.get_move_history()[6].as_ref().unwrap();
+        assert_eq!(castle.move_type, MoveType::CastleKing);
+        assert!(castle.is_check());
+    }
+
+    #[test]
+    fn test_plies_record_captures() {
+        let moves = Move::parse_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3"]);
+        let mut game = Game::new_with_moves(&moves);
+        assert_eq!(game.plies.len(), 5);
+        assert!(game.plies[0].white);
+        assert!(!game.plies[1].white);
+        assert_eq!(game.plies[2].moved, vec![PieceName::Epawn]);
+        assert_eq!(game.plies[2].captured, Some(PieceName::Dpawn));
+        assert_eq!(game.plies[3].captured, Some(PieceName::Epawn));
+        assert_eq!(game.plies[4].captured, None);
+
+        game.set_think_times(&[None, Some(Duration::from_secs(3))]);
+        assert_eq!(game.plies[1].think_time, Some(Duration::from_secs(3)));
+        game.set_clocks(&[Some(Duration::from_secs(58))]);
+        assert_eq!(game.plies[0].clock, Some(Duration::from_secs(58)));
+        assert_eq!(game.plies[1].clock, None);
+
+        // White is a pawn up after exd5, until the engine says otherwise
+        assert_eq!(game.plies[2].advantage(), game.plies[2].evaluation.total());
+        game.set_engine_evals(&[None, None, Some(EngineEval::Centipawns(35)), Some(EngineEval::Mate(-2))]);
+        assert_eq!(game.plies[2].advantage(), 35);
+        assert!(game.plies[3].advantage() < -1000);
+        assert_eq!(game.plies[4].think_time, None);
+    }
+
+    #[test]
+    fn test_uci_moves_match_san() {
+        let san = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "f6"];
+        let uci = ["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5c6", "d7c6", "e1g1", "f7f6"];
+        let expected = Game::new_with_moves(&Move::parse_moves(&san));
+        let mut game = Game::new();
+        for the_move in uci.iter() {
+            game.add_uci_move(the_move).unwrap();
+        }
+        assert_eq!(game.plies.len(), expected.plies.len());
+        for (ply, expected_ply) in game.plies.iter().zip(expected.plies.iter()) {
+            assert_eq!(ply.moved, expected_ply.moved);
+            assert_eq!(ply.captured, expected_ply.captured);
+            assert_eq!(ply.the_move.move_type, expected_ply.the_move.move_type);
+        }
+        assert_eq!(game.get_piece_history(PieceName::Krook, true), expected.get_piece_history(PieceName::Krook, true));
+
+        // Black's piece on white's turn, then nonsense
+        assert!(game.add_uci_move("e5e4").is_err());
+        assert!(game.add_uci_move("castle").is_err());
+        assert_eq!(game.plies.len(), 10);
+    }
+
+    #[test]
+    fn test_promotion() {
+        let san = ["a4", "b5", "axb5", "a6", "bxa6", "Bb7", "axb7", "Nc6", "bxa8=Q", "e6", "Qxd8+", "Kxd8"];
+        let uci = ["a2a4", "b7b5", "a4b5", "a7a6", "b5a6", "c8b7", "a6b7", "b8c6", "b7a8q", "e7e6", "a8d8", "e8d8"];
+        let game = Game::new_with_moves(&Move::parse_moves(&san));
+        assert_eq!(game.plies[8].the_move.promotion, Some(Role::Queen));
+        assert_eq!(game.plies[8].captured, Some(PieceName::Qrook));
+        // The new queen is the a pawn, the one on d1 can't reach d8
+        assert_eq!(game.plies[10].moved, vec![PieceName::Apawn]);
+        assert_eq!(game.plies[10].captured, Some(PieceName::Queen));
+        assert_eq!(game.plies[11].captured, Some(PieceName::Apawn));
+        let pawn = game.board.get_piece_with_name(PieceName::Apawn, true);
+        assert_eq!(pawn.get_role(), Role::Queen);
+        assert_eq!(pawn.get_role_after(8), Role::Pawn);
+        assert_eq!(pawn.get_role_after(9), Role::Queen);
+
+        let mut uci_game = Game::new();
+        for the_move in uci.iter() {
+            uci_game.add_uci_move(the_move).unwrap();
+        }
+        let zobrists = |game: &Game| game.plies.iter().map(|ply| ply.zobrist).collect::<Vec<u64>>();
+        assert_eq!(zobrists(&uci_game), zobrists(&game));
+        assert_eq!(uci_game.plies[8].the_move.promotion, Some(Role::Queen));
+
+        assert!(Game::new().add_uci_move("e2e4x").is_err());
+        assert_eq!(Game::try_new_with_moves(&Move::parse_moves(&["e4=Q"])).err().unwrap().to_string(), "Ply 1: white can't promote with e4=Q");
+    }
+
+    #[test]
+    fn test_en_passant() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "d5", "e5", "f5", "exf6"]));
+        assert_eq!(game.plies[4].captured, Some(PieceName::Fpawn));
+        assert!(game.board.get_live_piece_with_name(PieceName::Fpawn, false).is_none());
+        assert_eq!(game.board.pieces.iter().filter(|piece| piece.is_live()).count(), 31);
+    }
+
+    #[test]
+    fn test_moves_that_cant_be_played() {
+        let error = Game::try_new_with_moves(&Move::parse_moves(&["e4", "Ke5"])).err().unwrap();
+        assert_eq!(error.to_string(), "Ply 2: black has no piece that can play Ke5");
+        let error = Game::try_new_with_moves(&Move::parse_moves(&["e4", "e5", "Nf3", "Nc6", "O-O"])).err().unwrap();
+        assert_eq!(error.to_string(), "Ply 5: white can't play O-O with pieces in the way");
+        let error = Game::try_new_with_moves(&Move::parse_moves(&["e4", "e5", "Ke2", "Nc6", "Ke1", "Nf6", "Nf3", "Bc5", "Bc4", "d6", "O-O"])).err().unwrap();
+        assert_eq!(error.to_string(), "Ply 11: white can no longer play O-O");
+        let error = Game::try_new_with_moves(&Move::parse_moves(&["e4", "d5", "Nhf3"])).err().unwrap();
+        assert_eq!(error.to_string(), "Ply 3: white has no piece on the h file to play Nhf3");
+
+        let mut game = Game::new();
+        assert!(game.load_moves(&Move::parse_moves(&["d4", "d5", "Qd3", "Qd6", "Qxd5"])).is_err());
+        assert_eq!(game.plies.len(), 4);
+        assert!(Game::try_new_with_moves(&Move::parse_moves(&["e4", "e5", "Nf3"])).is_ok());
+    }
+}
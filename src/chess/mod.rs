@@ -3,10 +3,20 @@ pub mod board;
 pub mod chess_move;
 pub mod cell;
 pub mod game;
+pub mod ply;
+pub mod evaluation;
 pub mod piece;
+pub mod render;
+pub mod stats;
+pub mod zobrist;
 
 pub use game::Game as Game;
+pub use board::Board as Board;
+pub use ply::Ply as Ply;
+pub use evaluation::Evaluation as Evaluation;
+pub use evaluation::EngineEval as EngineEval;
 pub use types::PieceName as PieceName;
 pub use cell::Cell as Cell;
 pub use chess_move::Move as Move;
+pub use stats::GameStats as GameStats;
 
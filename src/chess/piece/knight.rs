@@ -46,17 +46,20 @@ impl Knight {
     }
 
     fn get_valid_cells(&self, board: &Board) -> Vec<Cell> {
+        Knight::valid_knight_cells(board, &self.get_curr_cell().unwrap())
+    }
+
+    pub fn valid_knight_cells(board: &Board, curr_cell: &Cell) -> Vec<Cell> {
         let mut valid_cells: Vec<Cell> = Vec::new();
-        let is_white = self.is_white();
-        let curr_cell = self.get_curr_cell().unwrap();
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, 1, 2), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, 2, 1), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, 2, -1), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, 1, -2), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, -1, -2), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, -2, -1), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, -2, 1), &board, &mut valid_cells, is_white);
-        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(&curr_cell, -1, 2), &board, &mut valid_cells, is_white);
+        let is_white = board.get_piece_at_cell(curr_cell).unwrap().is_white();
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, 1, 2), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, 2, 1), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, 2, -1), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, 1, -2), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, -1, -2), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, -2, -1), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, -2, 1), board, &mut valid_cells, is_white);
+        piece_utils::attempt_to_add_as_valid_cell(Cell::new_from_cell(curr_cell, -1, 2), board, &mut valid_cells, is_white);
 
         valid_cells
     }
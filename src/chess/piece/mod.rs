@@ -44,6 +44,9 @@ impl PieceStateTrait for PieceState {
     fn set_captured(&mut self) {
         self.cell = None
     }
+    fn promote(&mut self, role: Role) {
+        self.role = role
+    }
 }
 
 pub trait PieceStateTrait {
@@ -55,6 +58,7 @@ pub trait PieceStateTrait {
     fn has_moved(&self) -> bool;
     fn get_move_history(&self) -> &[Option<Move>];
     fn set_captured(&mut self);
+    fn promote(&mut self, role: Role);
 }
 
 
@@ -87,6 +91,20 @@ pub trait Piece {
     fn set_captured(&mut self) {
         self.get_mut_state().set_captured()
     }
+    fn promote(&mut self, role: Role) {
+        self.get_mut_state().promote(role)
+    }
+
+    // The role after a number of plies, a promoted pawn is a pawn until its promotion
+    fn get_role_after(&self, plies: usize) -> Role {
+        let promotion = |history: &[Option<Move>]| history.iter().rev().flatten().find_map(|the_move| the_move.promotion);
+        let history = self.get_move_history();
+        match promotion(&history[..plies.min(history.len())]) {
+            Some(role) => role,
+            None if promotion(history).is_some() => Role::Pawn,
+            None => self.get_role()
+        }
+    }
 
     fn get_char_representation(&self) -> char;
     fn get_state(&self) -> Box<&dyn PieceStateTrait>;
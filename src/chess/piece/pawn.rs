@@ -1,6 +1,6 @@
 use super::super::types::{PieceName, Role};
 use super::super::cell::Cell;
-use super::{Piece, PieceState, PieceStateTrait};
+use super::{Piece, PieceState, PieceStateTrait, Bishop, Knight, Rook};
 use super::super::board::Board;
 use super::super::chess_move::Move;
 
@@ -11,10 +11,31 @@ pub struct Pawn {
 impl Piece for Pawn {
     fn get_state(&self) -> Box<&dyn PieceStateTrait> {Box::new(&self.state)}
     fn get_mut_state(&mut self) -> Box<&mut dyn PieceStateTrait> {Box::new(&mut self.state)}
-    fn get_char_representation(&self) -> char {if self.is_white() {'P'} else {'p'}}
+    fn get_char_representation(&self) -> char {
+        let letter = match self.get_role() {
+            Role::Knight => 'N',
+            Role::Bishop => 'B',
+            Role::Rook => 'R',
+            Role::Queen => 'Q',
+            _ => 'P'
+        };
+        if self.is_white() {letter} else {letter.to_ascii_lowercase()}
+    }
+    // Once promoted it keeps its name and history but moves as its new role
     fn is_valid_move(&self, board: &Board, the_move: &Move) -> bool {
-        let valid_cells = self.get_valid_cells(board);
-        return valid_cells.contains(&the_move.cell);
+        let curr_cell = self.get_curr_cell().unwrap();
+        let valid_cells = match self.get_role() {
+            Role::Knight => Knight::valid_knight_cells(board, &curr_cell),
+            Role::Bishop => Bishop::valid_bishop_cells(board, &curr_cell),
+            Role::Rook => Rook::valid_rook_cells(board, &curr_cell),
+            Role::Queen => {
+                let mut valid_cells = Bishop::valid_bishop_cells(board, &curr_cell);
+                valid_cells.append(&mut Rook::valid_rook_cells(board, &curr_cell));
+                valid_cells
+            },
+            _ => self.get_valid_cells(board)
+        };
+        valid_cells.contains(&the_move.cell)
     }
     fn first_cell(&self) -> Cell {
         Pawn::init_cell(self.is_white(), self.get_name())
@@ -0,0 +1,34 @@
+use super::types::PieceName;
+use super::chess_move::Move;
+use super::evaluation::{Evaluation, EngineEval};
+
+use std::time::Duration;
+
+// A single half move of the game, white's plies have even indexes.
+#[derive(Clone, Debug)]
+pub struct Ply {
+    pub white: bool,
+    pub the_move: Move,
+    // Pieces moved in this ply, king and rook when castling
+    pub moved: Vec<PieceName>,
+    pub captured: Option<PieceName>,
+    pub think_time: Option<Duration>,
+    // Time left on the mover's clock after the ply
+    pub clock: Option<Duration>,
+    // Position after the ply
+    pub evaluation: Evaluation,
+    // Zobrist hash of the position after the ply
+    pub zobrist: u64,
+    pub engine_eval: Option<EngineEval>
+}
+
+impl Ply {
+    pub fn moved_piece(&self, name: PieceName, white: bool) -> bool {
+        self.white == white && self.moved.contains(&name)
+    }
+
+    // Centipawns for white, the engine's when the game has them
+    pub fn advantage(&self) -> i32 {
+        self.engine_eval.map_or_else(|| self.evaluation.total(), |eval| eval.centipawns())
+    }
+}
@@ -0,0 +1,232 @@
+use super::types::{Role, MoveType};
+use super::cell::Cell;
+use super::game::Game;
+use super::ply::Ply;
+
+const FILES: [char; 8] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
+// ANSI backgrounds for light and dark squares and the squares of the last move
+const LIGHT: &str = "\x1b[48;5;180m";
+const DARK: &str = "\x1b[48;5;137m";
+const HIGHLIGHT: &str = "\x1b[48;5;185m";
+const RESET: &str = "\x1b[0m";
+
+// How boards are drawn: chess glyphs or letters, and whether the terminal gets colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub unicode: bool,
+    pub color: bool
+}
+
+impl Style {
+    // Letters and brackets only, for logs and terminals without the glyphs
+    pub const ASCII: Style = Style {unicode: false, color: false};
+    pub const UNICODE: Style = Style {unicode: true, color: true};
+}
+
+// Where everything stood after a number of plies, worked out from the piece histories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub pieces: Vec<(Cell, Role, bool)>,
+    pub captured: Vec<(Role, bool)>,
+    // From and to cell of every piece moved in the last ply
+    pub last_move: Vec<(Cell, Cell)>,
+    pub plies: usize,
+    pub white_to_move: bool
+}
+
+impl Position {
+    // Every live piece has one history entry per ply, a captured one stops at its capture.
+    pub fn after(game: &Game, plies: usize) -> Position {
+        let plies = plies.min(game.plies.len());
+        let white_to_move = !matches!(game.plies[..plies].last(), Some(ply) if ply.white);
+        let mut position = Position {pieces: Vec::new(), captured: Vec::new(), last_move: Vec::new(), plies, white_to_move};
+        for piece in game.board.pieces.iter() {
+            let history = piece.get_cell_and_capture_history();
+            let cell_after = |ply: usize| if ply == 0 {piece.first_cell()} else {history[ply - 1].0};
+            if history.len() < plies {
+                position.captured.push((piece.get_role_after(plies), piece.is_white()));
+                continue;
+            }
+            let cell = cell_after(plies);
+            if plies > 0 && cell != cell_after(plies - 1) {
+                position.last_move.push((cell_after(plies - 1), cell));
+            }
+            position.pieces.push((cell, piece.get_role_after(plies), piece.is_white()));
+        }
+        position
+    }
+
+    pub fn piece_at(&self, cell: &Cell) -> Option<(Role, bool)> {
+        self.pieces.iter().find(|(piece_cell, _, _)| piece_cell == cell).map(|(_, role, white)| (*role, *white))
+    }
+
+    // The piece placement field of a FEN, like rnbqkbnr/pppppppp/8/..., the same for the
+    // same position however the pieces got there.
+    pub fn placement(&self) -> String {
+        let rows: Vec<String> = (1..=8).rev().map(|row| {
+            let mut text = String::new();
+            let mut empty = 0;
+            for file in FILES.iter() {
+                match self.piece_at(&Cell {file: *file, row}) {
+                    Some((role, white)) => {
+                        if empty > 0 {
+                            text.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        text.push(glyph(role, white, Style::ASCII));
+                    },
+                    None => empty += 1
+                }
+            }
+            if empty > 0 {
+                text.push_str(&empty.to_string());
+            }
+            text
+        }).collect();
+        rows.join("/")
+    }
+
+    fn highlighted(&self, cell: &Cell) -> bool {
+        self.last_move.iter().any(|(from, to)| from == cell || to == cell)
+    }
+}
+
+pub fn glyph(role: Role, white: bool, style: Style) -> char {
+    let (letter, unicode) = match role {
+        Role::King => ('K', ['♔', '♚']),
+        Role::Queen => ('Q', ['♕', '♛']),
+        Role::Rook => ('R', ['♖', '♜']),
+        Role::Bishop => ('B', ['♗', '♝']),
+        Role::Knight => ('N', ['♘', '♞']),
+        Role::Pawn => ('P', ['♙', '♟'])
+    };
+    match (style.unicode, white) {
+        (true, true) => unicode[0],
+        (true, false) => unicode[1],
+        (false, true) => letter,
+        (false, false) => letter.to_ascii_lowercase()
+    }
+}
+
+// The board from white's side with coordinates, the last move highlighted, then the
+// move, who is to move and the captured pieces.
+pub fn render(game: &Game, plies: usize, style: Style) -> String {
+    let position = Position::after(game, plies);
+    let files: String = FILES.iter().map(|file| format!(" {} ", file)).collect();
+    let mut text = format!("  {}\n", files);
+    for row in (1..=8).rev() {
+        text.push_str(&format!("{} ", row));
+        for (idx, file) in FILES.iter().enumerate() {
+            let cell = Cell {file: *file, row};
+            let piece = match position.piece_at(&cell) {
+                Some((role, white)) => glyph(role, white, style),
+                None if style.unicode => '·',
+                None => '.'
+            };
+            let highlighted = position.highlighted(&cell);
+            if style.color {
+                let background = if highlighted {HIGHLIGHT} else if (idx as i32 + row) % 2 == 0 {LIGHT} else {DARK};
+                text.push_str(&format!("{}\x1b[30m {} {}", background, piece, RESET));
+            } else if highlighted {
+                text.push_str(&format!("[{}]", piece));
+            } else {
+                text.push_str(&format!(" {} ", piece));
+            }
+        }
+        text.push_str(&format!(" {}\n", row));
+    }
+    text.push_str(&format!("  {}\n\n", files));
+
+    let to_move = if position.white_to_move {"white"} else {"black"};
+    match position.plies.checked_sub(1).and_then(|idx| game.plies.get(idx)) {
+        Some(ply) => {
+            let dots = if ply.white {"."} else {"..."};
+            text.push_str(&format!("Ply {} of {}: {}{} {}, {} to move\n", position.plies, game.plies.len(), position.plies.div_ceil(2), dots, move_text(ply, &position, style), to_move));
+        },
+        None => text.push_str(&format!("Start of {} plies, {} to move\n", game.plies.len(), to_move))
+    }
+    let captured: Vec<String> = position.captured.iter().map(|(role, white)| glyph(*role, *white, style).to_string()).collect();
+    if !captured.is_empty() {
+        text.push_str(&format!("Captured: {}\n", captured.join(" ")));
+    }
+    text
+}
+
+// Long algebraic, like Ng1-f3, exd5 or O-O, as the move is known from the histories.
+fn move_text(ply: &Ply, position: &Position, style: Style) -> String {
+    match ply.the_move.move_type {
+        MoveType::CastleKing => return "O-O".to_string(),
+        MoveType::CastleQueen => return "O-O-O".to_string(),
+        _ => {}
+    }
+    let name = |cell: &Cell| format!("{}{}", cell.file, cell.row);
+    let piece = match ply.the_move.role {
+        Role::Pawn => String::new(),
+        // Upper case letters for both sides, as in notation
+        role => glyph(role, ply.white || !style.unicode, style).to_string()
+    };
+    let separator = if ply.captured.is_some() {"x"} else {"-"};
+    let check = if ply.the_move.is_check() {"+"} else {""};
+    match position.last_move.first() {
+        Some((from, to)) => format!("{}{}{}{}{}", piece, name(from), separator, name(to), check),
+        None => format!("{}{}{}", piece, name(&ply.the_move.cell), check)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Move;
+
+    fn game() -> Game {
+        Game::new_with_moves(&Move::parse_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3"]))
+    }
+
+    #[test]
+    fn test_position_after() {
+        let game = game();
+        let start = Position::after(&game, 0);
+        assert_eq!(start.pieces.len(), 32);
+        assert!(start.last_move.is_empty());
+        assert_eq!(start.piece_at(&Cell::new("e2")), Some((Role::Pawn, true)));
+
+        let position = Position::after(&game, 3);
+        assert_eq!(position.piece_at(&Cell::new("d5")), Some((Role::Pawn, true)));
+        assert_eq!(position.captured, vec![(Role::Pawn, false)]);
+        assert_eq!(position.last_move, vec![(Cell::new("e4"), Cell::new("d5"))]);
+        assert!(!position.white_to_move);
+
+        let end = Position::after(&game, 5);
+        assert_eq!(end.piece_at(&Cell::new("d5")), Some((Role::Queen, false)));
+        assert_eq!(end.captured.len(), 2);
+    }
+
+    #[test]
+    fn test_placement() {
+        let game = game();
+        assert_eq!(Position::after(&game, 0).placement(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+        assert_eq!(Position::after(&game, 5).placement(), "rnb1kbnr/ppp1pppp/8/3q4/8/2N5/PPPP1PPP/R1BQKBNR");
+    }
+
+    #[test]
+    fn test_render_ascii() {
+        let text = render(&game(), 3, Style::ASCII);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "   a  b  c  d  e  f  g  h ");
+        assert_eq!(lines[1], "8  r  n  b  q  k  b  n  r  8");
+        assert_eq!(lines[4], "5  .  .  . [P] .  .  .  .  5");
+        assert_eq!(lines[5], "4  .  .  .  . [.] .  .  .  4");
+        assert!(text.contains("Ply 3 of 5: 2. e4xd5, black to move\n"));
+        assert!(text.contains("Captured: p\n"));
+    }
+
+    #[test]
+    fn test_render_unicode() {
+        let text = render(&game(), 5, Style::UNICODE);
+        assert!(text.contains("♛"));
+        assert!(text.contains(HIGHLIGHT));
+        assert!(text.contains("Ply 5 of 5: 3. ♘b1-c3, black to move\n"));
+        assert!(text.contains("Captured: ♙ ♟\n"));
+        assert!(render(&game(), 0, Style::UNICODE).contains("Start of 5 plies, white to move\n"));
+    }
+}
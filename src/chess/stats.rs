@@ -0,0 +1,195 @@
+use super::types::{Role, PieceName};
+use super::cell::Cell;
+use super::game::Game;
+
+const FILES: [char; 8] = ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'];
+// Squares listed under the heatmap in the report
+const BUSIEST: usize = 5;
+
+// What one piece did over the game, worked out from its history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PieceStats {
+    pub name: PieceName,
+    pub white: bool,
+    pub role: Role,
+    // Plies the piece moved in
+    pub moves: usize,
+    // In king steps, a knight's jump is two
+    pub distance: u32,
+    // Every square it stood on, from where it started, in the order it first got there
+    pub visited: Vec<Cell>,
+    pub captures: usize,
+    // Ply the piece was captured in, from 0, None when it lasted the game
+    pub captured_at: Option<usize>
+}
+
+impl PieceStats {
+    // Plies the piece was on the board for
+    pub fn lifetime(&self, plies: usize) -> usize {
+        self.captured_at.unwrap_or(plies)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameStats {
+    pub plies: usize,
+    pub pieces: Vec<PieceStats>,
+    // Moves that ended on each square, by row from the first and file from a
+    pub heatmap: [[u32; 8]; 8],
+    // The piece that gave check mate, when the game ends with one
+    pub mate: Option<(PieceName, bool)>
+}
+
+impl Game {
+    pub fn stats(&self) -> GameStats {
+        let mut heatmap = [[0; 8]; 8];
+        let pieces = self.board.pieces.iter().map(|piece| {
+            let mut stats = PieceStats {
+                name: piece.get_name(),
+                white: piece.is_white(),
+                role: piece.get_role(),
+                moves: 0,
+                distance: 0,
+                visited: vec![piece.first_cell()],
+                captures: 0,
+                captured_at: None
+            };
+            let history = piece.get_cell_and_capture_history();
+            let mut last_cell = piece.first_cell();
+            for (cell, capture) in history.iter() {
+                if *cell == last_cell {
+                    continue;
+                }
+                let (x_diff, y_diff) = last_cell.get_cell_diff(cell);
+                stats.moves += 1;
+                stats.distance += x_diff.abs().max(y_diff.abs()) as u32;
+                if *capture {
+                    stats.captures += 1;
+                }
+                if !stats.visited.contains(cell) {
+                    stats.visited.push(*cell);
+                }
+                heatmap[cell.row as usize - 1][file_index(cell)] += 1;
+                last_cell = *cell;
+            }
+            // A captured piece's history stops at the ply it was taken in
+            if !piece.is_live() {
+                stats.captured_at = Some(history.len());
+            }
+            stats
+        }).collect();
+
+        let mate = self.plies.last()
+            .filter(|ply| ply.the_move.is_mate())
+            .and_then(|ply| ply.moved.last().map(|name| (*name, ply.white)));
+        GameStats {plies: self.plies.len(), pieces, heatmap, mate}
+    }
+}
+
+impl GameStats {
+    pub fn piece(&self, name: PieceName, white: bool) -> Option<&PieceStats> {
+        self.pieces.iter().find(|piece| piece.name == name && piece.white == white)
+    }
+
+    // Squares by how many moves ended there, busiest first.
+    pub fn busiest_squares(&self) -> Vec<(Cell, u32)> {
+        let mut squares: Vec<(Cell, u32)> = (1..=8).flat_map(|row| FILES.iter().map(move |file| Cell {file: *file, row}))
+            .map(|cell| (cell, self.heatmap[cell.row as usize - 1][file_index(&cell)]))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        squares.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        squares
+    }
+
+    // A table of the pieces that moved, then the heatmap from white's side.
+    pub fn report(&self) -> String {
+        let mate = match self.mate {
+            Some((name, white)) => format!("mate by {} {:?}", side(white), name),
+            None => "no mate".to_string()
+        };
+        let mut text = format!("{} plies, {}\n\n", self.plies, mate);
+        text.push_str(&format!("{:<15} {:>5} {:>8} {:>8} {:>7} {:>8}\n", "piece", "moves", "distance", "captures", "squares", "captured"));
+        for piece in self.pieces.iter().filter(|piece| piece.moves > 0 || piece.captured_at.is_some()) {
+            let captured = piece.captured_at.map_or_else(|| "-".to_string(), |ply| format!("ply {}", ply + 1));
+            text.push_str(&format!("{:<15} {:>5} {:>8} {:>8} {:>7} {:>8}\n",
+                format!("{} {:?}", side(piece.white), piece.name), piece.moves, piece.distance, piece.captures, piece.visited.len(), captured));
+        }
+
+        text.push_str("\nMoves ending on each square\n");
+        for row in (1..=8).rev() {
+            let counts: Vec<String> = self.heatmap[row - 1].iter()
+                .map(|count| if *count == 0 {" .".to_string()} else {format!("{:>2}", count)})
+                .collect();
+            text.push_str(&format!("{} {}\n", row, counts.join(" ")));
+        }
+        text.push_str("   a  b  c  d  e  f  g  h\n");
+        let busiest: Vec<String> = self.busiest_squares().iter().take(BUSIEST)
+            .map(|(cell, count)| format!("{}{} ({})", cell.file, cell.row, count))
+            .collect();
+        if !busiest.is_empty() {
+            text.push_str(&format!("Busiest: {}\n", busiest.join(", ")));
+        }
+        text
+    }
+}
+
+fn side(white: bool) -> &'static str {
+    if white {"white"} else {"black"}
+}
+
+fn file_index(cell: &Cell) -> usize {
+    (cell.file as u8 - b'a') as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Move;
+
+    fn scholars_mate() -> GameStats {
+        Game::new_with_moves(&Move::parse_moves(&["e4", "e5", "Bc4", "Nc6", "Qh5", "Nf6", "Qxf7#"])).stats()
+    }
+
+    #[test]
+    fn test_piece_stats() {
+        let stats = scholars_mate();
+        assert_eq!(stats.plies, 7);
+        assert_eq!(stats.pieces.len(), 32);
+
+        let queen = stats.piece(PieceName::Queen, true).unwrap();
+        assert_eq!(queen.moves, 2);
+        // d1 to h5 is four diagonal steps, h5 to f7 two
+        assert_eq!(queen.distance, 6);
+        assert_eq!(queen.captures, 1);
+        assert_eq!(queen.visited, vec![Cell::new("d1"), Cell::new("h5"), Cell::new("f7")]);
+        assert_eq!(queen.lifetime(stats.plies), 7);
+
+        let knight = stats.piece(PieceName::Qknight, false).unwrap();
+        assert_eq!((knight.moves, knight.distance), (1, 2));
+
+        let fpawn = stats.piece(PieceName::Fpawn, false).unwrap();
+        assert_eq!(fpawn.moves, 0);
+        assert_eq!(fpawn.captured_at, Some(6));
+        assert_eq!(fpawn.lifetime(stats.plies), 6);
+    }
+
+    #[test]
+    fn test_heatmap_and_mate() {
+        let stats = scholars_mate();
+        assert_eq!(stats.mate, Some((PieceName::Queen, true)));
+        assert_eq!(stats.heatmap[6][5], 1);
+        assert_eq!(stats.heatmap.iter().flatten().sum::<u32>(), 7);
+        assert_eq!(stats.busiest_squares().len(), 7);
+        assert_eq!(Game::new_with_moves(&Move::parse_moves(&["e4", "e5"])).stats().mate, None);
+    }
+
+    #[test]
+    fn test_report() {
+        let report = scholars_mate().report();
+        assert!(report.starts_with("7 plies, mate by white Queen\n\n"), "{}", report);
+        assert!(report.contains("\nwhite Queen         2        6        1       3        -\n"), "{}", report);
+        assert!(report.contains("\nblack Fpawn         0        0        0       1    ply 7\n"), "{}", report);
+        assert!(report.contains("\n7  .  .  .  .  .  1  .  .\n"), "{}", report);
+        assert!(!report.contains("white Apawn"));
+    }
+}
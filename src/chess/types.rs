@@ -52,3 +52,18 @@ pub enum PieceName {
     Apawn, Bpawn, Cpawn, Dpawn, Epawn, Fpawn, Gpawn, Hpawn,
     Qrook, Qknight, Qbishop, Queen, King, Kbishop, Kknight, Krook
 }
+
+impl PieceName {
+    pub const ALL: [PieceName; 16] = [
+        PieceName::Apawn, PieceName::Bpawn, PieceName::Cpawn, PieceName::Dpawn,
+        PieceName::Epawn, PieceName::Fpawn, PieceName::Gpawn, PieceName::Hpawn,
+        PieceName::Qrook, PieceName::Qknight, PieceName::Qbishop, PieceName::Queen,
+        PieceName::King, PieceName::Kbishop, PieceName::Kknight, PieceName::Krook
+    ];
+
+    // "epawn", "qknight", "queen", ... as the variants are spelled.
+    pub fn from_name(name: &str) -> Option<PieceName> {
+        let name = name.to_lowercase();
+        PieceName::ALL.iter().cloned().find(|piece| format!("{:?}", piece).to_lowercase() == name)
+    }
+}
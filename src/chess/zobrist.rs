@@ -0,0 +1,70 @@
+// Random keys for Zobrist hashing: a position's hash is the xor of the keys of every piece
+// on its cell, the side to move, the castling rights and the en passant file. A move
+// updates the hash by xoring out what it took away and xoring in what it put.
+use super::types::Role;
+use super::cell::Cell;
+
+// Six roles of two colors on 64 cells, then black to move, four castling rights and eight files
+const PIECES: usize = 6 * 2 * 64;
+const BLACK_TO_MOVE: usize = PIECES;
+const CASTLING: usize = BLACK_TO_MOVE + 1;
+const EN_PASSANT: usize = CASTLING + 4;
+const COUNT: usize = EN_PASSANT + 8;
+// Fixed so hashes are the same from run to run
+const SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+const KEYS: [u64; COUNT] = keys();
+
+// Splitmix64, as in music::variation, worked out when compiling.
+const fn keys() -> [u64; COUNT] {
+    let mut keys = [0; COUNT];
+    let mut state = SEED;
+    let mut idx = 0;
+    while idx < COUNT {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[idx] = z ^ (z >> 31);
+        idx += 1;
+    }
+    keys
+}
+
+pub fn piece(role: Role, white: bool, cell: &Cell) -> u64 {
+    let color = if white {0} else {1};
+    KEYS[((role as usize * 2 + color) * 64) + cell_index(cell)]
+}
+
+pub fn black_to_move() -> u64 {
+    KEYS[BLACK_TO_MOVE]
+}
+
+// Rights are numbered as Board keeps them: white king side, white queen side, then black's
+pub fn castling(right: usize) -> u64 {
+    KEYS[CASTLING + right]
+}
+
+pub fn en_passant(cell: &Cell) -> u64 {
+    KEYS[EN_PASSANT + (cell.file as u8 - b'a') as usize]
+}
+
+fn cell_index(cell: &Cell) -> usize {
+    (cell.row as usize - 1) * 8 + (cell.file as u8 - b'a') as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys = KEYS.to_vec();
+        keys.sort_unstable();
+        keys.dedup();
+        assert_eq!(keys.len(), COUNT);
+        assert_ne!(piece(Role::Knight, true, &Cell::new("f3")), piece(Role::Knight, false, &Cell::new("f3")));
+        assert_ne!(piece(Role::Knight, true, &Cell::new("f3")), piece(Role::Bishop, true, &Cell::new("f3")));
+        assert_eq!(piece(Role::King, false, &Cell::new("h8")), KEYS[PIECES - 1]);
+    }
+}
@@ -1,14 +1,35 @@
-use crate::chess::types::MoveType;
-
 use super::pgn;
 use super::chess;
-use super::chess::{piece::Piece};
-use super::music::{MidiPlayer, Note, Melody};
-
-use std::sync::mpsc;
-use std::sync::Arc;
+use super::eco::{self, Opening};
+use super::chess::render::{self, Style};
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+use super::lichess::{LichessClient, LiveEvent, LiveStream};
+use super::music::{Key, Rhythm};
+#[cfg(feature = "midi-live")]
+use super::music::MidiPlayer;
+use super::music::rhythm::{QUARTER, DEFAULT_TEMPO};
+#[cfg(feature = "smf")]
+use super::music::smf;
+#[cfg(feature = "synth")]
+use super::music::synth;
+#[cfg(feature = "json")]
+use super::json;
+#[cfg(feature = "pictures")]
+use super::picture::{Picture, ImageFormat};
+#[cfg(feature = "notation")]
+use super::music::{notation, musicxml, lilypond};
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+use super::music::live::LivePlayer;
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+use super::music::sequencer::{MidiSink, ClockPanicked};
+use super::music::mapping::{self, Mapping};
+use super::music::variation::Variation;
+use super::music::score::{Score, ScoreConfig};
 
-extern crate crossbeam;
+use std::error::Error;
+use std::time::Duration;
+#[cfg(feature = "midi-live")]
+use std::time::Instant;
 
 static PIECES: &'static [(chess::PieceName, bool)] = &[
     // (chess::PieceName::Apawn, true),
@@ -50,108 +71,585 @@ struct ChessMusic {
     game: chess::Game
 }
 
-fn generate_pitches_by_pieces(pieces: &[(chess::PieceName, bool)], tx: mpsc::Sender<Melody>, game: &chess::Game) {
-    crossbeam::scope(|s| {
-        for (piece_name, white) in pieces.iter() {
-            let tx1 = mpsc::Sender::clone(&tx);
-            let piece = game.board.get_piece_with_name(*piece_name, *white);
-            let history = piece.get_cell_and_capture_history();
-            s.spawn(move |_| {
-                // TODO: initial not is wrong.
-                let melody = Melody::new(&history, Note::new(120));
-                tx1.send(melody).unwrap();
-            });
+// The PGN's own ECO tag when it has one, named from the Opening tag or the table,
+// otherwise the opening the moves reach.
+pub fn opening_for_game(game_str: &str, game: &chess::Game) -> Option<Opening> {
+    match pgn::parse_tag(game_str, "ECO") {
+        Some(code) => {
+            let name = pgn::parse_tag(game_str, "Opening").map(str::to_string)
+                .or_else(|| eco::openings().iter().find(|opening| opening.eco == code).map(|opening| opening.name.clone()))
+                .unwrap_or_default();
+            Some(Opening::new(code, &name))
+        },
+        None => eco::classify(game)
+    }
+}
+
+// The opening decides the key when it is known, otherwise the result does.
+fn key_for_game(game_str: &str, game: &chess::Game) -> Key {
+    opening_for_game(game_str, game).and_then(|opening| Key::from_eco(&opening.eco))
+        .or_else(|| pgn::parse_tag(game_str, "Result").and_then(Key::from_result))
+        .unwrap_or_default()
+}
+
+// Clock comments drive the rhythm when the PGN has them.
+fn rhythm_for_game(think_times: &[Option<Duration>]) -> Rhythm {
+    if think_times.iter().any(|think_time| think_time.is_some()) {
+        Rhythm::ThinkTime
+    } else {
+        Rhythm::Fixed(QUARTER)
+    }
+}
+
+// A tenth of the starting time, between ten seconds and a minute, is when the music starts to hurry.
+fn time_pressure_for_game(game_str: &str) -> Option<Duration> {
+    let (base, _) = pgn::parse_time_control(game_str)?;
+    Some((base / 10).clamp(Duration::from_secs(10), Duration::from_secs(60)))
+}
+
+// How a game is turned into music, beyond what the PGN itself decides.
+pub struct Options {
+    pub mapping: &'static dyn Mapping,
+    // Renders the same game differently, but the same way for the same seed
+    pub seed: Option<u64>,
+    // The pieces that get a voice
+    pub pieces: Vec<(chess::PieceName, bool)>,
+    // Beats per minute
+    pub tempo: u32,
+    // Replaces the key the opening or the result would pick
+    pub key: Option<Key>,
+    // MIDI output to play to, matched by part of its name
+    pub port: Option<String>
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {mapping: &mapping::InKey, seed: None, pieces: PIECES.to_vec(), tempo: DEFAULT_TEMPO, key: None, port: None}
+    }
+}
+
+// What a game can be rendered to, picked by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Midi,
+    Wav,
+    MusicXml,
+    LilyPond
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Option<Format> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        Format::from_name(&extension)
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "mid" | "midi" => Some(Format::Midi),
+            "wav" => Some(Format::Wav),
+            "musicxml" | "xml" => Some(Format::MusicXml),
+            "ly" => Some(Format::LilyPond),
+            _ => None
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Midi => "mid",
+            Format::Wav => "wav",
+            Format::MusicXml => "musicxml",
+            Format::LilyPond => "ly"
+        }
+    }
+}
+
+fn game_for_pgn(game_str: &str) -> Result<chess::Game, Box<dyn Error>> {
+    let str_moves = pgn::parse_moves(game_str);
+    let moves = chess::Move::try_parse_moves(&str_moves)?;
+    let mut game = chess::Game::try_new_with_moves(&moves)?;
+    game.set_think_times(&pgn::parse_think_times(game_str));
+    game.set_clocks(&pgn::parse_clocks(game_str));
+    game.set_engine_evals(&pgn::parse_evals(game_str));
+    Ok(game)
+}
+
+pub fn score_for_game(game_str: &str, options: &Options) -> Result<Score, Box<dyn Error>> {
+    let game = game_for_pgn(game_str)?;
+    let think_times: Vec<Option<Duration>> = game.plies.iter().map(|ply| ply.think_time).collect();
+
+    let config = ScoreConfig {
+        mapping: options.mapping,
+        variation: options.seed.map(Variation::new),
+        key: options.key.clone().unwrap_or_else(|| key_for_game(game_str, &game)),
+        rhythm: rhythm_for_game(&think_times),
+        time_pressure: time_pressure_for_game(game_str),
+        harmony: true,
+        ..ScoreConfig::default()
+    };
+    Ok(Score::from_game(&game, &options.pieces, &config))
+}
+
+// The opening, the final position and where each voiced piece stood after every ply, one line per piece.
+pub fn inspect_game(game_str: &str, options: &Options) -> Result<String, Box<dyn Error>> {
+    let game = game_for_pgn(game_str)?;
+    let opening = opening_for_game(game_str, &game)
+        .map_or_else(String::new, |opening| format!(", {} {}", opening.eco, opening.name));
+    let mut text = format!("{} plies{}\n\n{}\n\n", game.plies.len(), opening, game.board.diagram());
+    for (name, white) in options.pieces.iter() {
+        let cells: Vec<String> = game.get_piece_history(*name, *white).iter()
+            .map(|cell| format!("{}{}", cell.file, cell.row))
+            .collect();
+        let side = if *white {"white"} else {"black"};
+        text.push_str(&format!("{} {:?}: {}\n", side, name, cells.join(" ")));
+    }
+    Ok(text)
+}
+
+// Ctrl-C stops playback, the sequencer releases every sounding note before we exit.
+#[cfg(feature = "midi-live")]
+pub async fn play_game(game_str: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let score = score_for_game(game_str, options)?;
+
+    let mut player = MidiPlayer::new(options.port.as_deref())?;
+    player.set_tempo(options.tempo);
+    let handle = player.play_score(&score);
+    let controller = handle.controller();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            controller.stop();
         }
-    }).unwrap();
+    });
+    tokio::task::spawn_blocking(move || handle.wait()).await??;
+    Ok(())
 }
 
-fn receive_pitches_by_piece(pieces: &[(chess::PieceName, bool)], rx: mpsc::Receiver<Melody>) -> Vec<Melody> {
-    let mut melodies: Vec<Melody> = Vec::with_capacity(pieces.len());
+// Plays the game while the terminal follows along, a board for every ply as its music starts.
+#[cfg(feature = "midi-live")]
+pub async fn replay_game(game_str: &str, options: &Options, style: Style) -> Result<(), Box<dyn Error>> {
+    let game = game_for_pgn(game_str)?;
+    let score = score_for_game(game_str, options)?;
+    let tempo_map = score.tempo_map(options.tempo);
 
-    for _ in 0..pieces.len() {
-        match rx.recv() {
-            Ok(melody) => melodies.push(melody),
-            Err(the_error) => {
-                println!("{}", the_error.to_string())
+    let mut player = MidiPlayer::new(options.port.as_deref())?;
+    player.set_tempo(options.tempo);
+    let handle = player.play_score(&score);
+    let controller = handle.controller();
+    let start = Instant::now();
+    for ply in 0..game.plies.len() {
+        let due = start + Duration::from_micros(tempo_map.micros_at(score.ply_ticks[ply]));
+        tokio::select! {
+            _ = tokio::time::delay_until(tokio::time::Instant::from_std(due)) => {},
+            _ = tokio::signal::ctrl_c() => {
+                controller.stop();
+                break;
             }
         }
+        // Clear the screen and draw from the top
+        print!("\x1b[2J\x1b[H{}", replay_frame(&game, &score, ply, style));
+    }
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            controller.stop();
+        }
+    });
+    tokio::task::spawn_blocking(move || handle.wait()).await??;
+    Ok(())
+}
+
+// The board once ply has been played and what each piece's voice sounds while its music plays.
+#[cfg_attr(not(feature = "midi-live"), allow(dead_code))]
+fn replay_frame(game: &chess::Game, score: &Score, ply: usize, style: Style) -> String {
+    let mut text = render::render(game, ply + 1, style);
+    text.push('\n');
+    let sounding = score.notes_at_ply(ply);
+    for voice in score.voices.iter().filter(|voice| voice.piece.is_some()) {
+        let notes: Vec<String> = sounding.iter()
+            .filter(|(sounding_voice, _)| sounding_voice.name == voice.name)
+            .map(|(_, note)| note.name())
+            .collect();
+        let notes = if notes.is_empty() {"-".to_string()} else {notes.join(" ")};
+        text.push_str(&format!("{:<14} {}\n", voice.name, notes));
     }
+    text
+}
 
-    melodies
+// Plays a Lichess game, or Lichess TV when the id is "tv", as it is being played.
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+pub async fn play_live(game_id: &str, options: &Options) -> Result<(), Box<dyn Error>> {
+    let client = LichessClient::new();
+    let stream = match game_id {
+        "tv" => client.stream_tv().await?,
+        _ => client.stream_game(game_id).await?
+    };
+    follow_live(&client, stream, options, MidiPlayer::new(options.port.as_deref())?).await?;
+    Ok(())
 }
 
-fn chords_from_pitches_by_piece(melodies: &[Melody]) -> Vec<Vec<Note>> {
-    let mut chords: Vec<Vec<Note>> = Vec::new();
-    let longest_length = melodies.iter().max_by_key(|pitches| pitches.notes.len()).unwrap().notes.len();
-    for n in 0..longest_length {
-        let mut chord: Vec<Note> = Vec::new();
-        for pitches in melodies.iter() {
-            if let Some(pitch) =  pitches.notes.get(n) {
-                chord.push(*pitch);
+// Moves played before we joined are fetched and skipped, every move after that is
+// played the moment it arrives. Returns the number of moves played.
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+async fn follow_live<S: MidiSink + Send + 'static>(client: &LichessClient, mut stream: LiveStream, options: &Options, sink: S) -> Result<usize, Box<dyn Error>> {
+    let config = ScoreConfig {
+        mapping: options.mapping,
+        variation: options.seed.map(Variation::new),
+        harmony: true,
+        ..ScoreConfig::default()
+    };
+    let mut player = LivePlayer::new(&options.pieces, config, options.tempo, sink);
+    let mut following: Option<String> = None;
+    let mut played = 0;
+    loop {
+        let event = tokio::select! {
+            event = stream.next_event() => event,
+            _ = tokio::signal::ctrl_c() => {
+                player.stop()?;
+                return Ok(played);
             }
+        };
+        match event {
+            Some(Ok(LiveEvent::Game {id, plies})) => {
+                // The stream repeats the game when it ends
+                if following.as_ref() == Some(&id) {
+                    continue;
+                }
+                let game_str = match plies {
+                    Some(0) => String::new(),
+                    _ => client.get_game(&id, false).await?
+                };
+                player.load_moves(&chess::Move::try_parse_moves(&pgn::parse_moves(&game_str))?)?;
+                following = Some(id);
+            },
+            Some(Ok(LiveEvent::Move {uci, ply, placement})) => {
+                // Positions we already have, the stream starts with the current one and the
+                // export we caught up from may already have the move
+                if ply.is_some_and(|ply| ply <= player.plies()) || placement.as_ref() == Some(&player.placement()) {
+                    continue;
+                }
+                if play_in_step(&mut player, &uci, ply, placement.as_deref())? {
+                    played += 1;
+                    continue;
+                }
+                // Lost track, the export lagged behind the stream. Catch up again and play
+                // the move if it is the next one.
+                let id = following.as_ref().ok_or("Got a move before the game it was played in")?;
+                let game_str = client.get_game(id, false).await?;
+                player.load_moves(&chess::Move::try_parse_moves(&pgn::parse_moves(&game_str))?)?;
+                if placement.as_ref() != Some(&player.placement()) && play_in_step(&mut player, &uci, None, placement.as_deref())? {
+                    played += 1;
+                }
+            },
+            Some(Err(error)) => {
+                player.stop()?;
+                return Err(error);
+            },
+            None => break
         }
+    }
+    player.finish()?;
+    Ok(played)
+}
 
-        chords.push(chord);
+// Plays the move when it follows on from what the player has and lands where the stream
+// says, false when it doesn't and the player needs to catch up again.
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+fn play_in_step<S: MidiSink + Send + 'static>(player: &mut LivePlayer<S>, uci: &str, ply: Option<usize>, placement: Option<&str>) -> Result<bool, Box<dyn Error>> {
+    if ply.is_some_and(|ply| ply != player.plies() + 1) {
+        return Ok(false);
+    }
+    match player.play_uci(uci) {
+        Ok(()) => Ok(placement.is_none_or(|placement| placement == player.placement())),
+        Err(error) if error.is::<ClockPanicked>() => Err(error),
+        Err(_) => Ok(false)
     }
-    chords
 }
 
-pub fn play_game(game_str: &str) {
-    let str_moves = pgn::parse_moves(game_str);
-    let moves = chess::Move::parse_moves(&str_moves);
-    let game = chess::Game::new_with_moves(&moves);
+pub fn game_stats(game_str: &str) -> Result<chess::GameStats, Box<dyn Error>> {
+    Ok(game_for_pgn(game_str)?.stats())
+}
 
-    let (tx, rx): (mpsc::Sender<Melody>, mpsc::Receiver<Melody>) = mpsc::channel();
-    generate_pitches_by_pieces(PIECES, tx, &game);
-    let pitches_by_piece = receive_pitches_by_piece(PIECES, rx);
-    let chords = chords_from_pitches_by_piece(&pitches_by_piece);
+#[cfg(feature = "json")]
+pub fn game_stats_json(game_str: &str) -> Result<String, Box<dyn Error>> {
+    Ok(json::stats_to_string(&game_stats(game_str)?)?)
+}
+
+// The game's plies and piece histories with the score made from it, as JSON.
+#[cfg(feature = "json")]
+pub fn game_json(game_str: &str, options: &Options) -> Result<String, Box<dyn Error>> {
+    let game = game_for_pgn(game_str)?;
+    let score = score_for_game(game_str, options)?;
+    Ok(json::to_string(&game, &score, options.tempo)?)
+}
 
-    let mut midi_player = MidiPlayer::new();
-    for chord in chords.iter() {
-        let notes: Vec<u8> = chord.iter().map(|pitch|pitch.as_midi()).collect();
-        midi_player.play_notes(&notes);
+// The voiced pieces' paths annotated with the pitches they played, or with a ply the board after it,
+// as an SVG or PNG picked by the extension.
+#[cfg(feature = "pictures")]
+pub fn draw_game(game_str: &str, options: &Options, ply: Option<usize>, path: &str) -> Result<(), Box<dyn Error>> {
+    let format = ImageFormat::from_path(path).ok_or(format!("Can't tell what to draw {} as, expected a .svg or .png file", path))?;
+    let game = game_for_pgn(game_str)?;
+    let picture = match ply {
+        Some(ply) if ply > game.plies.len() => Err(format!("The game has {} plies, there is no ply {}", game.plies.len(), ply))?,
+        Some(ply) => Picture::position(&game, ply),
+        None => Picture::paths(&game, &options.pieces, Some(&score_for_game(game_str, options)?))
+    };
+    picture.save(format, path).map_err(|error| format!("Could not write {}: {}", path, error).into())
+}
+
+// The players, else the event, as the title of the sheet music.
+#[cfg(feature = "notation")]
+fn title_for_game(game_str: &str) -> String {
+    match (pgn::parse_tag(game_str, "White"), pgn::parse_tag(game_str, "Black")) {
+        (Some(white), Some(black)) => format!("{} - {}", white, black),
+        _ => pgn::parse_tag(game_str, "Event").unwrap_or("Chess music").to_string()
     }
 }
 
+#[cfg_attr(not(all(feature = "smf", feature = "synth", feature = "notation")), allow(unused_variables))]
+pub fn save_game(game_str: &str, options: &Options, format: Format, path: &str) -> Result<(), Box<dyn Error>> {
+    let score = score_for_game(game_str, options)?;
+    let saved: Result<(), Box<dyn Error>> = match format {
+        #[cfg(feature = "smf")]
+        Format::Midi => smf::save(&score, options.tempo, path),
+        #[cfg(feature = "synth")]
+        Format::Wav => synth::save(&score, options.tempo, path),
+        #[cfg(feature = "notation")]
+        Format::MusicXml => musicxml::save(&score, &title_for_game(game_str),
+            &notation::ply_lyrics(&pgn::parse_moves(game_str)), options.tempo, path),
+        #[cfg(feature = "notation")]
+        Format::LilyPond => lilypond::save(&score, &title_for_game(game_str),
+            &notation::ply_lyrics(&pgn::parse_moves(game_str)), options.tempo, path),
+        // The format's feature is off
+        #[allow(unreachable_patterns)]
+        _ => Err(format!("Built without the feature to write .{} files", format.extension()).into())
+    };
+    saved.map_err(|error| format!("Could not write {}: {}", path, error).into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_chords_from_pitches_by_piece_with_equal_length() {
-        let cell_history1 = vec![chess::Cell::new("a2"), chess::Cell::new("a3"), chess::Cell::new("a4")];
-        let pitches1 = Note::get_pitches_from_cell_history(&cell_history1);
-        let cell_history2 = vec![chess::Cell::new("c2"), chess::Cell::new("c3"), chess::Cell::new("d4")];
-        let pitches2 = Note::get_pitches_from_cell_history(&cell_history2);
-        let pitches_by_piece = vec![pitches1, pitches2];
-        let chords = chords_from_pitches_by_piece(&pitches_by_piece);
+    fn test_score_for_game() {
+        let game_str = "[ECO \"B01\"]\n[TimeControl \"600+0\"]\n\n1. e4 { [%clk 0:09:58] } 1... d5 { [%clk 0:09:40] } 2. exd5 { [%clk 0:09:57] } 2... Qxd5 { [%clk 0:09:39] } 1-0";
+        let score = score_for_game(game_str, &Options::default()).unwrap();
+        assert_eq!(score.ply_ticks.len(), 5);
+        // Black thought for 20 seconds on d5, so that ply is held longest
+        assert_eq!(score.ply_ticks[2] - score.ply_ticks[1], 960);
+        // Four harmony voices and the percussion after the pieces
+        assert_eq!(score.voices.len(), PIECES.len() + 5);
+        for voice in score.voices.iter() {
+            assert!(voice.events.iter().all(|event| event.tick + event.duration <= score.length()));
+        }
+    }
+
+    #[cfg(feature = "smf")]
+    #[test]
+    fn test_seeded_midi_is_reproducible() {
+        let game_str = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 dxc6 5. O-O f6 1-0";
+        let render = |seed| smf::to_bytes(&score_for_game(game_str, &Options {seed, ..Options::default()}).unwrap(), DEFAULT_TEMPO);
+        assert_eq!(render(Some(2024)), render(Some(2024)));
+        assert_ne!(render(Some(2024)), render(Some(2025)));
+        assert_eq!(render(None), render(None));
+    }
+
+    #[test]
+    fn test_options_change_the_score() {
+        let game_str = "[ECO \"C50\"]\n\n1. e4 e5 2. Nf3 Nc6 *";
+        let options = Options {
+            pieces: vec![(chess::PieceName::Kknight, true)],
+            key: Key::from_name("D-dorian"),
+            ..Options::default()
+        };
+        let score = score_for_game(game_str, &options).unwrap();
+        assert_eq!(score.voices.iter().filter(|voice| voice.piece.is_some()).count(), 1);
+        assert_eq!(score.voices[0].piece, Some((chess::PieceName::Kknight, true)));
+        assert_ne!(score, score_for_game(game_str, &Options {key: None, ..options}).unwrap());
+    }
+
+    #[test]
+    fn test_inspect_game() {
+        let text = inspect_game("1. e4 e5 2. Nf3 Nc6 *", &Options::default()).unwrap();
+        assert!(text.starts_with("4 plies, C44 King's Knight Opening: Normal Variation\n\n8 r.bqkbnr\n"), "{}", text);
+        assert!(text.contains("\nwhite Epawn: e4 e4 e4 e4\n"), "{}", text);
+        assert!(text.contains("\nblack Epawn: e7 e5 e5 e5\n"), "{}", text);
+    }
+
+    #[test]
+    fn test_replay_frame() {
+        let game_str = "1. e4 e5 2. Nf3 Nc6 *";
+        let options = Options {pieces: vec![(chess::PieceName::Epawn, true), (chess::PieceName::Qknight, false)], ..Options::default()};
+        let frame = replay_frame(&game_for_pgn(game_str).unwrap(), &score_for_game(game_str, &options).unwrap(), 3, Style::ASCII);
+        assert!(frame.contains("Ply 4 of 4: 2... Nb8-c6, white to move\n"), "{}", frame);
+        let voices: Vec<&str> = frame.lines().skip_while(|line| !line.is_empty()).skip(1).skip_while(|line| !line.is_empty()).skip(1).collect();
+        assert_eq!(voices.len(), 2, "{}", frame);
+        assert!(voices[0].starts_with("White Epawn"), "{}", frame);
+        assert!(voices[1].starts_with("Black Qknight"), "{}", frame);
+        // The knight moved in this ply, so its voice is sounding
+        assert!(!voices[1].ends_with('-'), "{}", frame);
+    }
 
-        assert_eq!(chords.len(), 3);
-        assert_eq!(chords[0].len(), 2);
-        assert_eq!(chords[1].len(), 2);
-        assert_eq!(chords[2].len(), 2);
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_game_json() {
+        let options = Options {pieces: vec![(chess::PieceName::Epawn, true)], ..Options::default()};
+        let json: serde_json::Value = serde_json::from_str(&game_json("1. e4 e5 2. Nf3 *", &options).unwrap()).unwrap();
+        assert_eq!(json["game"]["plies"].as_array().unwrap().len(), 3);
+        assert_eq!(json["score"]["tempo"], DEFAULT_TEMPO);
+        assert_eq!(json["score"]["voices"][0]["piece"], "epawn");
+    }
+
+    #[cfg(feature = "pictures")]
+    #[test]
+    fn test_draw_game() {
+        let game_str = "1. e4 e5 2. Nf3 Nc6 *";
+        let path = std::env::temp_dir().join("chessmusic_test_draw_game.svg");
+        let path = path.to_str().unwrap();
+        draw_game(game_str, &Options::default(), None, path).unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().contains("<polyline"));
+        draw_game(game_str, &Options::default(), Some(2), path).unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().contains("Position after ply 2"));
+        std::fs::remove_file(path).unwrap();
+        assert!(draw_game(game_str, &Options::default(), Some(5), path).unwrap_err().to_string().contains("no ply 5"));
+        assert!(draw_game(game_str, &Options::default(), None, "game.gif").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_path("out/game.MID"), Some(Format::Midi));
+        assert_eq!(Format::from_path("game.wav"), Some(Format::Wav));
+        assert_eq!(Format::from_path("game.musicxml"), Some(Format::MusicXml));
+        assert_eq!(Format::from_path("game.ly"), Some(Format::LilyPond));
+        assert_eq!(Format::from_path("game.mp3"), None);
+        assert_eq!(Format::from_path("game"), None);
+    }
 
-        assert_eq!(chords[0], vec![Note {base_midi: 57, adjustment: 1}, Note {base_midi: 60, adjustment: 1}]);
-        assert_eq!(chords[1], vec![Note {base_midi: 59, adjustment: 1}, Note {base_midi: 62, adjustment: 1}]);
-        assert_eq!(chords[2], vec![Note {base_midi: 61, adjustment: 1}, Note {base_midi: 64, adjustment: 2}]);
+    #[test]
+    fn test_rhythm_for_game() {
+        assert_eq!(rhythm_for_game(&[None, Some(Duration::from_secs(3))]), Rhythm::ThinkTime);
+        assert_eq!(rhythm_for_game(&[None, None]), Rhythm::Fixed(QUARTER));
     }
 
     #[test]
-    fn test_chords_from_pitches_by_piece_with_different_length() {
-        let cell_history1 = vec![chess::Cell::new("a2"), chess::Cell::new("a3")];
-        let pitches1 = Note::get_pitches_from_cell_history(&cell_history1);
-        let cell_history2 = vec![chess::Cell::new("c2"), chess::Cell::new("c2"), chess::Cell::new("c2")];
-        let pitches2 = Note::get_pitches_from_cell_history(&cell_history2);
-        let pitches_by_piece = vec![pitches1, pitches2];
-        let chords = chords_from_pitches_by_piece(&pitches_by_piece);
-
-        assert_eq!(chords.len(), 3);
-        assert_eq!(chords[0].len(), 2);
-        assert_eq!(chords[1].len(), 2);
-        assert_eq!(chords[2].len(), 1);
-
-        assert_eq!(chords[0], vec![Note {base_midi: 57, adjustment: 1}, Note {base_midi: 60, adjustment: 1}]);
-        assert_eq!(chords[1], vec![Note {base_midi: 59, adjustment: 1},  Note {base_midi: 60, adjustment: 1}]);
-        assert_eq!(chords[2], vec![Note {base_midi: 60, adjustment: 1}]);
+    fn test_time_pressure_for_game() {
+        assert_eq!(time_pressure_for_game("[TimeControl \"180+2\"]\n\n1. e4 *"), Some(Duration::from_secs(18)));
+        assert_eq!(time_pressure_for_game("[TimeControl \"60+0\"]\n\n1. e4 *"), Some(Duration::from_secs(10)));
+        assert_eq!(time_pressure_for_game("[TimeControl \"5400+30\"]\n\n1. e4 *"), Some(Duration::from_secs(60)));
+        assert_eq!(time_pressure_for_game("[TimeControl \"-\"]\n\n1. e4 *"), None);
+    }
+
+    #[test]
+    fn test_key_for_game() {
+        let game_str = "[Result \"1-0\"]\n[ECO \"C50\"]\n\n1. e4 e5 1-0";
+        assert_eq!(key_for_game(game_str, &game_for_pgn(game_str).unwrap()), Key::from_eco("C50").unwrap());
+
+        // Without the tag the moves tell the opening
+        let game_str = "[Result \"0-1\"]\n\n1. e4 e5 0-1";
+        assert_eq!(key_for_game(game_str, &game_for_pgn(game_str).unwrap()), Key::from_eco("C20").unwrap());
+
+        let game_str = "[Result \"0-1\"]\n\n1. h4 a5 0-1";
+        assert_eq!(key_for_game(game_str, &game_for_pgn(game_str).unwrap()), Key::from_result("0-1").unwrap());
+
+        assert_eq!(key_for_game("1. h4 a5 *", &game_for_pgn("1. h4 a5 *").unwrap()), Key::default());
+    }
+
+    #[test]
+    fn test_game_for_pgn() {
+        assert_eq!(game_for_pgn("1. e4 e5 2. Nf3 *").unwrap().plies.len(), 3);
+        assert_eq!(game_for_pgn("1. e4 Ke5 2. Nf3 *").err().unwrap().to_string(), "Ply 2: black has no piece that can play Ke5");
+        assert_eq!(game_for_pgn("1. e4 Zz *").err().unwrap().to_string(), "Zz is not a move");
+        assert!(score_for_game("1. e4 Ke5 *", &Options::default()).is_err());
+        assert!(inspect_game("1. e4 Ke5 *", &Options::default()).is_err());
+    }
+
+    #[test]
+    fn test_opening_for_game() {
+        let game_str = "[ECO \"B01\"]\n[Opening \"Scandinavian Defense\"]\n\n1. e4 d5 *";
+        assert_eq!(opening_for_game(game_str, &game_for_pgn(game_str).unwrap()), Some(Opening::new("B01", "Scandinavian Defense")));
+        let game_str = "[ECO \"C60\"]\n\n1. e4 e5 *";
+        assert_eq!(opening_for_game(game_str, &game_for_pgn(game_str).unwrap()).unwrap().name, "Ruy Lopez");
+        let game_str = "1. Nf3 d5 2. d4 *";
+        assert_eq!(opening_for_game(game_str, &game_for_pgn(game_str).unwrap()).unwrap().eco, "D02");
+    }
+
+    #[cfg(all(feature = "midi-live", feature = "lichess"))]
+    mod live {
+        use super::*;
+        use super::super::super::mock_server::{MockServer, MockResponse};
+        use super::super::super::music::midi;
+        use std::sync::{Arc, Mutex};
+        use tokio::runtime::Runtime;
+
+        #[derive(Clone)]
+        struct Recorder {
+            messages: Arc<Mutex<Vec<Vec<u8>>>>
+        }
+
+        impl MidiSink for Recorder {
+            fn send(&mut self, message: &[u8]) {
+                self.messages.lock().unwrap().push(message.to_vec());
+            }
+        }
+
+        #[test]
+        fn test_follow_live() {
+            Runtime::new().unwrap().block_on(async {
+                let fixture = include_str!("../fixtures/stream_game.ndjson");
+                let lines: Vec<&str> = fixture.split_inclusive('\n').collect();
+                let server = MockServer::start(vec![
+                    MockResponse::chunked(&lines),
+                    MockResponse::ok("[Event \"Rated Blitz game\"]\n\n1. e4 e5 *\n\n")
+                ]).await;
+
+                let recorder = Recorder {messages: Arc::new(Mutex::new(Vec::new()))};
+                let client = LichessClient::new().with_base_url(&server.url);
+                let stream = client.stream_game("abcd1234").await.unwrap();
+                let played = follow_live(&client, stream, &Options::default(), recorder.clone()).await.unwrap();
+                // Joined after e4 e5, the echo of e5 isn't played again
+                assert_eq!(played, 5);
+
+                let requests = server.requests();
+                assert!(requests[0].starts_with("GET /api/stream/game/abcd1234 "));
+                assert!(requests[1].starts_with("GET /game/export/abcd1234?"));
+                let messages = recorder.messages.lock().unwrap();
+                assert!(messages.iter().any(|message| message[0] & 0xF0 == midi::NOTE_ON_MSG));
+                assert!(messages.iter().all(|message| midi::validate(message).is_ok()));
+            });
+        }
+
+        async fn follow_tv(exports: &[&str]) -> (usize, Vec<String>) {
+            let fixture = include_str!("../fixtures/tv_feed.ndjson");
+            let lines: Vec<&str> = fixture.split_inclusive('\n').collect();
+            let mut responses = vec![MockResponse::chunked(&lines)];
+            responses.extend(exports.iter().map(|export| MockResponse::ok(export)));
+            let server = MockServer::start(responses).await;
+
+            let recorder = Recorder {messages: Arc::new(Mutex::new(Vec::new()))};
+            let client = LichessClient::new().with_base_url(&server.url);
+            let stream = client.stream_tv().await.unwrap();
+            let played = follow_live(&client, stream, &Options::default(), recorder).await.unwrap();
+            (played, server.requests())
+        }
+
+        #[test]
+        fn test_follow_tv() {
+            Runtime::new().unwrap().block_on(async {
+                // The export already has Nf3, which the feed sends next without a ply to go by
+                let (played, requests) = follow_tv(&["1. e4 e5 2. Nf3 *\n\n"]).await;
+                assert_eq!(played, 3);
+                assert!(requests[0].starts_with("GET /api/tv/feed "));
+                assert!(requests[1].starts_with("GET /game/export/efgh5678?"));
+                assert_eq!(requests.len(), 2);
+
+                // The export is a move behind, Nf3 can't be played so the export is fetched again
+                let (played, requests) = follow_tv(&["1. e4 *\n\n", "1. e4 e5 2. Nf3 *\n\n"]).await;
+                assert_eq!(played, 3);
+                assert_eq!(requests.len(), 3);
+                assert!(requests[2].starts_with("GET /game/export/efgh5678?"));
+
+                // Still behind after catching up, Nf3 is played from where the second export left off
+                let (played, _) = follow_tv(&["1. e4 *\n\n", "1. e4 e5 *\n\n"]).await;
+                assert_eq!(played, 4);
+            });
+        }
     }
 }
@@ -0,0 +1,558 @@
+use chessmusic::{Format, Options, PieceName, Key, MidiPlayer};
+use chessmusic::music::mapping;
+use chessmusic::chess::render::Style;
+use chessmusic::picture::ImageFormat;
+use chessmusic::{pgn, source};
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+pub const USAGE: &str = "Usage: chessmusic <command> [options]
+
+Commands:
+  play <source>               play a game on a MIDI output, with --live follow a Lichess game or tv as it is played
+  render <source> -o <file>   write a game to a .mid, .wav, .musicxml or .ly file
+  draw <source> -o <file>     draw the voiced pieces' paths with their pitches, or with --ply the board, to a .svg or .png file
+  inspect <source>            print the final position and where each voiced piece stood after every ply
+  stats <source>              report how far each piece travelled, what it captured, when it was taken and which squares were busiest
+  batch <source> -o <dir>     render every game of a PGN database into a directory
+  list-ports                  list the MIDI outputs to play to
+  help                        show this message
+
+Sources: a Lichess game id or URL, a PGN file, - for stdin, chesscom:<user>/<yyyy>/<mm>
+
+Options:
+  --source <source>     the source, instead of giving it after the command
+  --game <number>       game of a source with several, from 1
+  --ply <number>        with draw, the board after this many plies instead of the paths
+  --voices <list>       pieces that get a voice: all, white, black or pieces like white-epawn,black-queen,kknight
+  --tempo <bpm>         beats per minute
+  --key <key>           key to play in, like D, F#-minor or Bb-dorian
+  --mapping <name>      how moves become pitches
+  --seed <number>       renders the game differently, the same way for the same seed
+  --evals               fetch Lichess' engine evaluations to drive the harmony
+  --port <name>         MIDI output to play to, part of its name is enough
+  -o, --output <path>   file to render to, or directory for batch
+  --format <mid|wav|musicxml|ly>
+                        what batch renders to, mid by default
+  --live                with play, follow a game as it is played
+  --replay              with play, show the board and the sounding notes as each ply plays
+  --ascii               with --replay, draw the board with letters and no colors
+  --json                with inspect, print the plies, piece histories and score as JSON, with stats the report";
+
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+const MIN_TEMPO: u32 = 10;
+const MAX_TEMPO: u32 = 400;
+
+// The command line was wrong, rather than something going wrong while carrying it out.
+#[derive(Debug, PartialEq)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// EXIT_USAGE when the command line was wrong, EXIT_FAILURE when carrying it out failed.
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    if error.is::<UsageError>() {EXIT_USAGE} else {EXIT_FAILURE}
+}
+
+impl Error for UsageError {}
+
+fn usage_error<T>(message: String) -> Result<T, Box<dyn Error>> {
+    Err(Box::new(UsageError(message)))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    // replay draws the board in the terminal along with the music
+    Play {source: String, game: usize, live: bool, replay: Option<Style>},
+    Render {source: String, game: usize, output: String, format: Format},
+    Draw {source: String, game: usize, output: String, ply: Option<usize>},
+    Inspect {source: String, game: usize, json: bool},
+    Stats {source: String, game: usize, json: bool},
+    Batch {source: String, output: String, format: Format},
+    ListPorts,
+    Help
+}
+
+pub struct Cli {
+    pub command: Command,
+    pub options: Options,
+    pub evals: bool
+}
+
+// Arguments without the program name. Without a command, "<source>" plays and
+// "<source> <output>" renders, as before there were commands.
+pub fn parse(args: &[String]) -> Result<Cli, Box<dyn Error>> {
+    let mut args = args.to_vec();
+    let mut options = Options::default();
+    if take_switch(&mut args, "--help") || take_switch(&mut args, "-h") {
+        return Ok(Cli {command: Command::Help, options, evals: false});
+    }
+    if let Some(name) = take_flag(&mut args, "--mapping")? {
+        let names: Vec<&str> = mapping::MAPPINGS.iter().map(|mapping| mapping.name()).collect();
+        options.mapping = match mapping::from_name(&name) {
+            Some(mapping) => mapping,
+            None => usage_error(format!("Unknown mapping {}, expected one of: {}", name, names.join(", ")))?
+        };
+    }
+    if let Some(seed) = take_flag(&mut args, "--seed")? {
+        options.seed = Some(seed.parse().or_else(|_| usage_error(format!("Seed must be a whole number, got {}", seed)))?);
+    }
+    if let Some(voices) = take_flag(&mut args, "--voices")? {
+        options.pieces = parse_voices(&voices)?;
+    }
+    if let Some(tempo) = take_flag(&mut args, "--tempo")? {
+        options.tempo = match tempo.parse() {
+            Ok(tempo) if (MIN_TEMPO..=MAX_TEMPO).contains(&tempo) => tempo,
+            _ => usage_error(format!("Tempo must be from {} to {} beats per minute, got {}", MIN_TEMPO, MAX_TEMPO, tempo))?
+        };
+    }
+    if let Some(key) = take_flag(&mut args, "--key")? {
+        options.key = match Key::from_name(&key) {
+            Some(key) => Some(key),
+            None => return usage_error(format!("Unknown key {}, expected a tonic and a scale like F#-minor", key))
+        };
+    }
+    options.port = take_flag(&mut args, "--port")?;
+    let game = match take_flag(&mut args, "--game")? {
+        Some(number) => match number.parse() {
+            Ok(number) if number > 0 => Some(number),
+            _ => usage_error(format!("Game must be a number from 1, got {}", number))?
+        },
+        None => None
+    };
+    let output = match take_flag(&mut args, "--output")? {
+        Some(output) => Some(output),
+        None => take_flag(&mut args, "-o")?
+    };
+    let format = match take_flag(&mut args, "--format")? {
+        Some(name) => match Format::from_name(&name) {
+            Some(format) => Some(format),
+            None => return usage_error(format!("Format must be mid, wav, musicxml or ly, got {}", name))
+        },
+        None => None
+    };
+    let ply = match take_flag(&mut args, "--ply")? {
+        Some(number) => match number.parse() {
+            Ok(number) => Some(number),
+            _ => return usage_error(format!("Ply must be a whole number, got {}", number))
+        },
+        None => None
+    };
+    let source = take_flag(&mut args, "--source")?;
+    let live = take_switch(&mut args, "--live");
+    let evals = take_switch(&mut args, "--evals");
+    let json = take_switch(&mut args, "--json");
+    let replay = take_switch(&mut args, "--replay");
+    let ascii = take_switch(&mut args, "--ascii");
+    if let Some(unknown) = args.iter().find(|arg| arg.starts_with('-') && arg.len() > 1) {
+        return usage_error(format!("Unknown option {}", unknown));
+    }
+
+    let style = match (replay, ascii) {
+        (true, true) => Some(Style::ASCII),
+        (true, false) => Some(Style::UNICODE),
+        (false, true) => return usage_error("--ascii only goes with --replay".to_string()),
+        (false, false) => None
+    };
+    let name = if args.is_empty() {String::new()} else {args.remove(0)};
+    let command = match name.as_str() {
+        "play" => Command::Play {source: the_source(source, &mut args)?, game: game.unwrap_or(1), live, replay: style},
+        "render" => {
+            let source = the_source(source, &mut args)?;
+            let output = match output.clone() {
+                Some(output) => output,
+                None if !args.is_empty() => args.remove(0),
+                None => usage_error("render needs a file to write to, give it with --output".to_string())?
+            };
+            Command::Render {format: format_for(&output)?, source, game: game.unwrap_or(1), output}
+        },
+        "draw" => {
+            let source = the_source(source, &mut args)?;
+            let output = match output.clone() {
+                Some(output) => output,
+                None if !args.is_empty() => args.remove(0),
+                None => usage_error("draw needs a file to write to, give it with --output".to_string())?
+            };
+            if ImageFormat::from_path(&output).is_none() {
+                return usage_error(format!("Can't tell what to draw {} as, expected a .svg or .png file", output));
+            }
+            Command::Draw {source, game: game.unwrap_or(1), output, ply}
+        },
+        "inspect" => Command::Inspect {source: the_source(source, &mut args)?, game: game.unwrap_or(1), json},
+        "stats" => Command::Stats {source: the_source(source, &mut args)?, game: game.unwrap_or(1), json},
+        "batch" => {
+            let source = the_source(source, &mut args)?;
+            let output = match output.clone() {
+                Some(output) => output,
+                None => usage_error("batch needs a directory to write to, give it with --output".to_string())?
+            };
+            Command::Batch {source, output, format: format.unwrap_or(Format::Midi)}
+        },
+        "list-ports" => Command::ListPorts,
+        "help" => Command::Help,
+        "" => usage_error("No command given".to_string())?,
+        _ => {
+            // A source without a command
+            args.insert(0, name);
+            match (source, args.len()) {
+                (None, 1) => Command::Play {source: args.remove(0), game: game.unwrap_or(1), live, replay: style},
+                (None, 2) => {
+                    let output = args.remove(1);
+                    Command::Render {format: format_for(&output)?, source: args.remove(0), game: game.unwrap_or(1), output}
+                },
+                _ => usage_error(format!("Unknown command {}, expected one of: play, render, draw, inspect, stats, batch, list-ports, help", args[0]))?
+            }
+        }
+    };
+    if !args.is_empty() {
+        return usage_error(format!("Unexpected argument {}", args[0]));
+    }
+    if live && !matches!(command, Command::Play {..}) {
+        return usage_error("--live only goes with play".to_string());
+    }
+    if replay && !matches!(command, Command::Play {live: false, ..}) {
+        return usage_error("--replay only goes with play, and not with --live".to_string());
+    }
+    if json && !matches!(command, Command::Inspect {..} | Command::Stats {..}) {
+        return usage_error("--json only goes with inspect and stats".to_string());
+    }
+    if game.is_some() && matches!(command, Command::Batch {..}) {
+        return usage_error("batch renders every game, --game doesn't go with it".to_string());
+    }
+    if format.is_some() && !matches!(command, Command::Batch {..}) {
+        return usage_error("--format only goes with batch, render goes by the file extension".to_string());
+    }
+    if ply.is_some() && !matches!(command, Command::Draw {..}) {
+        return usage_error("--ply only goes with draw".to_string());
+    }
+    if output.is_some() && !matches!(command, Command::Render {..} | Command::Draw {..} | Command::Batch {..}) {
+        return usage_error("--output only goes with render, draw and batch".to_string());
+    }
+    Ok(Cli {command, options, evals})
+}
+
+pub async fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let options = &cli.options;
+    match cli.command {
+        Command::Help => println!("{}", USAGE),
+        Command::ListPorts => {
+            let ports = MidiPlayer::list_ports()?;
+            if ports.is_empty() {
+                println!("No MIDI output ports found");
+            }
+            for (i, name) in ports.iter().enumerate() {
+                println!("{}: {}", i, name);
+            }
+        },
+        Command::Play {source, live: true, ..} => {
+            println!("Following game {}", source);
+            chessmusic::play_live(&source, options).await?;
+        },
+        Command::Play {source, game, replay: Some(style), ..} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            chessmusic::replay_game(&game_str, options, style).await?;
+        },
+        Command::Play {source, game, ..} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            println!("Game:\n\n{}", game_str);
+            chessmusic::play_game(&game_str, options).await?;
+        },
+        Command::Render {source, game, output, format} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            chessmusic::save_game(&game_str, options, format, &output)?;
+            println!("Saved {} to {}", source, output);
+        },
+        Command::Draw {source, game, output, ply} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            chessmusic::draw_game(&game_str, options, ply, &output)?;
+            println!("Drew {} to {}", source, output);
+        },
+        Command::Stats {source, game, json: true} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            println!("{}", chessmusic::game_stats_json(&game_str)?);
+        },
+        Command::Stats {source, game, ..} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            print!("{}", chessmusic::game_stats(&game_str)?.report());
+        },
+        Command::Inspect {source, game, json: true} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            println!("{}", chessmusic::game_json(&game_str, options)?);
+        },
+        Command::Inspect {source, game, ..} => {
+            let game_str = load_game(&source, game, cli.evals).await?;
+            print!("{}", chessmusic::inspect_game(&game_str, options)?);
+        },
+        Command::Batch {source, output, format} => {
+            let source = source::from_arg(&source, cli.evals)?;
+            let games = source.games().await?;
+            if games.is_empty() {
+                Err(format!("Found no games in {}", source.describe()))?;
+            }
+            fs::create_dir_all(&output).map_err(|error| format!("Could not create {}: {}", output, error))?;
+            let mut failed = 0;
+            for (idx, game_str) in games.iter().enumerate() {
+                let path = Path::new(&output).join(batch_file_name(idx + 1, game_str, format));
+                match chessmusic::save_game(game_str, options, format, &path.to_string_lossy()) {
+                    Ok(()) => println!("{}", path.display()),
+                    Err(error) => {
+                        eprintln!("Game {}: {}", idx + 1, error);
+                        failed += 1;
+                    }
+                }
+            }
+            println!("Rendered {} of {} games from {} to {}", games.len() - failed, games.len(), source.describe(), output);
+            if failed > 0 {
+                Err(format!("{} of {} games could not be rendered", failed, games.len()))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn load_game(arg: &str, game_number: usize, evals: bool) -> Result<String, Box<dyn Error>> {
+    let source = source::from_arg(arg, evals)?;
+    let mut games = source.games().await?;
+    if game_number > games.len() {
+        Err(format!("Found {} games in {}, there is no game {}", games.len(), source.describe(), game_number))?;
+    }
+    if games.len() > 1 {
+        eprintln!("Game {} of {} from {}", game_number, games.len(), source.describe());
+    }
+    Ok(games.swap_remove(game_number - 1))
+}
+
+// "all", "white", "black", or pieces with an optional side: "white-epawn,black-queen,kknight".
+fn parse_voices(list: &str) -> Result<Vec<(PieceName, bool)>, Box<dyn Error>> {
+    let mut pieces = Vec::new();
+    for item in list.split(',').map(|item| item.trim().to_lowercase()) {
+        let (sides, names): (&[bool], Vec<PieceName>) = match item.as_str() {
+            "all" => (&[true, false], PieceName::ALL.to_vec()),
+            "white" => (&[true], PieceName::ALL.to_vec()),
+            "black" => (&[false], PieceName::ALL.to_vec()),
+            _ => {
+                let (sides, name): (&[bool], &str) = if let Some(name) = item.strip_prefix("white-") {
+                    (&[true], name)
+                } else if let Some(name) = item.strip_prefix("black-") {
+                    (&[false], name)
+                } else {
+                    (&[true, false], &item)
+                };
+                match PieceName::from_name(name) {
+                    Some(piece) => (sides, vec![piece]),
+                    None => return usage_error(format!("Unknown voice {}, expected all, white, black or a piece like white-epawn or black-qknight", item))
+                }
+            }
+        };
+        for white in sides.iter() {
+            for name in names.iter() {
+                if !pieces.contains(&(*name, *white)) {
+                    pieces.push((*name, *white));
+                }
+            }
+        }
+    }
+    Ok(pieces)
+}
+
+fn the_source(flag: Option<String>, args: &mut Vec<String>) -> Result<String, Box<dyn Error>> {
+    match flag {
+        Some(source) => Ok(source),
+        None if !args.is_empty() => Ok(args.remove(0)),
+        None => usage_error("No source given, expected a Lichess game, a PGN file, - or chesscom:<user>/<yyyy>/<mm>".to_string())
+    }
+}
+
+fn format_for(output: &str) -> Result<Format, Box<dyn Error>> {
+    match Format::from_path(output) {
+        Some(format) => Ok(format),
+        None => usage_error(format!("Can't tell what to render {} to, expected a .mid, .wav, .musicxml or .ly file", output))
+    }
+}
+
+// "007-Carlsen-Nakamura.mid", or just the number when the players aren't known.
+fn batch_file_name(number: usize, game_str: &str, format: Format) -> String {
+    let mut name = format!("{:03}", number);
+    for tag in ["White", "Black"].iter() {
+        if let Some(player) = pgn::parse_tag(game_str, tag) {
+            let player: String = player.chars().map(|c| if c.is_ascii_alphanumeric() {c} else {'_'}).collect();
+            name.push('-');
+            name.push_str(player.trim_matches('_'));
+        }
+    }
+    format!("{}.{}", name, format.extension())
+}
+
+// Removes "--flag value" from the arguments, returning the value.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let idx = match args.iter().position(|arg| arg == flag) {
+        Some(idx) => idx,
+        None => return Ok(None)
+    };
+    let value = match args.get(idx + 1) {
+        Some(value) => value.clone(),
+        None => usage_error(format!("{} needs a value", flag))?
+    };
+    args.drain(idx..=idx + 1);
+    Ok(Some(value))
+}
+
+fn take_switch(args: &mut Vec<String>, switch: &str) -> bool {
+    let idx = args.iter().position(|arg| arg == switch);
+    if let Some(idx) = idx {
+        args.remove(idx);
+    }
+    idx.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chessmusic::Scale;
+
+    fn parse_line(line: &str) -> Result<Cli, Box<dyn Error>> {
+        parse(&line.split_whitespace().map(String::from).collect::<Vec<String>>())
+    }
+
+    fn usage(line: &str) -> String {
+        match parse_line(line) {
+            Ok(cli) => panic!("{} parsed as {:?}", line, cli.command),
+            Err(error) => {
+                assert!(error.is::<UsageError>(), "{}", error);
+                error.to_string()
+            }
+        }
+    }
+
+    #[test]
+    fn test_commands() {
+        let command = |line| parse_line(line).unwrap().command;
+        let source = || "tzUJbFEX".to_string();
+        assert_eq!(command("play tzUJbFEX"), Command::Play {source: source(), game: 1, live: false, replay: None});
+        assert_eq!(command("play --live tv"), Command::Play {source: "tv".to_string(), game: 1, live: true, replay: None});
+        assert_eq!(command("play tzUJbFEX --replay"), Command::Play {source: source(), game: 1, live: false, replay: Some(Style::UNICODE)});
+        assert_eq!(command("draw tzUJbFEX -o paths.svg"), Command::Draw {source: source(), game: 1, output: "paths.svg".to_string(), ply: None});
+        assert_eq!(command("draw tzUJbFEX board.png --ply 12"), Command::Draw {source: source(), game: 1, output: "board.png".to_string(), ply: Some(12)});
+        assert_eq!(command("play tzUJbFEX --replay --ascii"), Command::Play {source: source(), game: 1, live: false, replay: Some(Style::ASCII)});
+        assert_eq!(command("render --source tzUJbFEX -o game.wav"), Command::Render {source: source(), game: 1, output: "game.wav".to_string(), format: Format::Wav});
+        assert_eq!(command("render games.pgn --game 3 game.mid"), Command::Render {source: "games.pgn".to_string(), game: 3, output: "game.mid".to_string(), format: Format::Midi});
+        assert_eq!(command("inspect - --game 2"), Command::Inspect {source: "-".to_string(), game: 2, json: false});
+        assert_eq!(command("inspect tzUJbFEX --json"), Command::Inspect {source: source(), game: 1, json: true});
+        assert_eq!(command("stats games.pgn --game 3 --json"), Command::Stats {source: "games.pgn".to_string(), game: 3, json: true});
+        assert_eq!(command("render games.pgn -o game.ly"), Command::Render {source: "games.pgn".to_string(), game: 1, output: "game.ly".to_string(), format: Format::LilyPond});
+        assert_eq!(command("batch games.pgn --output out --format wav"), Command::Batch {source: "games.pgn".to_string(), output: "out".to_string(), format: Format::Wav});
+        assert_eq!(command("batch games.pgn --output out"), Command::Batch {source: "games.pgn".to_string(), output: "out".to_string(), format: Format::Midi});
+        assert_eq!(command("list-ports"), Command::ListPorts);
+        assert_eq!(command("help"), Command::Help);
+        assert_eq!(command("render --help"), Command::Help);
+        // Without a command
+        assert_eq!(command("tzUJbFEX"), Command::Play {source: source(), game: 1, live: false, replay: None});
+        assert_eq!(command("tzUJbFEX game.mid"), Command::Render {source: source(), game: 1, output: "game.mid".to_string(), format: Format::Midi});
+    }
+
+    #[test]
+    fn test_options() {
+        let cli = parse_line("play --tempo 140 --key d-dorian --port fluid --seed 7 --evals --voices white-queen,black-epawn tzUJbFEX").unwrap();
+        assert_eq!(cli.options.tempo, 140);
+        assert_eq!(cli.options.key, Some(Key::new(62, Scale::Dorian)));
+        assert_eq!(cli.options.port, Some("fluid".to_string()));
+        assert_eq!(cli.options.seed, Some(7));
+        assert_eq!(cli.options.pieces, vec![(PieceName::Queen, true), (PieceName::Epawn, false)]);
+        assert!(cli.evals);
+
+        let cli = parse_line("play tzUJbFEX").unwrap();
+        assert_eq!(cli.options.key, None);
+        assert!(!cli.evals);
+    }
+
+    #[test]
+    fn test_voices() {
+        assert_eq!(parse_voices("all").unwrap().len(), 32);
+        assert_eq!(parse_voices("white").unwrap(), PieceName::ALL.iter().map(|name| (*name, true)).collect::<Vec<_>>());
+        assert_eq!(parse_voices("kknight, white-kknight").unwrap(), vec![(PieceName::Kknight, true), (PieceName::Kknight, false)]);
+        assert!(parse_voices("white-horse").is_err());
+        assert!(parse_voices("white-white").is_err());
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        assert_eq!(usage(""), "No command given");
+        assert!(usage("dance tzUJbFEX extra.mid more").starts_with("Unknown command dance"));
+        assert_eq!(usage("play"), "No source given, expected a Lichess game, a PGN file, - or chesscom:<user>/<yyyy>/<mm>");
+        assert_eq!(usage("play tzUJbFEX --tempo"), "--tempo needs a value");
+        assert!(usage("play tzUJbFEX --tempo 1000").starts_with("Tempo must be from 10 to 400"));
+        assert!(usage("play tzUJbFEX --key H").starts_with("Unknown key H"));
+        assert!(usage("play tzUJbFEX --mapping nope").starts_with("Unknown mapping nope"));
+        assert!(usage("play tzUJbFEX --game 0").starts_with("Game must be a number from 1"));
+        assert_eq!(usage("play tzUJbFEX --loud"), "Unknown option --loud");
+        assert_eq!(usage("play tzUJbFEX extra"), "Unexpected argument extra");
+        assert!(usage("render tzUJbFEX").starts_with("render needs a file"));
+        assert!(usage("render tzUJbFEX -o game.mp3").starts_with("Can't tell what to render game.mp3 to"));
+        assert!(usage("batch games.pgn").starts_with("batch needs a directory"));
+        assert!(usage("batch games.pgn -o out --format flac").starts_with("Format must be mid, wav, musicxml or ly"));
+        assert_eq!(usage("inspect tzUJbFEX --live"), "--live only goes with play");
+        assert_eq!(usage("play tzUJbFEX --json"), "--json only goes with inspect and stats");
+        assert_eq!(usage("play --live tv --replay"), "--replay only goes with play, and not with --live");
+        assert_eq!(usage("play tzUJbFEX --ascii"), "--ascii only goes with --replay");
+        assert_eq!(usage("play tzUJbFEX -o game.mid"), "--output only goes with render, draw and batch");
+        assert_eq!(usage("draw tzUJbFEX -o paths.gif"), "Can't tell what to draw paths.gif as, expected a .svg or .png file");
+        assert_eq!(usage("draw tzUJbFEX"), "draw needs a file to write to, give it with --output");
+        assert_eq!(usage("render tzUJbFEX game.mid --ply 3"), "--ply only goes with draw");
+        assert_eq!(usage("draw tzUJbFEX board.png --ply last"), "Ply must be a whole number, got last");
+    }
+
+    #[test]
+    fn test_batch_file_name() {
+        let game_str = "[White \"Carlsen, Magnus\"]\n[Black \"Nakamura\"]\n\n1. e4 *";
+        assert_eq!(batch_file_name(7, game_str, Format::Midi), "007-Carlsen__Magnus-Nakamura.mid");
+        assert_eq!(batch_file_name(12, "1. e4 *", Format::Wav), "012.wav");
+    }
+
+    #[test]
+    fn test_batch() {
+        let dir = std::env::temp_dir().join("chessmusic_test_batch");
+        let _ = fs::remove_dir_all(&dir);
+        let path = std::env::temp_dir().join("chessmusic_test_batch.pgn");
+        fs::write(&path, "[White \"One\"]\n\n1. e4 e5 *\n\n[White \"Two\"]\n\n1. d4 d5 *\n").unwrap();
+        let line = format!("batch {} -o {}", path.display(), dir.display());
+        tokio::runtime::Runtime::new().unwrap().block_on(run(parse_line(&line).unwrap())).unwrap();
+        let mut names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(names, vec!["001-One.mid", "002-Two.mid"]);
+    }
+
+    #[test]
+    fn test_batch_with_an_illegal_game() {
+        let dir = std::env::temp_dir().join("chessmusic_test_batch_illegal");
+        let _ = fs::remove_dir_all(&dir);
+        let path = std::env::temp_dir().join("chessmusic_test_batch_illegal.pgn");
+        fs::write(&path, "[White \"One\"]\n\n1. e4 e5 *\n\n[White \"Two\"]\n\n1. e4 Ke5 *\n\n[White \"Three\"]\n\n1. d4 d5 *\n").unwrap();
+        let line = format!("batch {} -o {}", path.display(), dir.display());
+        let error = tokio::runtime::Runtime::new().unwrap().block_on(run(parse_line(&line).unwrap())).unwrap_err();
+        let mut names: Vec<String> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().to_string()).collect();
+        names.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(error.to_string(), "1 of 3 games could not be rendered");
+        assert_eq!(exit_code(error.as_ref()), EXIT_FAILURE);
+        assert_eq!(names, vec!["001-One.mid", "003-Three.mid"]);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        assert_eq!(exit_code(&UsageError("Unknown command".to_string())), EXIT_USAGE);
+        let error: Box<dyn Error> = "Could not write game.mid".into();
+        assert_eq!(exit_code(error.as_ref()), EXIT_FAILURE);
+    }
+}
@@ -0,0 +1,97 @@
+// Openings by ECO code. A game is classified by the positions it goes through, so a
+// transposed move order finds the same opening as the book one.
+use super::pgn;
+use super::chess::{Game, Move};
+
+use std::sync::OnceLock;
+
+// Code, name and the moves of each line as PGN, in the layout of the Lichess openings table
+const TABLE: &str = include_str!("../data/eco.tsv");
+// A game may reach a book position a few plies late, by wasting tempi on the way
+const SLACK: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+    // The book line in SAN, empty when the opening came from the PGN tags
+    pub moves: Vec<String>
+}
+
+impl Opening {
+    pub fn new(eco: &str, name: &str) -> Opening {
+        Opening {eco: eco.to_string(), name: name.to_string(), moves: Vec::new()}
+    }
+
+    // Zobrist hash of the position at the end of the book line
+    fn zobrist(&self) -> u64 {
+        let moves: Vec<&str> = self.moves.iter().map(|the_move| the_move.as_str()).collect();
+        Game::new_with_moves(&Move::parse_moves(&moves)).board.zobrist()
+    }
+}
+
+// Every line in the table, in table order. Parsed once.
+pub fn openings() -> &'static [Opening] {
+    static OPENINGS: OnceLock<Vec<Opening>> = OnceLock::new();
+    OPENINGS.get_or_init(|| TABLE.lines().skip(1).filter_map(|line| {
+        let mut fields = line.split('\t');
+        let (eco, name, moves) = (fields.next()?, fields.next()?, fields.next()?);
+        let moves = pgn::parse_moves(moves).iter().map(|the_move| the_move.to_string()).collect();
+        Some(Opening {eco: eco.to_string(), name: name.to_string(), moves})
+    }).collect())
+}
+
+// Every opening with the hash of where its line ends, the lines are played out once.
+fn book() -> &'static [(&'static Opening, u64)] {
+    static BOOK: OnceLock<Vec<(&'static Opening, u64)>> = OnceLock::new();
+    BOOK.get_or_init(|| openings().iter().map(|opening| (opening, opening.zobrist())).collect())
+}
+
+// The opening with the longest book line whose final position the game passes through.
+pub fn classify(game: &Game) -> Option<Opening> {
+    let deepest = openings().iter().map(|opening| opening.moves.len()).max().unwrap_or(0);
+    let positions: Vec<u64> = game.plies.iter().take(deepest + SLACK).map(|ply| ply.zobrist).collect();
+    book().iter()
+        .filter(|(_, zobrist)| positions.contains(zobrist))
+        .max_by_key(|(opening, _)| opening.moves.len())
+        .map(|(opening, _)| (*opening).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_moves(moves: &[&str]) -> Option<Opening> {
+        classify(&Game::new_with_moves(&Move::parse_moves(moves)))
+    }
+
+    #[test]
+    fn test_openings() {
+        let openings = openings();
+        assert!(openings.len() > 100);
+        let ruy_lopez = openings.iter().find(|opening| opening.eco == "C60").unwrap();
+        assert_eq!(ruy_lopez.name, "Ruy Lopez");
+        assert_eq!(ruy_lopez.moves, vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        // Parsed and played out once, every call shares the table
+        assert!(std::ptr::eq(openings, super::openings()));
+        assert_eq!(book().len(), openings.len());
+    }
+
+    #[test]
+    fn test_classify() {
+        let opening = classify_moves(&["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7", "Re1"]).unwrap();
+        assert_eq!((opening.eco.as_str(), opening.name.as_str()), ("C84", "Ruy Lopez: Closed"));
+        // Past the book on the first move
+        assert_eq!(classify_moves(&["h4", "a5"]), None);
+        assert_eq!(classify_moves(&["e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4"]).unwrap().eco, "B54");
+    }
+
+    #[test]
+    fn test_classify_transposition() {
+        // The Semi-Slav reached through the Queen's Gambit Declined
+        let opening = classify_moves(&["d4", "d5", "c4", "e6", "Nc3", "Nf6", "Nf3", "c6"]).unwrap();
+        assert_eq!((opening.eco.as_str(), opening.name.as_str()), ("D43", "Semi-Slav Defense"));
+        // The Four Knights with the knights out in another order
+        assert_eq!(classify_moves(&["e4", "e5", "Nc3", "Nf6", "Nf3", "Nc6"]).unwrap().eco, "C47");
+    }
+}
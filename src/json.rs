@@ -0,0 +1,325 @@
+// Machine readable games and scores, for web frontends and data pipelines.
+// Pieces, roles and move types are lower case names, cells are like "e4" and times in milliseconds.
+use super::chess::{Cell, Game, GameStats, Ply, PieceName};
+use super::chess::types::{Role, MoveType};
+use super::music::Score;
+
+use serde::Serialize;
+use std::time::Duration;
+
+#[derive(Debug, Serialize)]
+pub struct GameJson {
+    pub plies: Vec<PlyJson>,
+    pub pieces: Vec<PieceJson>
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlyJson {
+    pub white: bool,
+    pub role: String,
+    pub move_type: String,
+    pub cell: String,
+    pub check: bool,
+    // Pieces moved in this ply, king and rook when castling
+    pub moved: Vec<String>,
+    pub captured: Option<String>,
+    pub think_time_ms: Option<u64>,
+    pub clock_ms: Option<u64>,
+    // Centipawns for white, the engine's when the game has them
+    pub advantage: i32
+}
+
+#[derive(Debug, Serialize)]
+pub struct PieceJson {
+    pub name: String,
+    pub white: bool,
+    pub role: String,
+    pub start: String,
+    // Still on the board at the end
+    pub live: bool,
+    // Cells the piece stood on, as the music follows it, and whether it captured there
+    pub history: Vec<HistoryJson>
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryJson {
+    pub cell: String,
+    pub capture: bool
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreJson {
+    // Beats per minute at the start, the tempo changes are percentages of it
+    pub tempo: u32,
+    pub ticks_per_beat: u32,
+    pub ply_ticks: Vec<u32>,
+    pub tempo_changes: Vec<TempoChangeJson>,
+    pub voices: Vec<VoiceJson>
+}
+
+#[derive(Debug, Serialize)]
+pub struct TempoChangeJson {
+    pub tick: u32,
+    pub percent: u32
+}
+
+#[derive(Debug, Serialize)]
+pub struct VoiceJson {
+    pub name: String,
+    pub piece: Option<String>,
+    pub white: Option<bool>,
+    pub channel: u8,
+    pub program: u8,
+    pub notes: Vec<NoteJson>
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteJson {
+    pub ply: usize,
+    pub tick: u32,
+    pub duration: u32,
+    pub time_ms: u64,
+    pub duration_ms: u64,
+    pub pitch: u8,
+    pub velocity: i32
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatsJson {
+    pub plies: usize,
+    pub mate: Option<MateJson>,
+    pub pieces: Vec<PieceStatsJson>,
+    // Moves that ended on each square, rows from the first, files from a
+    pub heatmap: Vec<Vec<u32>>
+}
+
+#[derive(Debug, Serialize)]
+pub struct MateJson {
+    pub piece: String,
+    pub white: bool
+}
+
+#[derive(Debug, Serialize)]
+pub struct PieceStatsJson {
+    pub name: String,
+    pub white: bool,
+    pub role: String,
+    pub moves: usize,
+    pub distance: u32,
+    pub visited: Vec<String>,
+    pub captures: usize,
+    // Index of the ply it was captured in
+    pub captured_ply: Option<usize>,
+    pub lifetime: usize
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExportJson {
+    pub game: GameJson,
+    pub score: ScoreJson
+}
+
+impl GameJson {
+    pub fn new(game: &Game) -> GameJson {
+        let plies = game.plies.iter().map(PlyJson::new).collect();
+        let pieces = game.board.pieces.iter().map(|piece| PieceJson {
+            name: piece_name(piece.get_name()),
+            white: piece.is_white(),
+            role: role_name(piece.get_role()).to_string(),
+            start: cell_name(&piece.first_cell()),
+            live: piece.is_live(),
+            history: piece.get_cell_and_capture_history().iter()
+                .map(|(cell, capture)| HistoryJson {cell: cell_name(cell), capture: *capture})
+                .collect()
+        }).collect();
+        GameJson {plies, pieces}
+    }
+}
+
+impl PlyJson {
+    fn new(ply: &Ply) -> PlyJson {
+        PlyJson {
+            white: ply.white,
+            role: role_name(ply.the_move.role).to_string(),
+            move_type: move_type_name(ply.the_move.move_type).to_string(),
+            cell: cell_name(&ply.the_move.cell),
+            check: ply.the_move.is_check(),
+            moved: ply.moved.iter().map(|name| piece_name(*name)).collect(),
+            captured: ply.captured.map(piece_name),
+            think_time_ms: ply.think_time.as_ref().map(millis),
+            clock_ms: ply.clock.as_ref().map(millis),
+            advantage: ply.advantage()
+        }
+    }
+}
+
+impl ScoreJson {
+    pub fn new(score: &Score, tempo: u32) -> ScoreJson {
+        let tempo_map = score.tempo_map(tempo);
+        let ms_at = |tick: u32| tempo_map.micros_at(tick) / 1000;
+        let voices = score.voices.iter().map(|voice| VoiceJson {
+            name: voice.name.clone(),
+            piece: voice.piece.map(|(name, _)| piece_name(name)),
+            white: voice.piece.map(|(_, white)| white),
+            channel: voice.instrument.channel,
+            program: voice.instrument.program,
+            notes: voice.notes().map(|event| {
+                let note = event.note.as_ref().unwrap();
+                NoteJson {
+                    ply: event.ply,
+                    tick: event.tick,
+                    duration: event.duration,
+                    time_ms: ms_at(event.tick),
+                    duration_ms: ms_at(event.end()) - ms_at(event.tick),
+                    pitch: note.as_midi(),
+                    velocity: note.velocity
+                }
+            }).collect()
+        }).collect();
+        ScoreJson {
+            tempo,
+            ticks_per_beat: super::music::rhythm::TICKS_PER_BEAT,
+            ply_ticks: score.ply_ticks.clone(),
+            tempo_changes: score.tempo_changes.iter().map(|(tick, percent)| TempoChangeJson {tick: *tick, percent: *percent}).collect(),
+            voices
+        }
+    }
+}
+
+impl StatsJson {
+    pub fn new(stats: &GameStats) -> StatsJson {
+        StatsJson {
+            plies: stats.plies,
+            mate: stats.mate.map(|(name, white)| MateJson {piece: piece_name(name), white}),
+            pieces: stats.pieces.iter().map(|piece| PieceStatsJson {
+                name: piece_name(piece.name),
+                white: piece.white,
+                role: role_name(piece.role).to_string(),
+                moves: piece.moves,
+                distance: piece.distance,
+                visited: piece.visited.iter().map(cell_name).collect(),
+                captures: piece.captures,
+                captured_ply: piece.captured_at,
+                lifetime: piece.lifetime(stats.plies)
+            }).collect(),
+            heatmap: stats.heatmap.iter().map(|row| row.to_vec()).collect()
+        }
+    }
+}
+
+pub fn stats_to_string(stats: &GameStats) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&StatsJson::new(stats))
+}
+
+// The game and the music made from it in one document.
+pub fn to_string(game: &Game, score: &Score, tempo: u32) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&ExportJson {game: GameJson::new(game), score: ScoreJson::new(score, tempo)})
+}
+
+fn cell_name(cell: &Cell) -> String {
+    format!("{}{}", cell.file, cell.row)
+}
+
+fn piece_name(name: PieceName) -> String {
+    format!("{:?}", name).to_lowercase()
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Pawn => "pawn",
+        Role::Bishop => "bishop",
+        Role::Knight => "knight",
+        Role::Rook => "rook",
+        Role::Queen => "queen",
+        Role::King => "king"
+    }
+}
+
+fn move_type_name(move_type: MoveType) -> &'static str {
+    match move_type {
+        MoveType::None => "none",
+        MoveType::Simple => "simple",
+        MoveType::Take => "take",
+        MoveType::CastleKing => "castle_king",
+        MoveType::CastleQueen => "castle_queen"
+    }
+}
+
+fn millis(duration: &Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::chess::Move;
+    use super::super::music::score::ScoreConfig;
+
+    fn game() -> Game {
+        let mut game = Game::new_with_moves(&Move::parse_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3+"]));
+        game.set_think_times(&[None, Some(Duration::from_millis(2500))]);
+        game
+    }
+
+    #[test]
+    fn test_game_json() {
+        let json = serde_json::to_value(GameJson::new(&game())).unwrap();
+        assert_eq!(json["plies"].as_array().unwrap().len(), 5);
+        assert_eq!(json["plies"][2]["move_type"], "take");
+        assert_eq!(json["plies"][2]["cell"], "d5");
+        assert_eq!(json["plies"][2]["captured"], "dpawn");
+        assert_eq!(json["plies"][1]["think_time_ms"], 2500);
+        assert_eq!(json["plies"][4]["check"], true);
+        assert_eq!(json["plies"][4]["role"], "knight");
+
+        let pieces = json["pieces"].as_array().unwrap();
+        assert_eq!(pieces.len(), 32);
+        let epawn = pieces.iter().find(|piece| piece["name"] == "epawn" && piece["white"] == true).unwrap();
+        assert_eq!(epawn["start"], "e2");
+        let cells: Vec<&str> = epawn["history"].as_array().unwrap().iter().map(|step| step["cell"].as_str().unwrap()).collect();
+        assert_eq!(cells, vec!["e4", "e4", "d5"]);
+        assert_eq!(epawn["history"][2]["capture"], true);
+        assert_eq!(epawn["live"], false);
+    }
+
+    #[test]
+    fn test_score_json() {
+        let score = Score::from_game(&game(), &[(PieceName::Queen, false)], &ScoreConfig::default());
+        let json = serde_json::to_value(ScoreJson::new(&score, 120)).unwrap();
+        assert_eq!(json["tempo"], 120);
+        assert_eq!(json["ply_ticks"].as_array().unwrap().len(), 6);
+        let voice = &json["voices"][0];
+        assert_eq!(voice["piece"], "queen");
+        assert_eq!(voice["white"], false);
+        let notes = voice["notes"].as_array().unwrap();
+        assert!(!notes.is_empty());
+        // A quarter note at 120 beats per minute
+        let note = notes.iter().find(|note| note["duration"] == 480).unwrap();
+        assert_eq!(note["duration_ms"], 500);
+        assert_eq!(note["time_ms"], note["tick"].as_u64().unwrap() * 500 / 480);
+    }
+
+    #[test]
+    fn test_stats_json() {
+        let json = serde_json::to_value(StatsJson::new(&game().stats())).unwrap();
+        assert_eq!(json["plies"], 5);
+        assert!(json["mate"].is_null());
+        let queen = json["pieces"].as_array().unwrap().iter().find(|piece| piece["name"] == "queen" && piece["white"] == false).unwrap();
+        assert_eq!(queen["visited"], serde_json::json!(["d8", "d5"]));
+        assert_eq!(queen["captures"], 1);
+        let epawn = json["pieces"].as_array().unwrap().iter().find(|piece| piece["name"] == "epawn" && piece["white"] == true).unwrap();
+        assert_eq!(epawn["captured_ply"], 3);
+        assert_eq!(epawn["lifetime"], 3);
+        // Black's pawn, white's pawn and black's queen all ended up on d5
+        assert_eq!(json["heatmap"][4][3], 3);
+    }
+
+    #[test]
+    fn test_to_string() {
+        let game = game();
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true)], &ScoreConfig::default());
+        let json: serde_json::Value = serde_json::from_str(&to_string(&game, &score, 100).unwrap()).unwrap();
+        assert!(json["game"]["plies"].is_array());
+        assert!(json["score"]["voices"].is_array());
+    }
+}
@@ -0,0 +1,39 @@
+// Music from chess games: PGN in, piece histories through the chess module,
+// a Score out of the music module, rendered to MIDI files, WAV, sheet music or a MIDI output.
+pub mod chess;
+pub mod pgn;
+pub mod eco;
+pub mod music;
+#[cfg(feature = "lichess")]
+pub mod lichess;
+#[cfg(feature = "lichess")]
+pub mod source;
+pub mod chessmusic;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "pictures")]
+pub mod picture;
+#[cfg(all(test, feature = "lichess"))]
+mod mock_server;
+
+pub use chess::{Game, GameStats, Board, Move, Ply, Cell, PieceName};
+pub use music::{Melody, Score, Key, Scale, Note};
+pub use music::score::ScoreConfig;
+#[cfg(feature = "smf")]
+pub use music::smf;
+#[cfg(feature = "synth")]
+pub use music::synth;
+#[cfg(feature = "notation")]
+pub use music::{musicxml, lilypond};
+#[cfg(feature = "midi-live")]
+pub use music::MidiPlayer;
+pub use eco::Opening;
+pub use chessmusic::{Options, Format, score_for_game, opening_for_game, inspect_game, save_game, game_stats};
+#[cfg(feature = "json")]
+pub use chessmusic::{game_json, game_stats_json};
+#[cfg(feature = "pictures")]
+pub use chessmusic::draw_game;
+#[cfg(feature = "midi-live")]
+pub use chessmusic::{play_game, replay_game};
+#[cfg(all(feature = "midi-live", feature = "lichess"))]
+pub use chessmusic::play_live;
@@ -1,27 +1,589 @@
 use std::error::Error;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-pub async fn get_game(game_id: &str) -> Result<String, Box<dyn Error>> {
-    let url = format!("https://lichess.org/game/export/{}?clocks=false&evals=false", game_id);
-    let response = reqwest::get(&url).await?;
-    let success = response.status().is_success();
-    if !success {
-        println!("Request was not successful.");
-        Err("Bad request")?;
+use reqwest::StatusCode;
+
+const LICHESS_URL: &str = "https://lichess.org";
+// Lichess asks clients that got a 429 to wait a full minute before the next request
+const RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+// Doubled after every server error in a row
+const SERVER_ERROR_WAIT: Duration = Duration::from_secs(1);
+const RETRIES: u32 = 2;
+
+#[derive(Debug)]
+pub enum LichessError {
+    // No such game or user
+    NotFound(String),
+    // Missing or bad OAuth token
+    Unauthorized,
+    // Still rate limited after waiting
+    RateLimited,
+    Server(StatusCode),
+    Unexpected(StatusCode),
+    Network(reqwest::Error)
+}
+
+impl fmt::Display for LichessError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LichessError::NotFound(what) => write!(f, "Lichess has no {}", what),
+            LichessError::Unauthorized => write!(f, "Lichess refused the token"),
+            LichessError::RateLimited => write!(f, "Lichess is still rate limiting us, try again in a minute"),
+            LichessError::Server(status) => write!(f, "Lichess had a server error: {}", status),
+            LichessError::Unexpected(status) => write!(f, "Unexpected response from Lichess: {}", status),
+            LichessError::Network(error) => write!(f, "Could not reach Lichess: {}", error)
+        }
+    }
+}
+
+impl Error for LichessError {}
+
+impl From<reqwest::Error> for LichessError {
+    fn from(error: reqwest::Error) -> LichessError {
+        LichessError::Network(error)
+    }
+}
+
+// Talks to Lichess, or anything serving its API. Rate limits are shared by every
+// request made through the same client.
+pub struct LichessClient {
+    base_url: String,
+    token: Option<String>,
+    rate_limit_wait: Duration,
+    server_error_wait: Duration,
+    http: reqwest::Client,
+    // No requests before then, set by a 429
+    paused_until: Mutex<Option<Instant>>
+}
+
+impl LichessClient {
+    pub fn new() -> LichessClient {
+        LichessClient {
+            base_url: LICHESS_URL.to_string(),
+            token: None,
+            rate_limit_wait: RATE_LIMIT_WAIT,
+            server_error_wait: SERVER_ERROR_WAIT,
+            http: reqwest::Client::new(),
+            paused_until: Mutex::new(None)
+        }
+    }
+
+    pub fn with_base_url(mut self, base_url: &str) -> LichessClient {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Personal API token, needed for private games and raises the rate limits
+    pub fn with_token(mut self, token: &str) -> LichessClient {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    pub fn with_retry_waits(mut self, rate_limit_wait: Duration, server_error_wait: Duration) -> LichessClient {
+        self.rate_limit_wait = rate_limit_wait;
+        self.server_error_wait = server_error_wait;
+        self
+    }
+
+    // One game as PGN, with clock comments and, when asked and the game was analysed, evaluations
+    pub async fn get_game(&self, game_id: &str, evals: bool) -> Result<String, LichessError> {
+        let path = format!("/game/export/{}", game_id);
+        let parameters = [("clocks", "true".to_string()), ("evals", evals.to_string())];
+        let response = self.get(&path, &parameters, "application/x-chess-pgn", &format!("game {}", game_id)).await?;
+        Ok(response.text().await?)
+    }
+
+    pub async fn user_games(&self, username: &str, query: &UserGamesQuery) -> Result<GameStream, LichessError> {
+        let path = format!("/api/games/user/{}", username);
+        let response = self.get(&path, &query.parameters(), query.accept(), &format!("user {}", username)).await?;
+        Ok(GameStream {records: Records::new(response), format: query.format})
+    }
+
+    pub async fn stream_game(&self, game_id: &str) -> Result<LiveStream, LichessError> {
+        let path = format!("/api/stream/game/{}", game_id);
+        let response = self.get(&path, &[], "application/x-ndjson", &format!("game {}", game_id)).await?;
+        Ok(LiveStream {records: Records::new(response)})
+    }
+
+    // Whatever Lichess TV features, one game after the other.
+    pub async fn stream_tv(&self) -> Result<LiveStream, LichessError> {
+        let response = self.get("/api/tv/feed", &[], "application/x-ndjson", "TV feed").await?;
+        Ok(LiveStream {records: Records::new(response)})
+    }
+
+    // Waits out rate limits and retries server errors with a growing wait.
+    async fn get(&self, path: &str, parameters: &[(&str, String)], accept: &str, what: &str) -> Result<reqwest::Response, LichessError> {
+        let mut server_error_wait = self.server_error_wait;
+        let mut attempt = 0;
+        loop {
+            self.wait_for_rate_limit().await;
+            let mut request = self.http.get(&format!("{}{}", self.base_url, path))
+                .query(parameters)
+                .header(reqwest::header::ACCEPT, accept);
+            if let Some(token) = &self.token {
+                request = request.bearer_auth(token);
+            }
+            let response = request.send().await?;
+            let status = response.status();
+            let error = match status {
+                _ if status.is_success() => return Ok(response),
+                StatusCode::NOT_FOUND => return Err(LichessError::NotFound(what.to_string())),
+                StatusCode::UNAUTHORIZED => return Err(LichessError::Unauthorized),
+                StatusCode::TOO_MANY_REQUESTS => {
+                    *self.paused_until.lock().unwrap() = Some(Instant::now() + self.rate_limit_wait);
+                    LichessError::RateLimited
+                },
+                _ if status.is_server_error() => LichessError::Server(status),
+                _ => return Err(LichessError::Unexpected(status))
+            };
+            attempt += 1;
+            if attempt > RETRIES {
+                return Err(error);
+            }
+            // Only waited out when there is another attempt to come
+            if let LichessError::Server(_) = error {
+                tokio::time::delay_for(server_error_wait).await;
+                server_error_wait *= 2;
+            }
+        }
+    }
+
+    async fn wait_for_rate_limit(&self) {
+        let paused_until = *self.paused_until.lock().unwrap();
+        if let Some(paused_until) = paused_until {
+            let now = Instant::now();
+            if paused_until > now {
+                tokio::time::delay_for(paused_until - now).await;
+            }
+        }
+    }
+}
+
+impl Default for LichessClient {
+    fn default() -> LichessClient {
+        LichessClient::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ExportFormat {
+    // Games one after the other, separated by blank lines
+    #[default]
+    Pgn,
+    // One JSON object per line
+    Ndjson
+}
+
+// Filters for a user's game export, unset filters are left out of the request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserGamesQuery {
+    pub max: Option<u32>,
+    // Milliseconds since the epoch
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    // e.g. "blitz", "rapid", "classical"
+    pub perf_types: Vec<String>,
+    pub rated: Option<bool>,
+    // Engine evaluations of the games that were analysed
+    pub evals: bool,
+    pub format: ExportFormat
+}
+
+impl UserGamesQuery {
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![("clocks", "true".to_string()), ("evals", self.evals.to_string())];
+        if let Some(max) = self.max {
+            parameters.push(("max", max.to_string()));
+        }
+        if let Some(since) = self.since {
+            parameters.push(("since", since.to_string()));
+        }
+        if let Some(until) = self.until {
+            parameters.push(("until", until.to_string()));
+        }
+        if !self.perf_types.is_empty() {
+            parameters.push(("perfType", self.perf_types.join(",")));
+        }
+        if let Some(rated) = self.rated {
+            parameters.push(("rated", rated.to_string()));
+        }
+        if self.format == ExportFormat::Ndjson {
+            parameters.push(("pgnInJson", "true".to_string()));
+        }
+        parameters
+    }
+
+    fn accept(&self) -> &'static str {
+        match self.format {
+            ExportFormat::Pgn => "application/x-chess-pgn",
+            ExportFormat::Ndjson => "application/x-ndjson"
+        }
+    }
+}
+
+// Response body read a record at a time, records end where the split function says.
+struct Records {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    done: bool
+}
+
+impl Records {
+    fn new(response: reqwest::Response) -> Records {
+        Records {response, buffer: Vec::new(), done: false}
+    }
+
+    // The rest of the body is the last record, it can be blank
+    async fn next(&mut self, split: fn(&[u8]) -> Option<usize>) -> Option<Result<Vec<u8>, Box<dyn Error>>> {
+        loop {
+            if let Some(end) = split(&self.buffer) {
+                return Some(Ok(self.buffer.drain(..end).collect()));
+            }
+            if self.done {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                return Some(Ok(self.buffer.drain(..).collect()));
+            }
+            match self.response.chunk().await {
+                Ok(Some(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Ok(None) => self.done = true,
+                Err(error) => {
+                    self.done = true;
+                    self.buffer.clear();
+                    return Some(Err(error.into()));
+                }
+            }
+        }
+    }
+}
+
+fn split_line(buffer: &[u8]) -> Option<usize> {
+    buffer.iter().position(|byte| *byte == b'\n').map(|idx| idx + 1)
+}
+
+// Games are handed out as PGN while the export is still downloading, a user can have thousands.
+pub struct GameStream {
+    records: Records,
+    format: ExportFormat
+}
+
+impl GameStream {
+    pub async fn next_game(&mut self) -> Option<Result<String, Box<dyn Error>>> {
+        let split: fn(&[u8]) -> Option<usize> = match self.format {
+            ExportFormat::Pgn => GameStream::split_pgn,
+            ExportFormat::Ndjson => split_line
+        };
+        loop {
+            let record = match self.records.next(split).await? {
+                Ok(record) => record,
+                Err(error) => return Some(Err(error))
+            };
+            if let Some(result) = self.parse(&record) {
+                return Some(result);
+            }
+        }
+    }
+
+    pub async fn all_games(mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut games = Vec::new();
+        while let Some(game) = self.next_game().await {
+            games.push(game?);
+        }
+        Ok(games)
+    }
+
+    // A game ends with the blank line after its moves, the tags before the moves
+    // are separated from them by a blank line too.
+    fn split_pgn(buffer: &[u8]) -> Option<usize> {
+        let mut start = 0;
+        while let Some(offset) = buffer[start..].windows(2).position(|window| window == b"\n\n") {
+            let end = start + offset + 2;
+            let paragraph = String::from_utf8_lossy(&buffer[start..end]);
+            let paragraph = paragraph.trim();
+            if !paragraph.is_empty() && !paragraph.starts_with('[') {
+                return Some(end);
+            }
+            start = end;
+        }
+        None
+    }
+
+    // None for blank leftovers between games
+    fn parse(&self, game: &[u8]) -> Option<Result<String, Box<dyn Error>>> {
+        let text = String::from_utf8_lossy(game);
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        Some(match self.format {
+            ExportFormat::Pgn => Ok(text.to_string()),
+            ExportFormat::Ndjson => GameStream::pgn_from_json(text)
+        })
+    }
+
+    // Exports requested without pgnInJson only carry the moves, enough for the music.
+    fn pgn_from_json(line: &str) -> Result<String, Box<dyn Error>> {
+        let game: serde_json::Value = serde_json::from_str(line)?;
+        if let Some(pgn) = game["pgn"].as_str() {
+            return Ok(pgn.trim().to_string());
+        }
+        let moves = game["moves"].as_str().ok_or("Game has no moves")?;
+        let result = match (game["winner"].as_str(), game["status"].as_str()) {
+            (Some("white"), _) => "1-0",
+            (Some("black"), _) => "0-1",
+            (None, Some("draw")) | (None, Some("stalemate")) => "1/2-1/2",
+            _ => "*"
+        };
+        let mut pgn = String::new();
+        if let Some(id) = game["id"].as_str() {
+            pgn.push_str(&format!("[Site \"{}/{}\"]\n", LICHESS_URL, id));
+        }
+        pgn.push_str(&format!("[Result \"{}\"]\n\n", result));
+        for (idx, san) in moves.split_whitespace().enumerate() {
+            if idx % 2 == 0 {
+                pgn.push_str(&format!("{}. ", idx / 2 + 1));
+            }
+            pgn.push_str(san);
+            pgn.push(' ');
+        }
+        pgn.push_str(result);
+        Ok(pgn)
+    }
+}
+
+const STANDARD_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// What the live streams send, reduced to what the music needs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveEvent {
+    // The game to follow from now on, on TV the featured game changed.
+    // Plies already played, when the stream says.
+    Game {id: String, plies: Option<usize>},
+    // Plies played including this move, when the stream sends a full FEN, and the
+    // piece placement field of the FEN, which the TV feed sends alone.
+    Move {uci: String, ply: Option<usize>, placement: Option<String>}
+}
+
+impl LiveEvent {
+    // None for messages the music doesn't care about.
+    fn parse(line: &str) -> Result<Option<LiveEvent>, Box<dyn Error>> {
+        let message: serde_json::Value = serde_json::from_str(line)?;
+        // The TV feed wraps its messages, as in {"t": "fen", "d": {...}}
+        let (kind, data) = match message["t"].as_str() {
+            Some(kind) => (kind, &message["d"]),
+            None => ("", &message)
+        };
+        if let Some(id) = data["id"].as_str() {
+            let variant = data["variant"]["key"].as_str().unwrap_or("standard");
+            let initial_fen = data["initialFen"].as_str().unwrap_or("startpos");
+            if variant != "standard" || (initial_fen != "startpos" && initial_fen != STANDARD_FEN) {
+                Err(format!("Game {} doesn't start from the standard position", id))?;
+            }
+            let plies = data["turns"].as_u64().map(|turns| turns as usize);
+            return Ok(Some(LiveEvent::Game {id: id.to_string(), plies}));
+        }
+        match (kind, data["lm"].as_str()) {
+            ("", Some(uci)) | ("fen", Some(uci)) => {
+                let fen = data["fen"].as_str();
+                let ply = fen.and_then(LiveEvent::plies_from_fen);
+                let placement = fen.and_then(|fen| fen.split_whitespace().next()).map(str::to_string);
+                Ok(Some(LiveEvent::Move {uci: uci.to_string(), ply, placement}))
+            },
+            _ => Ok(None)
+        }
+    }
+
+    // From the side to move and the move number, the TV feed leaves them out.
+    fn plies_from_fen(fen: &str) -> Option<usize> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 6 {
+            return None;
+        }
+        let full_moves: usize = fields[5].parse().ok()?;
+        let black_to_move = fields[1] == "b";
+        Some((full_moves.max(1) - 1) * 2 + black_to_move as usize)
+    }
+}
+
+// Moves as they are played, the stream stays open until the game ends.
+pub struct LiveStream {
+    records: Records
+}
+
+impl LiveStream {
+    pub async fn next_event(&mut self) -> Option<Result<LiveEvent, Box<dyn Error>>> {
+        loop {
+            let record = match self.records.next(split_line).await? {
+                Ok(record) => record,
+                Err(error) => return Some(Err(error))
+            };
+            let line = String::from_utf8_lossy(&record);
+            if line.trim().is_empty() {
+                continue;
+            }
+            match LiveEvent::parse(&line) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(error) => return Some(Err(error))
+            }
+        }
     }
-    let body = response.text().await?;
-    Ok(body)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::mock_server::{MockServer, MockResponse};
     use tokio::runtime::Runtime;
 
+    const FIRST_GAME: &str = "[Event \"Rated Blitz game\"]\n[Result \"1-0\"]\n\n1. e4 { [%clk 0:03:00] } e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0\n\n\n";
+    const SECOND_GAME: &str = "[Event \"Rated Rapid game\"]\n[Result \"1/2-1/2\"]\n\n1. d4 d5 1/2-1/2\n\n\n";
+
+    fn client(server: &MockServer) -> LichessClient {
+        LichessClient::new().with_base_url(&server.url).with_retry_waits(Duration::from_millis(50), Duration::from_millis(10))
+    }
+
     #[test]
-    fn test_get_game() -> Result<(), Box<dyn Error>> {
-        Runtime::new()
-            .expect("Failed to create Tokio runtime")
-            .block_on(get_game("tzUJbFEX"))?;
-        Ok(())
+    fn test_get_game() {
+        Runtime::new().unwrap().block_on(async {
+            let server = MockServer::start(vec![MockResponse::ok(FIRST_GAME)]).await;
+            let game = client(&server).with_token("secret").get_game("tzUJbFEX", true).await.unwrap();
+            assert_eq!(game, FIRST_GAME);
+
+            let request = server.requests()[0].to_lowercase();
+            assert!(request.starts_with("get /game/export/tzujbfex?clocks=true&evals=true "), "{}", request);
+            assert!(request.contains("authorization: bearer secret"), "{}", request);
+        });
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_typed_errors() {
+        Runtime::new().unwrap().block_on(async {
+            let server = MockServer::start(vec![
+                MockResponse::status(404, "Not found"),
+                MockResponse::status(401, "No such token"),
+                MockResponse::status(400, "Bad request")
+            ]).await;
+            let client = client(&server);
+            match client.get_game("missing1", false).await {
+                Err(LichessError::NotFound(what)) => assert_eq!(what, "game missing1"),
+                other => panic!("Expected not found, got {:?}", other)
+            }
+            assert!(matches!(client.get_game("private1", false).await, Err(LichessError::Unauthorized)));
+            assert!(matches!(client.stream_tv().await, Err(LichessError::Unexpected(StatusCode::BAD_REQUEST))));
+            // Boxed like every other error, the type survives
+            let boxed: Box<dyn Error> = LichessError::RateLimited.into();
+            assert!(boxed.downcast_ref::<LichessError>().is_some());
+        });
+    }
+
+    #[test]
+    fn test_rate_limit_waits() {
+        Runtime::new().unwrap().block_on(async {
+            let server = MockServer::start(vec![
+                MockResponse::status(429, "Too many requests"),
+                MockResponse::ok(FIRST_GAME),
+                MockResponse::ok(SECOND_GAME)
+            ]).await;
+            let client = client(&server);
+            let started = Instant::now();
+            assert_eq!(client.get_game("abcd1234", false).await.unwrap(), FIRST_GAME);
+            assert!(started.elapsed() >= Duration::from_millis(50));
+            assert_eq!(client.get_game("efgh5678", false).await.unwrap(), SECOND_GAME);
+            assert_eq!(server.requests().len(), 3);
+        });
+    }
+
+    #[test]
+    fn test_gives_up_after_retries() {
+        Runtime::new().unwrap().block_on(async {
+            let limited = (0..=RETRIES).map(|_| MockResponse::status(429, "Too many requests")).collect();
+            let server = MockServer::start(limited).await;
+            assert!(matches!(client(&server).get_game("abcd1234", false).await, Err(LichessError::RateLimited)));
+            assert_eq!(server.requests().len(), RETRIES as usize + 1);
+
+            let server = MockServer::start(vec![
+                MockResponse::status(503, "Down for maintenance"),
+                MockResponse::status(502, "Bad gateway"),
+                MockResponse::ok(FIRST_GAME)
+            ]).await;
+            assert_eq!(client(&server).get_game("abcd1234", false).await.unwrap(), FIRST_GAME);
+
+            let failing = (0..=RETRIES).map(|_| MockResponse::status(500, "Oops")).collect();
+            let server = MockServer::start(failing).await;
+            let client = LichessClient::new().with_base_url(&server.url).with_retry_waits(Duration::from_millis(50), Duration::from_millis(100));
+            let started = Instant::now();
+            assert!(matches!(client.get_game("abcd1234", false).await, Err(LichessError::Server(StatusCode::INTERNAL_SERVER_ERROR))));
+            // 100ms and 200ms before the retries, none after the last failure
+            let elapsed = started.elapsed();
+            assert!(elapsed >= Duration::from_millis(300) && elapsed < Duration::from_millis(700), "{:?}", elapsed);
+            assert_eq!(server.requests().len(), RETRIES as usize + 1);
+        });
+    }
+
+    #[test]
+    fn test_user_games_pgn() {
+        Runtime::new().unwrap().block_on(async {
+            let export = format!("{}{}", FIRST_GAME, SECOND_GAME);
+            // Chunk boundaries fall inside tags, moves and the blank line between games
+            let chunks = [&export[..10], &export[10..60], &export[60..FIRST_GAME.len() - 1], &export[FIRST_GAME.len() - 1..]];
+            let server = MockServer::start(vec![MockResponse::chunked(&chunks)]).await;
+            let query = UserGamesQuery {max: Some(2), perf_types: vec!["blitz".to_string(), "rapid".to_string()], rated: Some(true), ..UserGamesQuery::default()};
+            let games = client(&server).user_games("someone", &query).await.unwrap().all_games().await.unwrap();
+            assert_eq!(games, vec![FIRST_GAME.trim(), SECOND_GAME.trim()]);
+
+            let request = &server.requests()[0];
+            assert!(request.starts_with("GET /api/games/user/someone?clocks=true&evals=false&max=2&perfType=blitz%2Crapid&rated=true HTTP/1.1"), "{}", request);
+            assert!(request.to_lowercase().contains("accept: application/x-chess-pgn"), "{}", request);
+        });
+    }
+
+    #[test]
+    fn test_user_games_ndjson() {
+        Runtime::new().unwrap().block_on(async {
+            let with_pgn = format!("{{\"id\":\"abcd1234\",\"pgn\":{:?}}}\n", FIRST_GAME);
+            let without_pgn = "{\"id\":\"efgh5678\",\"moves\":\"d4 d5 c4\",\"status\":\"resign\",\"winner\":\"black\"}\n";
+            let server = MockServer::start(vec![MockResponse::chunked(&[&with_pgn[..30], &with_pgn[30..], without_pgn])]).await;
+            let query = UserGamesQuery {since: Some(1600000000000), format: ExportFormat::Ndjson, ..UserGamesQuery::default()};
+            let mut stream = client(&server).user_games("someone", &query).await.unwrap();
+
+            assert_eq!(stream.next_game().await.unwrap().unwrap(), FIRST_GAME.trim());
+            assert_eq!(stream.next_game().await.unwrap().unwrap(), "[Site \"https://lichess.org/efgh5678\"]\n[Result \"0-1\"]\n\n1. d4 d5 2. c4 0-1");
+            assert!(stream.next_game().await.is_none());
+
+            let request = &server.requests()[0];
+            assert!(request.contains("since=1600000000000&pgnInJson=true"), "{}", request);
+            assert!(request.to_lowercase().contains("accept: application/x-ndjson"), "{}", request);
+        });
+    }
+
+    #[test]
+    fn test_user_games_not_found() {
+        Runtime::new().unwrap().block_on(async {
+            let server = MockServer::start(vec![MockResponse::status(404, "Not found")]).await;
+            assert!(client(&server).user_games("nobody", &UserGamesQuery::default()).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_live_events() {
+        let game = "{\"id\":\"abcd1234\",\"variant\":{\"key\":\"standard\"},\"initialFen\":\"startpos\",\"turns\":12}";
+        assert_eq!(LiveEvent::parse(game).unwrap(), Some(LiveEvent::Game {id: "abcd1234".to_string(), plies: Some(12)}));
+        let chess960 = "{\"id\":\"abcd1234\",\"variant\":{\"key\":\"chess960\"}}";
+        assert!(LiveEvent::parse(chess960).is_err());
+
+        let position = "{\"fen\":\"rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2\",\"lm\":\"g1f3\",\"wc\":178,\"bc\":180}";
+        assert_eq!(LiveEvent::parse(position).unwrap(), Some(LiveEvent::Move {uci: "g1f3".to_string(), ply: Some(3),
+            placement: Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R".to_string())}));
+
+        let featured = "{\"t\":\"featured\",\"d\":{\"id\":\"efgh5678\",\"orientation\":\"white\",\"fen\":\"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR\"}}";
+        assert_eq!(LiveEvent::parse(featured).unwrap(), Some(LiveEvent::Game {id: "efgh5678".to_string(), plies: None}));
+        let tv_move = "{\"t\":\"fen\",\"d\":{\"fen\":\"rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR\",\"lm\":\"e7e5\",\"wc\":60,\"bc\":60}}";
+        assert_eq!(LiveEvent::parse(tv_move).unwrap(), Some(LiveEvent::Move {uci: "e7e5".to_string(), ply: None,
+            placement: Some("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR".to_string())}));
+        assert_eq!(LiveEvent::parse("{\"t\":\"ping\"}").unwrap(), None);
+    }
+}
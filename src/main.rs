@@ -1,24 +1,23 @@
-mod chessmusic;
-mod lichess;
+// The command line over the chessmusic library.
+mod cli;
 
-mod pgn;
-mod chess;
-mod music;
-
-use std::{error::Error, env};
+use std::{env, process};
 
+// Exits with cli::EXIT_USAGE when the command line is wrong and cli::EXIT_FAILURE when
+// carrying it out failed.
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = env::args().collect();
-    match args.len() {
-        2 => println!("Playing game {}", args[1]),
-        _ => panic!("Incorrect number of arguments.\n Usage: \"chessmusic <game_id>\"")
+async fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match cli::parse(&args) {
+        Ok(cli) => cli::run(cli).await,
+        Err(error) => Err(error)
+    };
+    if let Err(error) = result {
+        eprintln!("chessmusic: {}", error);
+        let code = cli::exit_code(error.as_ref());
+        if code == cli::EXIT_USAGE {
+            eprintln!("Run \"chessmusic help\" for usage.");
+        }
+        process::exit(code);
     }
-    let game_str = lichess::get_game(&args[1]).await?; 
-    println!("Game:\n\n{}", game_str);
-
-    chessmusic::play_game(&game_str);
-
-    Ok(())
 }
-
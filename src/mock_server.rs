@@ -0,0 +1,95 @@
+// A local HTTP server for tests. Each connection gets the next canned response,
+// the request lines it received are kept for assertions.
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    // Written one at a time with a short pause, to exercise streaming clients
+    chunks: Vec<String>
+}
+
+impl MockResponse {
+    #[allow(dead_code)]
+    pub fn ok(body: &str) -> MockResponse {
+        MockResponse::status(200, body)
+    }
+
+    pub fn status(status: u16, body: &str) -> MockResponse {
+        MockResponse {status, headers: Vec::new(), chunks: vec![body.to_string()]}
+    }
+
+    pub fn chunked(chunks: &[&str]) -> MockResponse {
+        MockResponse {status: 200, headers: Vec::new(), chunks: chunks.iter().map(|chunk| chunk.to_string()).collect()}
+    }
+
+    #[allow(dead_code)]
+    pub fn with_header(mut self, name: &str, value: &str) -> MockResponse {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> MockServer {
+        let mut listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(_) => return
+                };
+                let request = MockServer::read_request(&mut socket).await;
+                received.lock().unwrap().push(request);
+
+                let length: usize = response.chunks.iter().map(|chunk| chunk.len()).sum();
+                let mut head = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n", response.status, length);
+                for (name, value) in response.headers.iter() {
+                    head.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                head.push_str("\r\n");
+                if socket.write_all(head.as_bytes()).await.is_err() {
+                    continue;
+                }
+                for chunk in response.chunks.iter() {
+                    if socket.write_all(chunk.as_bytes()).await.is_err() {
+                        break;
+                    }
+                    let _ = socket.flush().await;
+                    tokio::time::delay_for(Duration::from_millis(10)).await;
+                }
+                let _ = socket.shutdown(std::net::Shutdown::Write);
+            }
+        });
+        MockServer {url, requests}
+    }
+
+    // Request line and headers, e.g. "GET /api/games/user/someone?max=2 HTTP/1.1\r\nhost: ..."
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            match socket.read(&mut buffer).await {
+                Ok(0) | Err(_) => break,
+                Ok(read) => request.extend_from_slice(&buffer[..read])
+            }
+        }
+        String::from_utf8_lossy(&request).to_string()
+    }
+}
@@ -0,0 +1,85 @@
+use super::Key;
+
+// Centipawn bands for the chord under each ply
+const EVEN: i32 = 50;
+const WINNING: i32 = 300;
+const SWING: i32 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Suspended,
+    Dominant7
+}
+
+impl ChordQuality {
+    // Semitones above the root
+    pub fn intervals(&self) -> &'static [i32] {
+        match self {
+            ChordQuality::Major => &[0, 4, 7],
+            ChordQuality::Minor => &[0, 3, 7],
+            ChordQuality::Suspended => &[0, 5, 7],
+            ChordQuality::Dominant7 => &[0, 4, 7, 10]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Chord {
+    pub root: i32,
+    pub quality: ChordQuality
+}
+
+impl Chord {
+    pub fn notes(&self) -> Vec<i32> {
+        self.quality.intervals().iter().map(|interval| self.root + interval).collect()
+    }
+
+    // A big swing in white's advantage, in centipawns, is a dominant seventh, otherwise the advantage
+    // brightens the chord towards the tonic major and black's darkens it towards the tonic minor.
+    // Chords sit an octave below the key's tonic, under the piece melodies.
+    pub fn for_position(advantage: i32, previous: i32, key: &Key) -> Chord {
+        let (degree, quality) = if (advantage - previous).abs() >= SWING {
+            (4, ChordQuality::Dominant7)
+        } else if advantage >= WINNING {
+            (0, ChordQuality::Major)
+        } else if advantage >= EVEN {
+            (3, ChordQuality::Major)
+        } else if advantage > -EVEN {
+            (0, ChordQuality::Suspended)
+        } else if advantage > -WINNING {
+            (5, ChordQuality::Minor)
+        } else {
+            (0, ChordQuality::Minor)
+        };
+        Chord {root: key.degree_to_midi(degree) - 12, quality}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chord_notes() {
+        let chord = Chord {root: 48, quality: ChordQuality::Dominant7};
+        assert_eq!(chord.notes(), vec![48, 52, 55, 58]);
+    }
+
+    #[test]
+    fn test_chord_for_position() {
+        let key = Key::default();
+        let even = Chord::for_position(0, 0, &key);
+        assert_eq!(even, Chord {root: 48, quality: ChordQuality::Suspended});
+
+        let white_better = Chord::for_position(100, 0, &key);
+        assert_eq!(white_better, Chord {root: 53, quality: ChordQuality::Major});
+        let black_winning = Chord::for_position(-400, -300, &key);
+        assert_eq!(black_winning, Chord {root: 48, quality: ChordQuality::Minor});
+
+        // Losing a rook is a swing, whoever it favours
+        let swing = Chord::for_position(-500, 0, &key);
+        assert_eq!(swing, Chord {root: 55, quality: ChordQuality::Dominant7});
+    }
+}
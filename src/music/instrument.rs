@@ -0,0 +1,97 @@
+use super::percussion::DRUM_CHANNEL;
+use super::super::chess::types::Role;
+
+const CENTER: u8 = 64;
+const DEFAULT_VOLUME: u8 = 100;
+// Channel 16 is kept for the harmony pad
+const PAD_CHANNEL: u8 = 15;
+const PAD_PROGRAM: u8 = 89;
+const PAD_VOLUME: u8 = 70;
+
+// Channel and General MIDI sound of a voice, programs are zero based.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instrument {
+    pub channel: u8,
+    pub program: u8,
+    // 0 is hard left, 127 hard right
+    pub pan: u8,
+    pub volume: u8
+}
+
+impl Instrument {
+    pub fn new(channel: u8, program: u8, pan: u8, volume: u8) -> Instrument {
+        Instrument {channel, program, pan, volume}
+    }
+
+    // Each role of each side gets its own channel, skipping the drums, and a program for its role.
+    // Voices sharing a channel, like two white pawns, share its program too.
+    // White is spread over the left of the stereo field and black over the right.
+    pub fn for_piece(role: Role, white: bool) -> Instrument {
+        let (program, spread) = match role {
+            Role::Pawn => (0, 0),    // Acoustic grand piano
+            Role::Knight => (11, 1), // Vibraphone
+            Role::Bishop => (73, 2), // Flute
+            Role::Rook => (42, 3),   // Cello
+            Role::Queen => (40, 4),  // Violin
+            Role::King => (60, 5)    // French horn
+        };
+        let mut channel = spread * 2 + if white {0} else {1};
+        if channel >= DRUM_CHANNEL {
+            channel += 1;
+        }
+        let pan = if white {16 + spread * 8} else {111 - spread * 8};
+        Instrument::new(channel, program, pan, DEFAULT_VOLUME)
+    }
+
+    // Warm pad
+    pub fn pad() -> Instrument {
+        Instrument::new(PAD_CHANNEL, PAD_PROGRAM, CENTER, PAD_VOLUME)
+    }
+
+    pub fn percussion() -> Instrument {
+        Instrument::new(DRUM_CHANNEL, 0, CENTER, DEFAULT_VOLUME)
+    }
+
+    pub fn is_percussion(&self) -> bool {
+        self.channel == DRUM_CHANNEL
+    }
+}
+
+impl Default for Instrument {
+    fn default() -> Instrument {
+        Instrument::new(0, 0, CENTER, DEFAULT_VOLUME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_piece_channels() {
+        let roles = [Role::Pawn, Role::Knight, Role::Bishop, Role::Rook, Role::Queen, Role::King];
+        let instruments: Vec<Instrument> = roles.iter()
+            .flat_map(|role| vec![Instrument::for_piece(*role, true), Instrument::for_piece(*role, false)])
+            .collect();
+        let mut channels: Vec<u8> = instruments.iter().map(|instrument| instrument.channel).collect();
+        assert_eq!(channels[..4], [0, 1, 2, 3]);
+        assert_eq!(channels[9], 10);
+        channels.sort_unstable();
+        channels.dedup();
+        assert_eq!(channels.len(), 12);
+        assert!(!channels.contains(&DRUM_CHANNEL));
+        assert!(!channels.contains(&PAD_CHANNEL));
+        // Same role and side, same channel and program
+        assert_eq!(Instrument::for_piece(Role::Pawn, false), instruments[1]);
+    }
+
+    #[test]
+    fn test_for_piece_stereo() {
+        let white = Instrument::for_piece(Role::Queen, true);
+        let black = Instrument::for_piece(Role::Queen, false);
+        assert_eq!(white.program, black.program);
+        assert!(white.pan < CENTER && black.pan > CENTER);
+        assert_eq!(CENTER - white.pan, black.pan - CENTER + 1);
+        assert!(Instrument::percussion().is_percussion());
+    }
+}
@@ -0,0 +1,107 @@
+use super::Score;
+use super::notation::{Notation, Element, Spelling, BEATS_PER_MEASURE};
+
+use std::error::Error;
+use std::fs;
+
+const VERSION: &str = "2.22.0";
+// Major keys by the number of sharps, from six flats
+const MAJOR_KEYS: [&str; 13] = ["ges", "des", "aes", "ees", "bes", "f", "c", "g", "d", "a", "e", "b", "fis"];
+
+// LilyPond source, a staff with the moves as lyrics for every piece.
+pub fn to_string(score: &Score, title: &str, lyrics: &[String], tempo: u32) -> String {
+    let notation = Notation::new(score, title, lyrics, tempo);
+    let key = MAJOR_KEYS[(notation.fifths.clamp(-6, 6) + 6) as usize];
+    let mut ly = format!("\\version \"{}\"\n\n\\header {{\n  title = {}\n  tagline = ##f\n}}\n\n\\score {{\n  <<\n", VERSION, quote(&notation.title));
+    for staff in notation.staves.iter() {
+        ly.push_str(&format!("    \\new Staff \\with {{ instrumentName = {} }} {{\n", quote(&staff.name)));
+        ly.push_str(&format!("      \\clef {} \\key {} \\major \\time {}/4 \\tempo 4 = {}\n",
+            if staff.treble {"treble"} else {"bass"}, key, BEATS_PER_MEASURE, notation.tempo));
+        let mut syllables = Vec::new();
+        for measure in staff.measures.iter() {
+            let elements: Vec<String> = measure.iter().map(element).collect();
+            ly.push_str(&format!("      {} |\n", elements.join(" ")));
+            // Tied notes and rests take no syllable
+            for element in measure.iter().filter(|element| element.pitch.is_some() && !element.tie_stop) {
+                syllables.push(element.lyric.as_deref().map_or_else(|| "_".to_string(), quote));
+            }
+        }
+        ly.push_str("    }\n");
+        ly.push_str(&format!("    \\addlyrics {{ {} }}\n", syllables.join(" ")));
+    }
+    ly.push_str("  >>\n  \\layout { }\n}\n");
+    ly
+}
+
+pub fn save(score: &Score, title: &str, lyrics: &[String], tempo: u32, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_string(score, title, lyrics, tempo))?;
+    Ok(())
+}
+
+fn element(element: &Element) -> String {
+    let mut ly = match element.pitch {
+        Some(spelling) => pitch(&spelling),
+        None => "r".to_string()
+    };
+    ly.push_str(&(1920 / element.value).to_string());
+    if element.dotted {
+        ly.push('.');
+    }
+    if let Some(dynamic) = element.dynamic {
+        ly.push_str(&format!("\\{}", dynamic));
+    }
+    if element.tie_start {
+        ly.push_str(" ~");
+    }
+    ly
+}
+
+// "fis'" for F#4, c is the C below middle C.
+fn pitch(spelling: &Spelling) -> String {
+    let mut ly = spelling.step.to_ascii_lowercase().to_string();
+    let accidental = if spelling.alter > 0 {"is"} else {"es"};
+    for _ in 0..spelling.alter.abs() {
+        ly.push_str(accidental);
+    }
+    let octaves = spelling.octave - 3;
+    let mark = if octaves > 0 {'\''} else {','};
+    for _ in 0..octaves.abs() {
+        ly.push(mark);
+    }
+    ly
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::notation::ply_lyrics;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    #[test]
+    fn test_pitch() {
+        assert_eq!(pitch(&Spelling {step: 'F', alter: 1, octave: 4}), "fis'");
+        assert_eq!(pitch(&Spelling {step: 'C', alter: 0, octave: 3}), "c");
+        assert_eq!(pitch(&Spelling {step: 'B', alter: -1, octave: 1}), "bes,,");
+        assert_eq!(pitch(&Spelling {step: 'E', alter: -2, octave: 5}), "eeses''");
+    }
+
+    #[test]
+    fn test_to_string() {
+        let moves = ["e4", "e5", "Nf3", "Nc6"];
+        let game = Game::new_with_moves(&Move::parse_moves(&moves));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Qknight, false)], &ScoreConfig::default());
+        let ly = to_string(&score, "Opera \"game\"", &ply_lyrics(&moves), 90);
+        assert!(ly.starts_with("\\version \"2.22.0\""));
+        assert!(ly.contains("title = \"Opera \\\"game\\\"\""));
+        assert!(ly.contains("\\new Staff \\with { instrumentName = \"White Epawn\" }"));
+        assert!(ly.contains("\\key c \\major \\time 4/4 \\tempo 4 = 90"));
+        assert!(ly.contains("\"1. e4\""));
+        assert!(ly.contains("\"2... Nc6\""));
+        assert_eq!(ly.matches("\\addlyrics").count(), 2);
+    }
+}
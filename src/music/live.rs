@@ -0,0 +1,104 @@
+use super::score::{Score, ScoreConfig};
+use super::sequencer::{MidiSink, Sequencer, SequencerHandle, TempoMap};
+use super::super::chess::{Game, Move, PieceName};
+use super::super::chess::render::Position;
+
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+// Plays a game as its moves come in. Every new ply is played straight away on its own
+// sequencer, cutting off whatever the previous ply was still playing.
+pub struct LivePlayer<S: MidiSink + Send + 'static> {
+    game: Game,
+    pieces: Vec<(PieceName, bool)>,
+    config: ScoreConfig,
+    tempo: u32,
+    sink: Arc<Mutex<S>>,
+    playing: Option<SequencerHandle>
+}
+
+impl<S: MidiSink + Send + 'static> LivePlayer<S> {
+    pub fn new(pieces: &[(PieceName, bool)], config: ScoreConfig, tempo: u32, sink: S) -> LivePlayer<S> {
+        LivePlayer {game: Game::new(), pieces: pieces.to_vec(), config, tempo, sink: Arc::new(Mutex::new(sink)), playing: None}
+    }
+
+    pub fn plies(&self) -> usize {
+        self.game.plies.len()
+    }
+
+    // Where the pieces stand now, as the first field of a FEN.
+    pub fn placement(&self) -> String {
+        Position::after(&self.game, self.plies()).placement()
+    }
+
+    // Catches up with moves played before we joined, without playing them.
+    pub fn load_moves(&mut self, moves: &[Move]) -> Result<(), Box<dyn Error>> {
+        self.stop()?;
+        self.game.load_moves(moves)
+    }
+
+    pub fn play_uci(&mut self, uci: &str) -> Result<(), Box<dyn Error>> {
+        self.game.add_uci_move(uci)?;
+        // The whole score is rendered again so the ply sounds as it would in the finished game
+        let score = Score::from_game(&self.game, &self.pieces, &self.config);
+        let mut sequencer = Sequencer::new(TempoMap::constant(self.tempo));
+        sequencer.schedule_ply(&score, self.game.plies.len() - 1);
+        self.stop()?;
+        self.playing = Some(sequencer.start(self.sink.clone()));
+        Ok(())
+    }
+
+    // Lets the last ply finish.
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(handle) = self.playing.take() {
+            handle.wait()?;
+        }
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(handle) = self.playing.take() {
+            handle.controller().stop();
+            handle.wait()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::midi;
+
+    #[derive(Clone)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<Vec<u8>>>>
+    }
+
+    impl MidiSink for Recorder {
+        fn send(&mut self, message: &[u8]) {
+            self.messages.lock().unwrap().push(message.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_plays_each_move() {
+        let recorder = Recorder {messages: Arc::new(Mutex::new(Vec::new()))};
+        let pieces = &[(PieceName::Epawn, true), (PieceName::Epawn, false)];
+        let mut player = LivePlayer::new(pieces, ScoreConfig::default(), 6000, recorder.clone());
+        player.load_moves(&Move::parse_moves(&["e4"])).unwrap();
+        assert_eq!(player.plies(), 1);
+        assert!(recorder.messages.lock().unwrap().is_empty());
+
+        player.play_uci("e7e5").unwrap();
+        player.finish().unwrap();
+        let messages = recorder.messages.lock().unwrap().clone();
+        let note_ons: Vec<&Vec<u8>> = messages.iter().filter(|message| message[0] & 0xF0 == midi::NOTE_ON_MSG).collect();
+        // Only black's pawn moved on that ply
+        assert_eq!(note_ons.len(), 1);
+        assert_eq!(note_ons[0][0] & 0x0F, 1);
+
+        assert!(player.play_uci("e5e4").is_err());
+        assert_eq!(player.plies(), 2);
+    }
+}
@@ -0,0 +1,226 @@
+use super::{Note, Melody, Key};
+use super::super::chess::Cell;
+use super::super::chess::types::Role;
+
+use std::fmt::Debug;
+
+// Turns a piece's cell history into music. The history starts with the piece's first cell,
+// and every cell gets a phrase: the notes played, in order, during the ply it was reached.
+pub trait Mapping: Debug + Sync {
+    fn name(&self) -> &'static str;
+    fn phrases(&self, history: &[(Cell, bool)], role: Role, key: &Key) -> Vec<Vec<Note>>;
+}
+
+impl PartialEq for dyn Mapping {
+    fn eq(&self, other: &dyn Mapping) -> bool {
+        self.name() == other.name()
+    }
+}
+
+pub static MAPPINGS: &[&dyn Mapping] = &[&InKey, &Relative, &Absolute, &Distance, &Motif, &FileRank];
+
+pub fn from_name(name: &str) -> Option<&'static dyn Mapping> {
+    MAPPINGS.iter().find(|mapping| mapping.name() == name).cloned()
+}
+
+fn single_notes(notes: Vec<Note>) -> Vec<Vec<Note>> {
+    notes.into_iter().map(|note| vec![note]).collect()
+}
+
+fn file_index(cell: &Cell) -> i32 {
+    cell.file as i32 - 'a' as i32
+}
+
+// Ranks move two scale degrees and files one, starting from the first cell's file.
+#[derive(Debug)]
+pub struct InKey;
+
+impl Mapping for InKey {
+    fn name(&self) -> &'static str {"in-key"}
+
+    fn phrases(&self, history: &[(Cell, bool)], _role: Role, key: &Key) -> Vec<Vec<Note>> {
+        let initial_degree = history.first().map_or(0, |(cell, _)| file_index(cell));
+        single_notes(Melody::new_in_key(history, initial_degree, key).notes)
+    }
+}
+
+// Ranks move a whole step and files a half step, ignoring the key.
+#[derive(Debug)]
+pub struct Relative;
+
+impl Mapping for Relative {
+    fn name(&self) -> &'static str {"relative"}
+
+    fn phrases(&self, history: &[(Cell, bool)], _role: Role, _key: &Key) -> Vec<Vec<Note>> {
+        match history.first() {
+            Some((cell, _)) => single_notes(Melody::new(history, Note::new_with_cell(cell)).notes),
+            None => Vec::new()
+        }
+    }
+}
+
+// Every square has its own pitch, a chromatic grid from a1 (C2) up to h8.
+#[derive(Debug)]
+pub struct Absolute;
+
+const ABSOLUTE_LOWEST: i32 = 36;
+
+impl Mapping for Absolute {
+    fn name(&self) -> &'static str {"absolute"}
+
+    fn phrases(&self, history: &[(Cell, bool)], _role: Role, _key: &Key) -> Vec<Vec<Note>> {
+        history.iter()
+            .map(|(cell, _)| vec![Note::new(ABSOLUTE_LOWEST + (cell.row - 1) * 8 + file_index(cell))])
+            .collect()
+    }
+}
+
+// Long moves are wide intervals: each square travelled is a scale degree, up when the
+// piece moves up the board or sideways, down when it moves back.
+#[derive(Debug)]
+pub struct Distance;
+
+impl Mapping for Distance {
+    fn name(&self) -> &'static str {"distance"}
+
+    fn phrases(&self, history: &[(Cell, bool)], _role: Role, key: &Key) -> Vec<Vec<Note>> {
+        let mut degree = 0;
+        let mut phrases = vec![vec![Note::new(key.degree_to_midi(degree))]];
+        for cells in history.windows(2) {
+            let (x_diff, y_diff) = cells[0].0.get_cell_diff(&cells[1].0);
+            let distance = x_diff.abs().max(y_diff.abs());
+            degree += if y_diff < 0 {-distance} else {distance};
+            phrases.push(vec![Note::new(key.degree_to_midi(degree))]);
+        }
+        phrases
+    }
+}
+
+// The in-key melody, with each move played as a motif for the piece's role:
+// knights arpeggiate, bishops and queens run up or down the scale, rooks leap an octave.
+#[derive(Debug)]
+pub struct Motif;
+
+const MAX_RUN: i32 = 8;
+
+impl Motif {
+    fn motif(role: Role, from: i32, to: i32, key: &Key) -> Vec<Note> {
+        let degrees: Vec<i32> = match role {
+            Role::Knight => vec![to, to + 2, to + 4],
+            Role::Bishop | Role::Queen if from != to => {
+                let step = if to > from {1} else {-1};
+                let start = if (to - from).abs() > MAX_RUN {to - step * (MAX_RUN - 1)} else {from + step};
+                (0..=(to - start) * step).map(|idx| start + idx * step).collect()
+            },
+            Role::Rook => vec![to, to + key.scale.intervals().len() as i32],
+            _ => vec![to]
+        };
+        degrees.iter().map(|degree| Note::new(key.degree_to_midi(*degree))).collect()
+    }
+}
+
+impl Mapping for Motif {
+    fn name(&self) -> &'static str {"motif"}
+
+    fn phrases(&self, history: &[(Cell, bool)], role: Role, key: &Key) -> Vec<Vec<Note>> {
+        let initial_degree = history.first().map_or(0, |(cell, _)| file_index(cell));
+        let degrees: Vec<i32> = Melody::new_in_key(history, initial_degree, key).notes.iter()
+            .map(|note| key.nearest_degree(note.pitch()))
+            .collect();
+        let mut phrases = vec![vec![Note::new(key.degree_to_midi(initial_degree))]];
+        for (idx, pair) in degrees.windows(2).enumerate() {
+            // Plies the piece stayed put keep its last note, it isn't played again
+            if history[idx].0 == history[idx + 1].0 {
+                phrases.push(vec![Note::new(key.degree_to_midi(pair[1]))]);
+            } else {
+                phrases.push(Motif::motif(role, pair[0], pair[1], key));
+            }
+        }
+        phrases
+    }
+}
+
+// The file picks the scale degree and every two ranks go up an octave.
+#[derive(Debug)]
+pub struct FileRank;
+
+impl Mapping for FileRank {
+    fn name(&self) -> &'static str {"file-rank"}
+
+    fn phrases(&self, history: &[(Cell, bool)], _role: Role, key: &Key) -> Vec<Vec<Note>> {
+        history.iter()
+            .map(|(cell, _)| vec![Note::new(key.degree_to_midi(file_index(cell)) + ((cell.row - 1) / 2 - 1) * 12)])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(cells: &[&str]) -> Vec<(Cell, bool)> {
+        cells.iter().map(|cell| (Cell::new(cell), false)).collect()
+    }
+
+    fn pitches(phrases: &[Vec<Note>]) -> Vec<Vec<i32>> {
+        phrases.iter().map(|phrase| phrase.iter().map(|note| note.pitch()).collect()).collect()
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(from_name("motif").unwrap().name(), "motif");
+        assert!(from_name("motif").unwrap() == &Motif as &dyn Mapping);
+        assert!(from_name("twelve-tone").is_none());
+        assert!(MAPPINGS.iter().all(|mapping| from_name(mapping.name()).is_some()));
+    }
+
+    #[test]
+    fn test_every_cell_gets_a_phrase() {
+        let history = history(&["g1", "f3", "f3", "e5"]);
+        for mapping in MAPPINGS.iter() {
+            let phrases = mapping.phrases(&history, Role::Knight, &Key::default());
+            assert_eq!(phrases.len(), history.len(), "{}", mapping.name());
+            assert!(phrases.iter().all(|phrase| !phrase.is_empty()), "{}", mapping.name());
+        }
+    }
+
+    #[test]
+    fn test_relative() {
+        let phrases = Relative.phrases(&history(&["a2", "a3", "b3"]), Role::Rook, &Key::default());
+        assert_eq!(pitches(&phrases), vec![vec![58], vec![60], vec![61]]);
+    }
+
+    #[test]
+    fn test_absolute() {
+        let phrases = Absolute.phrases(&history(&["a1", "h1", "e4", "h8"]), Role::Queen, &Key::default());
+        assert_eq!(pitches(&phrases), vec![vec![36], vec![43], vec![64], vec![99]]);
+    }
+
+    #[test]
+    fn test_distance() {
+        // Up three squares, back one, then sideways two
+        let phrases = Distance.phrases(&history(&["d1", "d4", "d3", "f3"]), Role::Rook, &Key::default());
+        assert_eq!(pitches(&phrases), vec![vec![60], vec![65], vec![64], vec![67]]);
+    }
+
+    #[test]
+    fn test_motif() {
+        let key = Key::default();
+        let knight = Motif.phrases(&history(&["g1", "f3"]), Role::Knight, &key);
+        // g file starts on degree 6, f3 is degree 9
+        assert_eq!(pitches(&knight), vec![vec![71], vec![76, 79, 83]]);
+
+        let bishop = Motif.phrases(&history(&["c1", "f4"]), Role::Bishop, &key);
+        // Nine degrees up, only the last eight are run
+        assert_eq!(pitches(&bishop), vec![vec![64], vec![67, 69, 71, 72, 74, 76, 77, 79]]);
+
+        let rook = Motif.phrases(&history(&["a1", "a3", "a3"]), Role::Rook, &key);
+        assert_eq!(pitches(&rook), vec![vec![60], vec![67, 79], vec![67]]);
+    }
+
+    #[test]
+    fn test_file_rank() {
+        let phrases = FileRank.phrases(&history(&["a1", "a3", "c4", "h8"]), Role::Pawn, &Key::default());
+        assert_eq!(pitches(&phrases), vec![vec![48], vec![60], vec![64], vec![96]]);
+    }
+}
@@ -1,5 +1,12 @@
-use super::Note;
+use super::{Note, Key};
+use super::range::{Range, RangePolicy};
+use super::rhythm::{NoteContext, EIGHTH, HALF};
 use super::super::chess::Cell;
+use super::super::chess::types::MoveType;
+
+const CAPTURE_VELOCITY: i32 = 110;
+const CHECK_VELOCITY: i32 = 120;
+const CASTLE_VELOCITY: i32 = 100;
 
 pub struct Melody {
     pub notes: Vec<Note>
@@ -12,6 +19,59 @@ impl Melody {
         }
     }
 
+    // Board movement becomes scale degrees instead of semitones: each rank is a third
+    // (two degrees) and each file a step, so melodies stay in the key.
+    pub fn new_in_key(move_history_with_captures: &[(Cell, bool)], initial_degree: i32, key: &Key) -> Melody {
+        Melody {
+            notes: Melody::compose_in_key(move_history_with_captures, initial_degree, key)
+        }
+    }
+
+    fn compose_in_key(move_history_with_captures: &[(Cell, bool)], initial_degree: i32, key: &Key) -> Vec<Note> {
+        let mut degree = initial_degree;
+        let mut notes = vec![Note::new(key.degree_to_midi(degree))];
+        for cells in move_history_with_captures.windows(2) {
+            let (x_diff, y_diff) = cells[0].0.get_cell_diff(&cells[1].0);
+            degree += y_diff * 2 + x_diff;
+            notes.push(Note::new(key.degree_to_midi(degree)));
+        }
+
+        notes
+    }
+
+    // Captures, checks and castling are played louder than regular moves.
+    pub fn accent_for(context: &NoteContext) -> Option<i32> {
+        match context.move_type {
+            _ if context.check => Some(CHECK_VELOCITY),
+            MoveType::Take => Some(CAPTURE_VELOCITY),
+            MoveType::CastleKing | MoveType::CastleQueen => Some(CASTLE_VELOCITY),
+            _ => None
+        }
+    }
+
+    pub fn fit_to_range(&mut self, range: &Range, policy: RangePolicy) {
+        for note in self.notes.iter_mut() {
+            *note = note.fit_to_range(range, policy);
+        }
+    }
+
+    // A captured piece's voice ends falling down the scale and fading out.
+    pub fn add_falling_gesture(&mut self, key: &Key) {
+        let last_note = match self.notes.last() {
+            Some(note) => *note,
+            None => return
+        };
+        let degree = key.nearest_degree(last_note.as_midi() as i32);
+        for (step, (degree_offset, velocity)) in [(1, 90), (2, 70), (4, 50)].iter().enumerate() {
+            let mut note = last_note;
+            note.base_midi = key.degree_to_midi(degree - degree_offset);
+            note.adjustment = 0;
+            note.velocity = *velocity;
+            note.duration = if step == 2 {HALF} else {EIGHTH};
+            self.notes.push(note);
+        }
+    }
+
     fn compose_with_move_take_history(move_history_with_captures: &[(Cell, bool)], initial_note: Note) -> Vec<Note> {
         let mut notes = vec![initial_note];
         let mut last_cell_opt: Option<Cell> = None;
@@ -33,6 +93,8 @@ impl Melody {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use super::super::Scale;
+    use super::super::super::chess::types::Role;
 
     #[test]
     fn test_compose_with_cell_history_vertical() {
@@ -66,4 +128,63 @@ mod tests {
         assert_eq!(melody[1].as_midi(), 53);
         assert_eq!(melody[2].as_midi(), 56);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_accent_for() {
+        let context = |move_type, check| NoteContext {role: Role::Pawn, move_type, check, think_time: None};
+        assert_eq!(Melody::accent_for(&context(MoveType::None, false)), None);
+        assert_eq!(Melody::accent_for(&context(MoveType::Simple, false)), None);
+        assert_eq!(Melody::accent_for(&context(MoveType::Take, false)), Some(CAPTURE_VELOCITY));
+        assert_eq!(Melody::accent_for(&context(MoveType::Take, true)), Some(CHECK_VELOCITY));
+        assert_eq!(Melody::accent_for(&context(MoveType::CastleKing, false)), Some(CASTLE_VELOCITY));
+    }
+
+    #[test]
+    fn test_add_falling_gesture() {
+        let key = Key::new(60, Scale::Major);
+        let cell_history = &[(Cell::new("e2"), false), (Cell::new("e4"), false)];
+        let mut melody = Melody::new_in_key(cell_history, 4, &key);
+        assert_eq!(melody.notes.last().unwrap().as_midi(), 74);
+
+        melody.add_falling_gesture(&key);
+        assert_eq!(melody.notes.len(), 5);
+        let pitches: Vec<u8> = melody.notes[2..].iter().map(|note| note.as_midi()).collect();
+        assert_eq!(pitches, vec![72, 71, 67]);
+        assert!(melody.notes[2].velocity > melody.notes[4].velocity);
+        assert_eq!(melody.notes[4].duration, HALF);
+    }
+
+    #[test]
+    fn test_compose_in_key_stays_in_scale() {
+        let key = Key::new(60, Scale::Major);
+        let cell_history = &[(Cell::new("a2"), false), (Cell::new("a3"), false), (Cell::new("b4"), false), (Cell::new("a3"), false)];
+        let melody = Melody::compose_in_key(cell_history, 0, &key);
+        assert_eq!(melody.len(), 4);
+        assert_eq!(melody[0].as_midi(), 60);
+        assert_eq!(melody[1].as_midi(), 64);
+        assert_eq!(melody[2].as_midi(), 69);
+        assert_eq!(melody[3].as_midi(), 64);
+    }
+
+    #[test]
+    fn test_compose_in_key_pentatonic() {
+        let key = Key::new(57, Scale::MinorPentatonic);
+        let cell_history = &[(Cell::new("e7"), false), (Cell::new("e5"), false), (Cell::new("e5"), false)];
+        let melody = Melody::new_in_key(cell_history, 2, &key);
+        assert_eq!(melody.notes.len(), 3);
+        assert_eq!(melody.notes[0].as_midi(), 62);
+        assert_eq!(melody.notes[1].as_midi(), 52);
+        assert_eq!(melody.notes[2].as_midi(), 52);
+    }
+
+    #[test]
+    fn test_fit_to_range() {
+        let key = Key::new(60, Scale::Major);
+        let cell_history = &[(Cell::new("a1"), false), (Cell::new("a8"), false), (Cell::new("h8"), false)];
+        let mut melody = Melody::new_in_key(cell_history, 0, &key);
+        assert_eq!(melody.notes[2].pitch(), 96);
+
+        melody.fit_to_range(&Range::new(48, 84).unwrap(), RangePolicy::Fold);
+        assert_eq!(melody.notes.iter().map(|note| note.as_midi()).collect::<Vec<u8>>(), vec![60, 84, 84]);
+    }
+}
@@ -0,0 +1,164 @@
+use super::Note;
+use super::instrument::Instrument;
+use super::score::{Score, Voice};
+
+use std::error::Error;
+
+pub const NOTE_OFF_MSG: u8 = 0x80;
+pub const NOTE_ON_MSG: u8 = 0x90;
+pub const CONTROL_CHANGE_MSG: u8 = 0xB0;
+pub const PROGRAM_CHANGE_MSG: u8 = 0xC0;
+
+const NOTE_OFF_VELOCITY: u8 = 0x40;
+const VOLUME_CC: u8 = 7;
+const PAN_CC: u8 = 10;
+const ALL_NOTES_OFF_CC: u8 = 123;
+
+pub fn note_on(note: &Note) -> Vec<u8> {
+    vec![NOTE_ON_MSG | note.channel, note.as_midi(), note.velocity.clamp(1, 127) as u8]
+}
+
+pub fn note_off(note: &Note) -> Vec<u8> {
+    vec![NOTE_OFF_MSG | note.channel, note.as_midi(), NOTE_OFF_VELOCITY]
+}
+
+pub fn control_change(channel: u8, controller: u8, value: u8) -> Vec<u8> {
+    vec![CONTROL_CHANGE_MSG | channel, controller, value]
+}
+
+pub fn all_notes_off(channel: u8) -> Vec<u8> {
+    control_change(channel, ALL_NOTES_OFF_CC, 0)
+}
+
+// Program change, pan and volume. Drum kits keep the synth's default program.
+pub fn instrument_messages(instrument: &Instrument) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    if !instrument.is_percussion() {
+        messages.push(vec![PROGRAM_CHANGE_MSG | instrument.channel, instrument.program]);
+    }
+    messages.push(control_change(instrument.channel, PAN_CC, instrument.pan));
+    messages.push(control_change(instrument.channel, VOLUME_CC, instrument.volume));
+    messages
+}
+
+// Setup for each channel of the score, sent before the first note.
+pub fn setup_messages(score: &Score) -> Vec<Vec<u8>> {
+    let mut channels: Vec<u8> = Vec::new();
+    let mut messages = Vec::new();
+    for voice in score.voices.iter() {
+        if !channels.contains(&voice.instrument.channel) {
+            channels.push(voice.instrument.channel);
+            messages.extend(instrument_messages(&voice.instrument));
+        }
+    }
+    messages
+}
+
+// Timestamped note on/off messages sorted by tick, note offs first so
+// repeated notes in a voice are released before they sound again. Notes with no
+// duration are left out, their note off would come before the note on.
+pub fn voice_messages(voices: &[&Voice]) -> Vec<(u32, Vec<u8>)> {
+    let mut messages: Vec<(u32, Vec<u8>)> = Vec::new();
+    for voice in voices.iter() {
+        for event in voice.notes().filter(|event| event.duration > 0) {
+            let note = event.note.as_ref().unwrap();
+            messages.push((event.tick, note_on(note)));
+            messages.push((event.end(), note_off(note)));
+        }
+    }
+    messages.sort_by_key(|(tick, message)| (*tick, message[0] & 0xF0 == NOTE_ON_MSG));
+    messages
+}
+
+pub fn score_messages(score: &Score) -> Vec<(u32, Vec<u8>)> {
+    voice_messages(&score.voices.iter().collect::<Vec<&Voice>>())
+}
+
+// A status byte with the high bit set, followed by the data bytes it expects, each below 0x80.
+pub fn validate(message: &[u8]) -> Result<(), Box<dyn Error>> {
+    let status = match message.first() {
+        Some(status) => *status,
+        None => Err("Empty midi message")?
+    };
+    let data_len = match status & 0xF0 {
+        0x80 | 0x90 | 0xA0 | 0xB0 | 0xE0 => 2,
+        0xC0 | 0xD0 => 1,
+        0xF0 => Err(format!("Unsupported system message {:02X}", status))?,
+        _ => Err(format!("Invalid status byte {:02X}", status))?
+    };
+    if message.len() != data_len + 1 {
+        Err(format!("Expected {} data bytes, got {}", data_len, message.len() - 1))?;
+    }
+    if let Some(data) = message[1..].iter().find(|data| **data > 0x7F) {
+        Err(format!("Invalid data byte {:02X}", data))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::score::{ScoreConfig, Event};
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    fn score_with_moves(moves: &[&str], pieces: &[(PieceName, bool)], percussion: bool) -> Score {
+        let game = Game::new_with_moves(&Move::parse_moves(moves));
+        let config = ScoreConfig {percussion, ..ScoreConfig::default()};
+        Score::from_game(&game, pieces, &config)
+    }
+
+    #[test]
+    fn test_score_messages() {
+        let score = score_with_moves(&["e4", "d5", "exd5"], &[(PieceName::Epawn, true), (PieceName::Dpawn, false)], false);
+        let messages = score_messages(&score);
+
+        let note_ons = messages.iter().filter(|(_, message)| message[0] & 0xF0 == NOTE_ON_MSG).count();
+        let note_offs = messages.iter().filter(|(_, message)| message[0] & 0xF0 == NOTE_OFF_MSG).count();
+        assert_eq!(note_ons, note_offs);
+        assert!(messages.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(messages.last().unwrap().0, score.length());
+
+        // The pawn released at the end of ply 0 comes before the next note on
+        let at_first_ply_end: Vec<u8> = messages.iter().filter(|(tick, _)| *tick == 480).map(|(_, message)| message[0] & 0xF0).collect();
+        assert_eq!(at_first_ply_end, vec![NOTE_OFF_MSG, NOTE_ON_MSG]);
+        assert!(messages.iter().all(|(_, message)| validate(message).is_ok()));
+    }
+
+    #[test]
+    fn test_zero_length_notes_are_dropped() {
+        let event = |tick, duration| Event {ply: 0, tick, duration, note: Some(Note::new(60))};
+        let voice = Voice {name: "test".to_string(), piece: None, role: None, instrument: Instrument::pad(), events: vec![event(0, 0), event(0, 480)]};
+        let messages = voice_messages(&[&voice]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!((messages[0].0, messages[0].1[0] & 0xF0), (0, NOTE_ON_MSG));
+        assert_eq!((messages[1].0, messages[1].1[0] & 0xF0), (480, NOTE_OFF_MSG));
+    }
+
+    #[test]
+    fn test_voices_on_their_own_channels() {
+        let score = score_with_moves(&["e4", "d5", "exd5"], &[(PieceName::Epawn, true), (PieceName::Dpawn, false)], true);
+        let channels: Vec<u8> = score_messages(&score).iter().map(|(_, message)| message[0] & 0x0F).collect();
+        assert!(channels.contains(&0) && channels.contains(&1) && channels.contains(&9));
+
+        let setup = setup_messages(&score);
+        // Program, pan and volume for both pieces, pan and volume for the drums
+        assert_eq!(setup.len(), 8);
+        assert_eq!(setup[0], vec![PROGRAM_CHANGE_MSG, 0]);
+        assert!(setup.iter().all(|message| validate(message).is_ok()));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&[0x90, 60, 100]).is_ok());
+        assert!(validate(&[0xC0, 5]).is_ok());
+        assert!(validate(&[]).is_err());
+        assert!(validate(&[0x3C, 60, 100]).is_err());
+        assert!(validate(&[0x90, 200, 100]).is_err());
+        assert!(validate(&[0x90, 60]).is_err());
+
+        let mut note = Note::new(-20);
+        note.velocity = 300;
+        assert!(validate(&note_on(&note)).is_ok());
+        assert!(validate(&note_off(&note)).is_ok());
+    }
+}
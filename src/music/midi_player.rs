@@ -1,82 +1,106 @@
-use midir::{MidiOutput, MidiOutputPort, MidiOutputConnection};
+use midir::{MidiOutput, MidiOutputConnection};
+use std::error::Error;
 use std::io::{stdin, stdout, Write};
 use std::thread::sleep;
 use std::time::Duration;
 
-const NOTE_ON_MSG: u8 = 0x90;
-const NOTE_OFF_MSG: u8 = 0x80;
-const VELOCITY: u8 = 0x64;
+use super::Note;
+use super::midi;
+use super::rhythm::{TICKS_PER_BEAT, DEFAULT_TEMPO};
+use super::score::Score;
+use super::sequencer::{MidiSink, Sequencer, SequencerHandle};
+
+const CLIENT_NAME: &str = "chessmusic";
 
 pub struct MidiPlayer {
-    conn_out: MidiOutputConnection
+    conn_out: MidiOutputConnection,
+    // Beats per minute
+    tempo: u32
 }
 
 impl MidiPlayer {
-    pub fn new() -> MidiPlayer {
-        MidiPlayer {
-            conn_out: MidiPlayer::get_conn_out()
-        }
+    pub fn new(port_name: Option<&str>) -> Result<MidiPlayer, Box<dyn Error>> {
+        Ok(MidiPlayer {
+            conn_out: MidiPlayer::get_conn_out(port_name)?,
+            tempo: DEFAULT_TEMPO
+        })
     }
 
-    #[allow(dead_code)]
-    pub fn play_note(&mut self, note: u8) {
-        let duration = 4;
-        
-        match self.conn_out.send(&[NOTE_ON_MSG, note, VELOCITY]) {
-            Ok(()) => (),
-            Err(the_error) => println!("{}", the_error.to_string())
-        }
-        sleep(Duration::from_millis(duration * 150));
-        self.conn_out.send(&[NOTE_OFF_MSG, note, VELOCITY]).unwrap();
+    pub fn set_tempo(&mut self, tempo: u32) {
+        self.tempo = tempo;
     }
 
-    pub fn play_notes(&mut self, notes: &Vec<u8>) {
-        let duration = 4;
-        for note in notes {
-            match self.conn_out.send(&[NOTE_ON_MSG, *note, VELOCITY]) {
-                Ok(()) => (),
-                Err(the_error) => println!("{}", the_error.to_string())
-            }
-        }
-        
-        sleep(Duration::from_millis(duration * 150));
+    fn ticks_to_duration(&self, ticks: u32) -> Duration {
+        Duration::from_millis(ticks as u64 * 60_000 / (self.tempo as u64 * TICKS_PER_BEAT as u64))
+    }
 
-        for note in notes {
-            self.conn_out.send(&[NOTE_OFF_MSG, *note, VELOCITY]).unwrap();
-        }
+    pub fn play_note(&mut self, note: &Note) {
+        self.send(&midi::note_on(note));
+        sleep(self.ticks_to_duration(note.duration));
+        self.send(&midi::note_off(note));
     }
 
-    fn get_conn_out() -> MidiOutputConnection {
-        let midi_out = match MidiOutput::new("My Test Output") {
-            Ok(midi_out) => midi_out,
-            Err(_the_error) => panic!("Failed to create new midi output")
-        };
-    
-        // Get an output port (read from console if multiple are available)
+    // Plays the score on a sequencer thread, the player moves along with it.
+    pub fn play_score(self, score: &Score) -> SequencerHandle {
+        let mut sequencer = Sequencer::new(score.tempo_map(self.tempo));
+        sequencer.schedule_score(score);
+        sequencer.start(self)
+    }
+
+    // Names of the MIDI outputs we could play to.
+    pub fn list_ports() -> Result<Vec<String>, Box<dyn Error>> {
+        let midi_out = MidiOutput::new(CLIENT_NAME)?;
+        let names = midi_out.ports().iter().map(|port| midi_out.port_name(port)).collect::<Result<Vec<String>, _>>()?;
+        Ok(names)
+    }
+
+    // The port whose name contains port_name, or when none is given the only port there is
+    // (read from console if multiple are available).
+    fn get_conn_out(port_name: Option<&str>) -> Result<MidiOutputConnection, Box<dyn Error>> {
+        let midi_out = MidiOutput::new(CLIENT_NAME)?;
         let out_ports = midi_out.ports();
-        let out_port: &MidiOutputPort = match out_ports.len() {
-            0 => panic!("no output port found"),
-            1 => {
-                println!("Choosing the only available output port: {}", midi_out.port_name(&out_ports[0]).unwrap());
-                &out_ports[0]
+        let mut names = Vec::new();
+        for port in out_ports.iter() {
+            names.push(midi_out.port_name(port)?);
+        }
+        let idx = match (port_name, out_ports.len()) {
+            (_, 0) => Err("No MIDI output port found, is a synthesizer running?")?,
+            (Some(port_name), _) => names.iter().position(|name| name.to_lowercase().contains(&port_name.to_lowercase()))
+                .ok_or(format!("No MIDI output port matches {}, available ports: {}", port_name, names.join(", ")))?,
+            (None, 1) => {
+                println!("Choosing the only available output port: {}", names[0]);
+                0
             },
-            _ => {
+            (None, _) => {
                 println!("\nAvailable output ports:");
-                for (i, p) in out_ports.iter().enumerate() {
-                    println!("{}: {}", i, midi_out.port_name(p).unwrap());
+                for (i, name) in names.iter().enumerate() {
+                    println!("{}: {}", i, name);
                 }
                 print!("Please select output port: ");
-                stdout().flush().unwrap();
+                stdout().flush()?;
                 let mut input = String::new();
-                stdin().read_line(&mut input).unwrap();
-                out_ports.get(input.trim().parse::<usize>().unwrap())
-                        .ok_or("invalid output port selected").unwrap()
+                stdin().read_line(&mut input)?;
+                input.trim().parse::<usize>().ok().filter(|idx| *idx < names.len())
+                    .ok_or(format!("Invalid output port selected: {}", input.trim()))?
             }
         };
 
-        println!("\nOpening connection");
-        let conn_out = midi_out.connect(out_port, "doesn't seem to matter").unwrap();
+        println!("\nOpening connection to {}", names[idx]);
+        let conn_out = midi_out.connect(&out_ports[idx], "doesn't seem to matter")
+            .map_err(|error| format!("Could not connect to {}: {}", names[idx], error))?;
         println!("Connection open. Listen!");
-        return conn_out;
+        Ok(conn_out)
     }
-}
\ No newline at end of file
+}
+
+impl MidiSink for MidiPlayer {
+    fn send(&mut self, message: &[u8]) {
+        if let Err(the_error) = midi::validate(message) {
+            println!("Not sending {:02X?}: {}", message, the_error);
+            return;
+        }
+        if let Err(the_error) = self.conn_out.send(message) {
+            println!("{}", the_error);
+        }
+    }
+}
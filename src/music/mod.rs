@@ -1,7 +1,35 @@
 pub mod note;
+#[cfg(feature = "midi-live")]
 pub mod midi_player;
 pub mod melody;
+pub mod scale;
+pub mod rhythm;
+pub mod percussion;
+pub mod score;
+pub mod range;
+pub mod instrument;
+pub mod midi;
+#[cfg(feature = "smf")]
+pub mod smf;
+pub mod sequencer;
+pub mod harmony;
+pub mod mapping;
+pub mod variation;
+pub mod live;
+#[cfg(feature = "synth")]
+pub mod synth;
+#[cfg(feature = "notation")]
+pub mod notation;
+#[cfg(feature = "notation")]
+pub mod musicxml;
+#[cfg(feature = "notation")]
+pub mod lilypond;
 
 pub use note::Note as Note;
+#[cfg(feature = "midi-live")]
 pub use midi_player::MidiPlayer as MidiPlayer;
-pub use melody::Melody as Melody;
\ No newline at end of file
+pub use melody::Melody as Melody;
+pub use scale::Key as Key;
+pub use scale::Scale as Scale;
+pub use rhythm::Rhythm as Rhythm;
+pub use score::Score as Score;
\ No newline at end of file
@@ -0,0 +1,134 @@
+use super::Score;
+use super::notation::{Notation, Element, BEATS_PER_MEASURE};
+use super::rhythm::TICKS_PER_BEAT;
+
+use std::error::Error;
+use std::fs;
+
+// Partwise MusicXML, a part with its own staff for every piece.
+pub fn to_string(score: &Score, title: &str, lyrics: &[String], tempo: u32) -> String {
+    let notation = Notation::new(score, title, lyrics, tempo);
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    xml.push_str("<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 3.1 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">\n");
+    xml.push_str("<score-partwise version=\"3.1\">\n");
+    xml.push_str(&format!("  <work><work-title>{}</work-title></work>\n", escape(&notation.title)));
+    xml.push_str("  <part-list>\n");
+    for (idx, staff) in notation.staves.iter().enumerate() {
+        xml.push_str(&format!("    <score-part id=\"P{}\"><part-name>{}</part-name></score-part>\n", idx + 1, escape(&staff.name)));
+    }
+    xml.push_str("  </part-list>\n");
+
+    for (idx, staff) in notation.staves.iter().enumerate() {
+        xml.push_str(&format!("  <part id=\"P{}\">\n", idx + 1));
+        for (number, measure) in staff.measures.iter().enumerate() {
+            xml.push_str(&format!("    <measure number=\"{}\">\n", number + 1));
+            if number == 0 {
+                let clef = if staff.treble {"<sign>G</sign><line>2</line>"} else {"<sign>F</sign><line>4</line>"};
+                xml.push_str(&format!(
+                    "      <attributes><divisions>{}</divisions><key><fifths>{}</fifths></key><time><beats>{}</beats><beat-type>4</beat-type></time><clef>{}</clef></attributes>\n",
+                    TICKS_PER_BEAT, notation.fifths, BEATS_PER_MEASURE, clef));
+                xml.push_str(&format!(
+                    "      <direction placement=\"above\"><direction-type><metronome><beat-unit>quarter</beat-unit><per-minute>{}</per-minute></metronome></direction-type><sound tempo=\"{}\"/></direction>\n",
+                    notation.tempo, notation.tempo));
+            }
+            for element in measure.iter() {
+                if let Some(dynamic) = element.dynamic {
+                    xml.push_str(&format!("      <direction placement=\"below\"><direction-type><dynamics><{}/></dynamics></direction-type></direction>\n", dynamic));
+                }
+                xml.push_str(&note(element));
+            }
+            xml.push_str("    </measure>\n");
+        }
+        xml.push_str("  </part>\n");
+    }
+    xml.push_str("</score-partwise>\n");
+    xml
+}
+
+pub fn save(score: &Score, title: &str, lyrics: &[String], tempo: u32, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_string(score, title, lyrics, tempo))?;
+    Ok(())
+}
+
+// Children in the order the schema wants them.
+fn note(element: &Element) -> String {
+    let mut xml = String::from("      <note>");
+    match element.pitch {
+        Some(spelling) => {
+            xml.push_str(&format!("<pitch><step>{}</step>", spelling.step));
+            if spelling.alter != 0 {
+                xml.push_str(&format!("<alter>{}</alter>", spelling.alter));
+            }
+            xml.push_str(&format!("<octave>{}</octave></pitch>", spelling.octave));
+        },
+        None => xml.push_str("<rest/>")
+    }
+    xml.push_str(&format!("<duration>{}</duration>", element.ticks));
+    if element.tie_stop {
+        xml.push_str("<tie type=\"stop\"/>");
+    }
+    if element.tie_start {
+        xml.push_str("<tie type=\"start\"/>");
+    }
+    xml.push_str(&format!("<voice>1</voice><type>{}</type>", note_type(element.value)));
+    if element.dotted {
+        xml.push_str("<dot/>");
+    }
+    if element.tie_start || element.tie_stop {
+        xml.push_str("<notations>");
+        if element.tie_stop {
+            xml.push_str("<tied type=\"stop\"/>");
+        }
+        if element.tie_start {
+            xml.push_str("<tied type=\"start\"/>");
+        }
+        xml.push_str("</notations>");
+    }
+    if let Some(lyric) = &element.lyric {
+        xml.push_str(&format!("<lyric number=\"1\"><syllabic>single</syllabic><text>{}</text></lyric>", escape(lyric)));
+    }
+    xml.push_str("</note>\n");
+    xml
+}
+
+fn note_type(value: u32) -> &'static str {
+    match value {
+        1920 => "whole",
+        960 => "half",
+        480 => "quarter",
+        240 => "eighth",
+        120 => "16th",
+        _ => "32nd"
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::notation::ply_lyrics;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    #[test]
+    fn test_to_string() {
+        let moves = ["e4", "e5", "Nf3", "Nc6"];
+        let game = Game::new_with_moves(&Move::parse_moves(&moves));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Qknight, false)], &ScoreConfig::default());
+        let xml = to_string(&score, "Tom & Jerry", &ply_lyrics(&moves), 100);
+        assert!(xml.contains("<work-title>Tom &amp; Jerry</work-title>"));
+        assert!(xml.contains("<score-part id=\"P1\"><part-name>White Epawn</part-name></score-part>"));
+        assert!(xml.contains("<score-part id=\"P2\"><part-name>Black Qknight</part-name></score-part>"));
+        assert!(xml.contains("<divisions>480</divisions><key><fifths>0</fifths></key>"));
+        assert!(xml.contains("<text>1. e4</text>"));
+        assert!(xml.contains("<text>2... Nc6</text>"));
+        assert!(xml.contains("<dynamics><mf/></dynamics>") || xml.contains("<dynamics><mp/></dynamics>"), "{}", xml);
+        assert_eq!(xml.matches("<part id=").count(), 2);
+        assert_eq!(xml.matches("<note>").count(), xml.matches("</note>").count());
+        assert_eq!(xml.matches("<tie type=\"start\"/>").count(), xml.matches("<tie type=\"stop\"/>").count());
+    }
+}
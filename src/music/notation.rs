@@ -0,0 +1,300 @@
+use super::{Key, Scale, Score};
+use super::rhythm::TICKS_PER_BEAT;
+use super::score::Voice;
+
+// Printed in 4/4
+pub const BEATS_PER_MEASURE: u32 = 4;
+pub const MEASURE: u32 = TICKS_PER_BEAT * BEATS_PER_MEASURE;
+// Printed rhythms are rounded to thirty-second notes
+const GRID: u32 = TICKS_PER_BEAT / 8;
+// (ticks, undotted value) of the note values we print, longest first
+const NOTE_VALUES: [(u32, u32); 10] = [
+    (1920, 1920), (1440, 960), (960, 960), (720, 480), (480, 480),
+    (360, 240), (240, 240), (180, 120), (120, 120), (60, 60)
+];
+const LETTERS: [char; 7] = ['C', 'D', 'E', 'F', 'G', 'A', 'B'];
+const NATURALS: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+const MAJOR_STEPS: [i32; 7] = [0, 2, 4, 5, 7, 9, 11];
+// Sharps (positive) or flats in the major key on each pitch class, F# rather than Gb
+const FIFTHS: [i32; 12] = [0, -5, 2, -3, 4, -1, 6, 1, -4, 3, -2, 5];
+
+// A note's spelling, lyric and dynamic while it is laid out
+type Sounding = (Spelling, Option<String>, Option<&'static str>);
+
+// How a pitch is written: letter, sharps (positive) or flats, and octave, middle C is C4.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spelling {
+    pub step: char,
+    pub alter: i32,
+    pub octave: i32
+}
+
+// A note or rest as printed: a single note value within a measure, tied to the next
+// part of the same note when it had to be split.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    // None for rests
+    pub pitch: Option<Spelling>,
+    pub ticks: u32,
+    // The value without its dot: 1920 is a whole note, 60 a thirty-second
+    pub value: u32,
+    pub dotted: bool,
+    pub tie_start: bool,
+    pub tie_stop: bool,
+    pub lyric: Option<String>,
+    pub dynamic: Option<&'static str>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Staff {
+    pub name: String,
+    pub treble: bool,
+    pub measures: Vec<Vec<Element>>
+}
+
+// The score laid out as sheet music, one staff per piece.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notation {
+    pub title: String,
+    // Key signature, sharps are positive
+    pub fifths: i32,
+    pub tempo: u32,
+    pub staves: Vec<Staff>
+}
+
+impl Notation {
+    // lyrics[n] goes under the first note of ply n in every staff.
+    pub fn new(score: &Score, title: &str, lyrics: &[String], tempo: u32) -> Notation {
+        let fifths = fifths(&score.key);
+        let measures = score.length().div_ceil(MEASURE).max(1);
+        let staves = score.voices.iter()
+            .filter(|voice| voice.piece.is_some())
+            .map(|voice| Notation::staff(voice, fifths, lyrics, measures * MEASURE))
+            .collect();
+        Notation {title: title.to_string(), fifths, tempo, staves}
+    }
+
+    fn staff(voice: &Voice, fifths: i32, lyrics: &[String], length: u32) -> Staff {
+        // (start, end, pitch, velocity, ply) on the grid, one note at a time
+        let mut notes: Vec<(u32, u32, u8, i32, usize)> = Vec::new();
+        for event in voice.notes() {
+            let note = event.note.as_ref().unwrap();
+            let start = snap(event.tick);
+            let end = snap(event.end()).max(start + GRID).min(length);
+            if start >= length {
+                continue;
+            }
+            match notes.last_mut() {
+                Some(last) if last.0 == start => continue,
+                Some(last) if last.1 > start => last.1 = start,
+                _ => {}
+            }
+            notes.push((start, end, note.as_midi(), note.velocity, event.ply));
+        }
+
+        // (start, end, and for notes the spelling, lyric and dynamic)
+        let mut elements: Vec<(u32, u32, Option<Sounding>)> = Vec::new();
+        let (mut position, mut last_ply, mut last_dynamic) = (0, None, None);
+        for (start, end, pitch, velocity, ply) in notes.iter() {
+            if *start > position {
+                elements.push((position, *start, None));
+            }
+            let lyric = if last_ply != Some(*ply) {lyrics.get(*ply).cloned()} else {None};
+            let dynamic = Some(dynamic_for(*velocity)).filter(|dynamic| last_dynamic != Some(*dynamic));
+            last_ply = Some(*ply);
+            last_dynamic = Some(dynamic_for(*velocity));
+            elements.push((*start, *end, Some((spell(*pitch, fifths), lyric, dynamic))));
+            position = *end;
+        }
+        if position < length {
+            elements.push((position, length, None));
+        }
+
+        let mut measures: Vec<Vec<Element>> = (0..length / MEASURE).map(|_| Vec::new()).collect();
+        for (start, end, note) in elements {
+            let parts = split(start, end);
+            let count = parts.len();
+            for (idx, (tick, ticks, value, dotted)) in parts.into_iter().enumerate() {
+                let first = idx == 0;
+                let element = match &note {
+                    Some((spelling, lyric, dynamic)) => Element {
+                        pitch: Some(*spelling),
+                        ticks,
+                        value,
+                        dotted,
+                        tie_start: idx + 1 < count,
+                        tie_stop: !first,
+                        lyric: if first {lyric.clone()} else {None},
+                        dynamic: if first {*dynamic} else {None}
+                    },
+                    None => Element {pitch: None, ticks, value, dotted, tie_start: false, tie_stop: false, lyric: None, dynamic: None}
+                };
+                measures[(tick / MEASURE) as usize].push(element);
+            }
+        }
+
+        let pitches: Vec<u32> = notes.iter().map(|note| note.2 as u32).collect();
+        let treble = pitches.is_empty() || pitches.iter().sum::<u32>() / pitches.len() as u32 >= 60;
+        Staff {name: voice.name.clone(), treble, measures}
+    }
+}
+
+// "1. e4", "1... e5", one per ply.
+pub fn ply_lyrics(sans: &[&str]) -> Vec<String> {
+    sans.iter().enumerate().map(|(ply, san)| {
+        let dots = if ply % 2 == 1 {"..."} else {"."};
+        format!("{}{} {}", ply / 2 + 1, dots, san)
+    }).collect()
+}
+
+// Key signature of the key's relative major, none for scales without one.
+pub fn fifths(key: &Key) -> i32 {
+    let offset = match key.scale {
+        Scale::Major | Scale::MajorPentatonic => 0,
+        Scale::Dorian => 2,
+        Scale::Phrygian => 4,
+        Scale::Lydian => 5,
+        Scale::Mixolydian => 7,
+        Scale::Minor | Scale::MinorPentatonic => 9,
+        Scale::Locrian => 11,
+        Scale::Chromatic | Scale::Custom(_) => return 0
+    };
+    FIFTHS[(key.tonic - offset).rem_euclid(12) as usize]
+}
+
+// Notes of the key are spelled as the key signature has them, others with sharps
+// in sharp keys and flats in flat keys.
+pub fn spell(pitch: u8, fifths: i32) -> Spelling {
+    let pitch_class = pitch as i32 % 12;
+    // The major key's tonic is a fifth (four letters) up for every sharp
+    let tonic_letter = (fifths * 4).rem_euclid(7) as usize;
+    let tonic = (fifths * 7).rem_euclid(12);
+    let diatonic = (0..7).find(|degree| (tonic + MAJOR_STEPS[*degree]) % 12 == pitch_class);
+    let (letter, alter) = match diatonic {
+        Some(degree) => {
+            let letter = (tonic_letter + degree) % 7;
+            (letter, alter_between(NATURALS[letter], pitch_class))
+        },
+        None if fifths >= 0 => {
+            let letter = NATURALS.iter().rposition(|natural| *natural <= pitch_class).unwrap();
+            (letter, pitch_class - NATURALS[letter])
+        },
+        None => {
+            let letter = NATURALS.iter().position(|natural| *natural >= pitch_class).unwrap();
+            (letter, pitch_class - NATURALS[letter])
+        }
+    };
+    Spelling {step: LETTERS[letter], alter, octave: (pitch as i32 - alter).div_euclid(12) - 1}
+}
+
+// Semitones from the natural letter to the pitch class, across the octave when closer.
+fn alter_between(natural: i32, pitch_class: i32) -> i32 {
+    let alter = (pitch_class - natural).rem_euclid(12);
+    if alter > 6 {alter - 12} else {alter}
+}
+
+pub fn dynamic_for(velocity: i32) -> &'static str {
+    match velocity {
+        velocity if velocity < 40 => "pp",
+        velocity if velocity < 56 => "p",
+        velocity if velocity < 72 => "mp",
+        velocity if velocity < 88 => "mf",
+        velocity if velocity < 104 => "f",
+        _ => "ff"
+    }
+}
+
+fn snap(tick: u32) -> u32 {
+    (tick + GRID / 2) / GRID * GRID
+}
+
+// (tick, ticks, value, dotted) of the printable notes from start to end, split at the bar lines.
+fn split(start: u32, end: u32) -> Vec<(u32, u32, u32, bool)> {
+    let mut parts = Vec::new();
+    let mut tick = start;
+    while tick < end {
+        let bar = (tick / MEASURE + 1) * MEASURE;
+        let left = end.min(bar) - tick;
+        let (ticks, value) = *NOTE_VALUES.iter().find(|(ticks, _)| *ticks <= left).unwrap();
+        parts.push((tick, ticks, value, ticks != value));
+        tick += ticks;
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    fn spelled(pitch: u8, fifths: i32) -> String {
+        let spelling = spell(pitch, fifths);
+        let accidental = match spelling.alter {
+            1 => "#",
+            -1 => "b",
+            _ => ""
+        };
+        format!("{}{}{}", spelling.step, accidental, spelling.octave)
+    }
+
+    #[test]
+    fn test_fifths() {
+        assert_eq!(fifths(&Key::new(60, Scale::Major)), 0);
+        assert_eq!(fifths(&Key::new(69, Scale::Minor)), 0);
+        assert_eq!(fifths(&Key::new(62, Scale::Major)), 2);
+        assert_eq!(fifths(&Key::new(62, Scale::Dorian)), 0);
+        assert_eq!(fifths(&Key::new(65, Scale::Major)), -1);
+        assert_eq!(fifths(&Key::new(70, Scale::Minor)), -5);
+        assert_eq!(fifths(&Key::new(66, Scale::Major)), 6);
+        assert_eq!(fifths(&Key::new(60, Scale::Chromatic)), 0);
+    }
+
+    #[test]
+    fn test_spell() {
+        assert_eq!(spelled(60, 0), "C4");
+        assert_eq!(spelled(61, 0), "C#4");
+        assert_eq!(spelled(70, -1), "Bb4");
+        assert_eq!(spelled(61, -1), "Db4");
+        // E# in F# major, and Cb in Gb major belongs to the octave above
+        assert_eq!(spelled(65, 6), "E#4");
+        assert_eq!(spelled(71, -6), "Cb5");
+        assert_eq!(spelled(66, 2), "F#4");
+        assert_eq!(spelled(45, 0), "A2");
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split(0, 480), vec![(0, 480, 480, false)]);
+        assert_eq!(split(0, 720), vec![(0, 720, 480, true)]);
+        // Across the bar line
+        assert_eq!(split(1440, 2400), vec![(1440, 480, 480, false), (1920, 480, 480, false)]);
+        assert_eq!(split(0, 1920 + 60), vec![(0, 1920, 1920, false), (1920, 60, 60, false)]);
+    }
+
+    #[test]
+    fn test_ply_lyrics() {
+        assert_eq!(ply_lyrics(&["e4", "e5", "Nf3"]), vec!["1. e4", "1... e5", "2. Nf3"]);
+    }
+
+    #[test]
+    fn test_notation() {
+        let moves = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"];
+        let game = Game::new_with_moves(&Move::parse_moves(&moves));
+        let config = ScoreConfig {harmony: true, percussion: true, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Kknight, true)], &config);
+        let lyrics = ply_lyrics(&moves);
+        let notation = Notation::new(&score, "Test", &lyrics, 100);
+        // Only the pieces get a staff
+        assert_eq!(notation.staves.len(), 2);
+        for staff in notation.staves.iter() {
+            for measure in staff.measures.iter() {
+                assert_eq!(measure.iter().map(|element| element.ticks).sum::<u32>(), MEASURE);
+            }
+        }
+        let knight = &notation.staves[1];
+        let lyric_texts: Vec<&str> = knight.measures.iter().flatten().filter_map(|element| element.lyric.as_deref()).collect();
+        assert!(lyric_texts.contains(&"2. Nf3"), "{:?}", lyric_texts);
+        assert!(knight.measures.iter().flatten().any(|element| element.dynamic.is_some()));
+    }
+}
@@ -1,8 +1,8 @@
 use chess::cell::{Cell};
 
 use crate::chess;
-
-extern crate midir;
+use super::rhythm::QUARTER;
+use super::range::{Range, RangePolicy, MIDI_LOWEST, MIDI_HIGHEST};
 
 use std::{vec};
 
@@ -11,7 +11,11 @@ use std::{vec};
 pub struct Note {
     pub base_midi: i32,
     pub adjustment: i32,
-    pub velocity: i32
+    pub velocity: i32,
+    // In ticks, see rhythm::TICKS_PER_BEAT
+    pub duration: u32,
+    // Zero based midi channel, percussion::DRUM_CHANNEL for drum hits
+    pub channel: u8
 }
 
 impl Note {
@@ -19,7 +23,9 @@ impl Note {
         Note {
             base_midi: midi_note,
             adjustment: 0,
-            velocity: 80
+            velocity: 80,
+            duration: QUARTER,
+            channel: 0
         }
     }
     #[allow(dead_code)]
@@ -27,14 +33,18 @@ impl Note {
         Note {
             base_midi: Note::file_to_midi(file),
             adjustment: 0,
-            velocity: 80
+            velocity: 80,
+            duration: QUARTER,
+            channel: 0
         }
     }
     pub fn new_with_cell(cell: &Cell) -> Note {
         Note {
             base_midi: Note::file_to_midi(cell.file),
             adjustment: cell.row - 1,
-            velocity: 80
+            velocity: 80,
+            duration: QUARTER,
+            channel: 0
         }
     }
 
@@ -44,7 +54,9 @@ impl Note {
          Note {
             base_midi: self.base_midi + (y * 2),
             adjustment: self.adjustment + x,
-            velocity: 80
+            velocity: 80,
+            duration: QUARTER,
+            channel: 0
         }
     }
 
@@ -67,8 +79,28 @@ impl Note {
         midi_note
     }
 
+    pub fn pitch(&self) -> i32 {
+        self.base_midi + self.adjustment
+    }
+
+    // Out of range pitches are clamped rather than wrapped, use fit_to_range to pick a policy.
     pub fn as_midi(&self) -> u8 {
-        (self.base_midi + self.adjustment) as u8
+        self.pitch().clamp(MIDI_LOWEST, MIDI_HIGHEST) as u8
+    }
+
+    // Like "C#4", sharps only, middle C is C4.
+    pub fn name(&self) -> String {
+        const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+        let midi = self.as_midi() as usize;
+        format!("{}{}", NAMES[midi % 12], midi as i32 / 12 - 1)
+    }
+
+    pub fn fit_to_range(&self, range: &Range, policy: RangePolicy) -> Note {
+        let pitch = range.fit(self.pitch(), policy);
+        Note {
+            base_midi: pitch - self.adjustment,
+            ..*self
+        }
     }
 
     pub fn get_pitches_from_cell_history(cell_history: &Vec<Cell>) -> Vec<Note> {
@@ -108,12 +140,30 @@ mod tests {
         assert_eq!(new_pitch.adjustment, 1);
     }
 
+    #[test]
+    fn test_name() {
+        assert_eq!(Note::new(60).name(), "C4");
+        assert_eq!(Note::new(70).name(), "A#4");
+        assert_eq!(Note::new(21).name(), "A0");
+    }
+
     #[test]
     fn test_get_pitches_from_cell_history() {
         let cell_history = vec![Cell::new("a2"), Cell::new("a3"), Cell::new("a4")];
         let pitches = Note::get_pitches_from_cell_history(&cell_history);
-        assert_eq!(pitches[0], Note {base_midi: 57, adjustment: 1, velocity: 80});
-        assert_eq!(pitches[1], Note {base_midi: 59, adjustment: 1, velocity: 80});
-        assert_eq!(pitches[2], Note {base_midi: 61, adjustment: 1, velocity: 80});
+        assert_eq!(pitches[0], Note {base_midi: 57, adjustment: 1, velocity: 80, duration: 480, channel: 0});
+        assert_eq!(pitches[1], Note {base_midi: 59, adjustment: 1, velocity: 80, duration: 480, channel: 0});
+        assert_eq!(pitches[2], Note {base_midi: 61, adjustment: 1, velocity: 80, duration: 480, channel: 0});
+    }
+
+    #[test]
+    fn test_as_midi_out_of_range() {
+        assert_eq!(Note::new(-3).as_midi(), 0);
+        assert_eq!(Note::new(250).as_midi(), 127);
+
+        let mut note = Note::new(120);
+        note.adjustment = 10;
+        assert_eq!(note.as_midi(), 127);
+        assert_eq!(note.fit_to_range(&Range::default(), RangePolicy::Fold).as_midi(), 118);
     }
 }
@@ -0,0 +1,49 @@
+use super::Note;
+use super::rhythm::EIGHTH;
+use super::super::chess::Move;
+use super::super::chess::types::MoveType;
+
+// General MIDI percussion lives on channel 10
+pub const DRUM_CHANNEL: u8 = 9;
+
+const SNARE: i32 = 38;
+const LOW_TOM: i32 = 45;
+const CRASH: i32 = 49;
+
+// Checks, captures and castling get a drum hit, other moves none.
+pub fn drum_for_move(the_move: &Move) -> Option<Note> {
+    let (drum, velocity) = match the_move.move_type {
+        _ if the_move.is_check() => (CRASH, 110),
+        MoveType::Take => (SNARE, 100),
+        MoveType::CastleKing | MoveType::CastleQueen => (LOW_TOM, 90),
+        _ => return None
+    };
+    let mut note = Note::new(drum);
+    note.velocity = velocity;
+    note.duration = EIGHTH;
+    note.channel = DRUM_CHANNEL;
+    Some(note)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drum_for_move() {
+        assert!(drum_for_move(&Move::parse("e4")).is_none());
+
+        let drum = drum_for_move(&Move::parse("exd5")).unwrap();
+        assert_eq!(drum.as_midi(), SNARE as u8);
+        assert_eq!(drum.channel, DRUM_CHANNEL);
+
+        assert_eq!(drum_for_move(&Move::parse("Qe5+")).unwrap().as_midi(), CRASH as u8);
+        assert_eq!(drum_for_move(&Move::parse("Qxe2#")).unwrap().as_midi(), CRASH as u8);
+    }
+
+    #[test]
+    fn test_drum_for_castling() {
+        assert_eq!(drum_for_move(&Move::parse("O-O")).unwrap().as_midi(), LOW_TOM as u8);
+        assert_eq!(drum_for_move(&Move::parse("O-O-O")).unwrap().as_midi(), LOW_TOM as u8);
+    }
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+
+pub const MIDI_LOWEST: i32 = 0;
+pub const MIDI_HIGHEST: i32 = 127;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangePolicy {
+    // Notes outside the range stick to its edges
+    Clamp,
+    // Notes outside the range move by octaves until they fit
+    Fold,
+    // Notes outside the range bounce back off its edges
+    Reflect
+}
+
+impl RangePolicy {
+    pub fn from_name(name: &str) -> Option<RangePolicy> {
+        let policy = match name {
+            "clamp" => RangePolicy::Clamp,
+            "fold" | "octave" => RangePolicy::Fold,
+            "reflect" => RangePolicy::Reflect,
+            _ => return None
+        };
+        Some(policy)
+    }
+}
+
+// Inclusive range of midi notes a voice may play.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub low: i32,
+    pub high: i32
+}
+
+impl Range {
+    // Ranges must hold at least an octave so every note can be folded into them.
+    pub fn new(low: i32, high: i32) -> Result<Range, Box<dyn Error>> {
+        if low < MIDI_LOWEST || high > MIDI_HIGHEST {
+            Err(format!("Range {}..{} is outside midi notes 0..127", low, high))?;
+        }
+        if high - low < 11 {
+            Err(format!("Range {}..{} is narrower than an octave", low, high))?;
+        }
+        Ok(Range {low, high})
+    }
+
+    pub fn contains(&self, midi_note: i32) -> bool {
+        self.low <= midi_note && midi_note <= self.high
+    }
+
+    pub fn fit(&self, midi_note: i32, policy: RangePolicy) -> i32 {
+        if self.contains(midi_note) {
+            return midi_note;
+        }
+        match policy {
+            RangePolicy::Clamp => midi_note.clamp(self.low, self.high),
+            RangePolicy::Fold => self.fold(midi_note),
+            RangePolicy::Reflect => self.reflect(midi_note)
+        }
+    }
+
+    fn fold(&self, midi_note: i32) -> i32 {
+        let mut folded = midi_note;
+        if folded < self.low {
+            folded += (self.low - folded + 11) / 12 * 12;
+        } else if folded > self.high {
+            folded -= (folded - self.high + 11) / 12 * 12;
+        }
+        folded
+    }
+
+    fn reflect(&self, midi_note: i32) -> i32 {
+        let width = self.high - self.low;
+        let offset = (midi_note - self.low).rem_euclid(width * 2);
+        if offset <= width {self.low + offset} else {self.high - (offset - width)}
+    }
+}
+
+impl Default for Range {
+    fn default() -> Range {
+        Range {low: MIDI_LOWEST, high: MIDI_HIGHEST}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_new() {
+        assert!(Range::new(36, 84).is_ok());
+        assert!(Range::new(60, 65).is_err());
+        assert!(Range::new(-12, 60).is_err());
+        assert!(Range::new(100, 140).is_err());
+    }
+
+    #[test]
+    fn test_clamp() {
+        let range = Range::new(48, 72).unwrap();
+        assert_eq!(range.fit(60, RangePolicy::Clamp), 60);
+        assert_eq!(range.fit(30, RangePolicy::Clamp), 48);
+        assert_eq!(range.fit(90, RangePolicy::Clamp), 72);
+    }
+
+    #[test]
+    fn test_fold() {
+        let range = Range::new(48, 72).unwrap();
+        assert_eq!(range.fit(47, RangePolicy::Fold), 59);
+        assert_eq!(range.fit(30, RangePolicy::Fold), 54);
+        assert_eq!(range.fit(73, RangePolicy::Fold), 61);
+        assert_eq!(range.fit(200, RangePolicy::Fold), 68);
+        assert_eq!(range.fit(-5, RangePolicy::Fold), 55);
+
+        let octave = Range::new(60, 71).unwrap();
+        assert!((-100..300).all(|note| octave.contains(octave.fit(note, RangePolicy::Fold))));
+    }
+
+    #[test]
+    fn test_reflect() {
+        let range = Range::new(48, 72).unwrap();
+        assert_eq!(range.fit(74, RangePolicy::Reflect), 70);
+        assert_eq!(range.fit(45, RangePolicy::Reflect), 51);
+        assert_eq!(range.fit(100, RangePolicy::Reflect), 52);
+        assert!((-100..300).all(|note| range.contains(range.fit(note, RangePolicy::Reflect))));
+    }
+}
@@ -0,0 +1,128 @@
+use super::super::chess::types::{MoveType, Role};
+
+use std::time::Duration;
+
+pub const TICKS_PER_BEAT: u32 = 480;
+pub const SIXTEENTH: u32 = TICKS_PER_BEAT / 4;
+pub const EIGHTH: u32 = TICKS_PER_BEAT / 2;
+pub const QUARTER: u32 = TICKS_PER_BEAT;
+pub const HALF: u32 = TICKS_PER_BEAT * 2;
+pub const WHOLE: u32 = TICKS_PER_BEAT * 4;
+// Beats per minute
+pub const DEFAULT_TEMPO: u32 = 100;
+
+// What a rhythm strategy knows about the ply a note was generated from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteContext {
+    pub role: Role,
+    pub move_type: MoveType,
+    pub check: bool,
+    pub think_time: Option<Duration>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rhythm {
+    Fixed(u32),
+    ThinkTime,
+    ByRole,
+    ByMoveType
+}
+
+impl Rhythm {
+    pub fn from_name(name: &str) -> Option<Rhythm> {
+        let rhythm = match name {
+            "fixed" => Rhythm::Fixed(QUARTER),
+            "think-time" | "clock" => Rhythm::ThinkTime,
+            "role" => Rhythm::ByRole,
+            "move-type" => Rhythm::ByMoveType,
+            _ => return None
+        };
+        Some(rhythm)
+    }
+
+    // Durations are in ticks, see TICKS_PER_BEAT
+    pub fn duration(&self, context: &NoteContext) -> u32 {
+        match self {
+            Rhythm::Fixed(duration) => *duration,
+            Rhythm::ThinkTime => Rhythm::duration_for_think_time(context.think_time),
+            Rhythm::ByRole => Rhythm::duration_for_role(context.role),
+            Rhythm::ByMoveType => Rhythm::duration_for_move_type(context.move_type)
+        }
+    }
+
+    // Quick moves are short notes, long thinks are held.
+    fn duration_for_think_time(think_time: Option<Duration>) -> u32 {
+        let millis = match think_time {
+            Some(think_time) => think_time.as_millis(),
+            None => return QUARTER
+        };
+        match millis {
+            0..=999 => SIXTEENTH,
+            1000..=2999 => EIGHTH,
+            3000..=9999 => QUARTER,
+            10000..=29999 => HALF,
+            _ => WHOLE
+        }
+    }
+
+    fn duration_for_role(role: Role) -> u32 {
+        match role {
+            Role::Pawn => EIGHTH,
+            Role::Knight => EIGHTH + SIXTEENTH,
+            Role::Bishop => QUARTER,
+            Role::Rook => HALF,
+            Role::Queen => QUARTER,
+            Role::King => WHOLE
+        }
+    }
+
+    fn duration_for_move_type(move_type: MoveType) -> u32 {
+        match move_type {
+            MoveType::None => EIGHTH,
+            MoveType::Simple => QUARTER,
+            MoveType::Take => HALF,
+            MoveType::CastleKing | MoveType::CastleQueen => WHOLE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(role: Role, move_type: MoveType, think_time: Option<Duration>) -> NoteContext {
+        NoteContext {role, move_type, check: false, think_time}
+    }
+
+    #[test]
+    fn test_fixed_rhythm() {
+        let rhythm = Rhythm::Fixed(HALF);
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, None)), HALF);
+        assert_eq!(rhythm.duration(&context(Role::King, MoveType::Take, Some(Duration::from_secs(60)))), HALF);
+    }
+
+    #[test]
+    fn test_think_time_rhythm() {
+        let rhythm = Rhythm::ThinkTime;
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, Some(Duration::from_millis(400)))), SIXTEENTH);
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, Some(Duration::from_secs(2)))), EIGHTH);
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, Some(Duration::from_secs(15)))), HALF);
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, Some(Duration::from_secs(90)))), WHOLE);
+        assert_eq!(rhythm.duration(&context(Role::Pawn, MoveType::Simple, None)), QUARTER);
+    }
+
+    #[test]
+    fn test_role_and_move_type_rhythms() {
+        assert_eq!(Rhythm::ByRole.duration(&context(Role::Rook, MoveType::Simple, None)), HALF);
+        assert_eq!(Rhythm::ByRole.duration(&context(Role::Knight, MoveType::Simple, None)), 360);
+        assert_eq!(Rhythm::ByMoveType.duration(&context(Role::Rook, MoveType::Take, None)), HALF);
+        assert_eq!(Rhythm::ByMoveType.duration(&context(Role::King, MoveType::CastleQueen, None)), WHOLE);
+    }
+
+    #[test]
+    fn test_rhythm_from_name() {
+        assert_eq!(Rhythm::from_name("role"), Some(Rhythm::ByRole));
+        assert_eq!(Rhythm::from_name("fixed"), Some(Rhythm::Fixed(QUARTER)));
+        assert_eq!(Rhythm::from_name("swing"), None);
+    }
+}
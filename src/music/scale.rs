@@ -0,0 +1,235 @@
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Scale {
+    Major,
+    Minor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+    MajorPentatonic,
+    MinorPentatonic,
+    Chromatic,
+    Custom(Vec<i32>)
+}
+
+impl Scale {
+    // Custom scales are semitone offsets from the tonic, ascending, within one octave.
+    pub fn new_custom(intervals: &[i32]) -> Result<Scale, Box<dyn Error>> {
+        if intervals.is_empty() || intervals[0] != 0 {
+            Err("Custom scale must start with the tonic (0)")?;
+        }
+        if intervals.windows(2).any(|pair| pair[0] >= pair[1]) {
+            Err("Custom scale intervals must be strictly ascending")?;
+        }
+        if intervals.iter().any(|interval| *interval < 0 || *interval > 11) {
+            Err("Custom scale intervals must be within one octave")?;
+        }
+        Ok(Scale::Custom(intervals.to_vec()))
+    }
+
+    pub fn from_name(name: &str) -> Option<Scale> {
+        let scale = match name.to_lowercase().as_str() {
+            "major" | "ionian" => Scale::Major,
+            "minor" | "aeolian" => Scale::Minor,
+            "dorian" => Scale::Dorian,
+            "phrygian" => Scale::Phrygian,
+            "lydian" => Scale::Lydian,
+            "mixolydian" => Scale::Mixolydian,
+            "locrian" => Scale::Locrian,
+            "major-pentatonic" | "pentatonic" => Scale::MajorPentatonic,
+            "minor-pentatonic" => Scale::MinorPentatonic,
+            "chromatic" => Scale::Chromatic,
+            _ => return None
+        };
+        Some(scale)
+    }
+
+    pub fn intervals(&self) -> Vec<i32> {
+        match self {
+            Scale::Major => vec![0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => vec![0, 2, 3, 5, 7, 8, 10],
+            Scale::Dorian => vec![0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => vec![0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => vec![0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => vec![0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => vec![0, 1, 3, 5, 6, 8, 10],
+            Scale::MajorPentatonic => vec![0, 2, 4, 7, 9],
+            Scale::MinorPentatonic => vec![0, 3, 5, 7, 10],
+            Scale::Chromatic => (0..12).collect(),
+            Scale::Custom(intervals) => intervals.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    // Midi note of the tonic in the octave melodies start from.
+    pub tonic: i32,
+    pub scale: Scale
+}
+
+impl Key {
+    pub fn new(tonic: i32, scale: Scale) -> Key {
+        Key {tonic, scale}
+    }
+
+    // Degree 0 is the tonic, negative degrees go below it.
+    pub fn degree_to_midi(&self, degree: i32) -> i32 {
+        let intervals = self.scale.intervals();
+        let len = intervals.len() as i32;
+        let octave = degree.div_euclid(len);
+        let step = degree.rem_euclid(len) as usize;
+        self.tonic + octave * 12 + intervals[step]
+    }
+
+    // Closest scale degree to a midi note, preferring the lower degree on ties.
+    pub fn nearest_degree(&self, midi_note: i32) -> i32 {
+        let len = self.scale.intervals().len() as i32;
+        let mut degree = (midi_note - self.tonic).div_euclid(12) * len;
+        while self.degree_to_midi(degree + 1) <= midi_note {
+            degree += 1;
+        }
+        let below = midi_note - self.degree_to_midi(degree);
+        let above = self.degree_to_midi(degree + 1) - midi_note;
+        if above < below {degree + 1} else {degree}
+    }
+
+    pub fn quantize(&self, midi_note: i32) -> i32 {
+        self.degree_to_midi(self.nearest_degree(midi_note))
+    }
+
+    // ECO volumes pick the mode (A flank, B semi-open, C open, D closed, E indian),
+    // the opening number picks the tonic.
+    pub fn from_eco(eco: &str) -> Option<Key> {
+        let mut chars = eco.trim().chars();
+        let scale = match chars.next()? {
+            'A' => Scale::Dorian,
+            'B' => Scale::Minor,
+            'C' => Scale::Major,
+            'D' => Scale::Mixolydian,
+            'E' => Scale::Phrygian,
+            _ => return None
+        };
+        let number = chars.as_str().parse::<i32>().ok()?;
+        Some(Key::new(57 + number % 12, scale))
+    }
+
+    // A tonic with an optional scale, major when left out: "D", "F#-minor", "Bb dorian".
+    pub fn from_name(name: &str) -> Option<Key> {
+        let name = name.trim();
+        let mut chars = name.chars();
+        let mut tonic = match chars.next()?.to_ascii_uppercase() {
+            'C' => 60,
+            'D' => 62,
+            'E' => 64,
+            'F' => 65,
+            'G' => 67,
+            'A' => 69,
+            'B' => 71,
+            _ => return None
+        };
+        let rest = chars.as_str();
+        let rest = if let Some(rest) = rest.strip_prefix('#') {
+            tonic += 1;
+            rest
+        } else if let Some(rest) = rest.strip_prefix('b') {
+            tonic -= 1;
+            rest
+        } else {
+            rest
+        };
+        let scale = match rest.trim_start_matches(&['-', ' '][..]) {
+            "" => Scale::Major,
+            scale => Scale::from_name(scale)?
+        };
+        Some(Key::new(tonic, scale))
+    }
+
+    pub fn from_result(result: &str) -> Option<Key> {
+        match result.trim() {
+            "1-0" => Some(Key::new(60, Scale::Major)),
+            "0-1" => Some(Key::new(60, Scale::Minor)),
+            "1/2-1/2" => Some(Key::new(60, Scale::Dorian)),
+            _ => None
+        }
+    }
+}
+
+impl Default for Key {
+    fn default() -> Key {
+        Key::new(60, Scale::Major)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_degree_to_midi_major() {
+        let key = Key::new(60, Scale::Major);
+        assert_eq!(key.degree_to_midi(0), 60);
+        assert_eq!(key.degree_to_midi(2), 64);
+        assert_eq!(key.degree_to_midi(7), 72);
+        assert_eq!(key.degree_to_midi(-1), 59);
+        assert_eq!(key.degree_to_midi(-7), 48);
+    }
+
+    #[test]
+    fn test_degree_to_midi_pentatonic() {
+        let key = Key::new(57, Scale::MinorPentatonic);
+        assert_eq!(key.degree_to_midi(1), 60);
+        assert_eq!(key.degree_to_midi(5), 69);
+        assert_eq!(key.degree_to_midi(-1), 55);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let key = Key::new(60, Scale::Major);
+        assert_eq!(key.quantize(61), 60);
+        assert_eq!(key.quantize(66), 65);
+        assert_eq!(key.quantize(70), 69);
+        assert_eq!(key.quantize(47), 47);
+        assert_eq!(key.nearest_degree(72), 7);
+    }
+
+    #[test]
+    fn test_key_from_name() {
+        assert_eq!(Key::from_name("C"), Some(Key::new(60, Scale::Major)));
+        assert_eq!(Key::from_name("f#-minor"), Some(Key::new(66, Scale::Minor)));
+        assert_eq!(Key::from_name("Bb dorian"), Some(Key::new(70, Scale::Dorian)));
+        assert_eq!(Key::from_name("A-minor-pentatonic"), Some(Key::new(69, Scale::MinorPentatonic)));
+        assert_eq!(Key::from_name("H"), None);
+        assert_eq!(Key::from_name("C-bebop"), None);
+        assert_eq!(Key::from_name(""), None);
+    }
+
+    #[test]
+    fn test_custom_scale() {
+        let scale = Scale::new_custom(&[0, 3, 6, 9]).unwrap();
+        let key = Key::new(60, scale);
+        assert_eq!(key.degree_to_midi(3), 69);
+        assert_eq!(key.degree_to_midi(4), 72);
+
+        assert!(Scale::new_custom(&[]).is_err());
+        assert!(Scale::new_custom(&[0, 4, 2]).is_err());
+        assert!(Scale::new_custom(&[0, 12]).is_err());
+    }
+
+    #[test]
+    fn test_key_from_eco() {
+        assert_eq!(Key::from_eco("B01"), Some(Key::new(58, Scale::Minor)));
+        assert_eq!(Key::from_eco("C50"), Some(Key::new(59, Scale::Major)));
+        assert_eq!(Key::from_eco("?"), None);
+        assert_eq!(Key::from_eco("F00"), None);
+    }
+
+    #[test]
+    fn test_key_from_result() {
+        assert_eq!(Key::from_result("0-1"), Some(Key::new(60, Scale::Minor)));
+        assert_eq!(Key::from_result("*"), None);
+    }
+}
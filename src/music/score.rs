@@ -0,0 +1,629 @@
+use super::{Note, Melody, Key, Rhythm};
+use super::rhythm::{NoteContext, QUARTER};
+use super::percussion;
+use super::harmony::Chord;
+use super::mapping::{self, Mapping};
+use super::variation::Variation;
+use super::range::{Range, RangePolicy};
+use super::instrument::Instrument;
+use super::sequencer::TempoMap;
+use super::super::chess::{Game, Ply, PieceName, Cell};
+use super::super::chess::types::Role;
+
+use std::time::Duration;
+
+extern crate crossbeam;
+
+// Enough for the largest chord, a seventh
+const HARMONY_VOICES: usize = 4;
+const PAD_VELOCITY: i32 = 60;
+// Percent of the tempo with no time left on the clock
+const MAX_HURRY: u32 = 150;
+// Tempo changes go in steps, not with every second
+const HURRY_STEP: u32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestPolicy {
+    // A piece that doesn't move is silent for the ply
+    Rest,
+    // A piece that doesn't move holds its last note
+    Sustain
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreConfig {
+    pub mapping: &'static dyn Mapping,
+    pub key: Key,
+    pub rhythm: Rhythm,
+    pub rest_policy: RestPolicy,
+    pub percussion: bool,
+    // A pad of chords following the evaluation of the position
+    pub harmony: bool,
+    pub range_policy: RangePolicy,
+    // Used by every piece voice without its own entry in voice_ranges
+    pub range: Range,
+    pub voice_ranges: Vec<((PieceName, bool), Range)>,
+    // Overrides Instrument::for_piece for some voices
+    pub instruments: Vec<((PieceName, bool), Instrument)>,
+    // Seeded ornaments, humanizing and instrument picks, None plays the score as composed
+    pub variation: Option<Variation>,
+    // The music speeds up while the mover's clock is below this
+    pub time_pressure: Option<Duration>
+}
+
+impl ScoreConfig {
+    pub fn range_for(&self, name: PieceName, white: bool) -> Range {
+        self.voice_ranges.iter()
+            .find(|(piece, _)| *piece == (name, white))
+            .map_or(self.range, |(_, range)| *range)
+    }
+
+    pub fn instrument_for(&self, name: PieceName, white: bool, role: Role) -> Instrument {
+        self.instruments.iter()
+            .find(|(piece, _)| *piece == (name, white))
+            .map_or_else(|| Instrument::for_piece(role, white), |(_, instrument)| *instrument)
+    }
+}
+
+impl Default for ScoreConfig {
+    fn default() -> ScoreConfig {
+        ScoreConfig {
+            mapping: &mapping::InKey,
+            key: Key::default(),
+            rhythm: Rhythm::Fixed(QUARTER),
+            rest_policy: RestPolicy::Rest,
+            percussion: true,
+            harmony: false,
+            range_policy: RangePolicy::Fold,
+            range: Range::default(),
+            voice_ranges: Vec::new(),
+            instruments: Vec::new(),
+            variation: None,
+            time_pressure: None
+        }
+    }
+}
+
+// Ticks are absolute from the start of the game, a rest has no note.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub ply: usize,
+    pub tick: u32,
+    pub duration: u32,
+    pub note: Option<Note>
+}
+
+impl Event {
+    pub fn end(&self) -> u32 {
+        self.tick + self.duration
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Voice {
+    pub name: String,
+    // None for voices that don't follow a single piece, like percussion
+    pub piece: Option<(PieceName, bool)>,
+    // The role the piece's voice and instrument were picked for
+    pub role: Option<Role>,
+    pub instrument: Instrument,
+    pub events: Vec<Event>
+}
+
+impl Voice {
+    fn new(name: String, piece: Option<(PieceName, bool)>, instrument: Instrument) -> Voice {
+        Voice {name, piece, role: None, instrument, events: Vec::new()}
+    }
+
+    fn add_rest(&mut self, ply: usize, tick: u32, duration: u32) {
+        self.events.push(Event {ply, tick, duration, note: None});
+    }
+
+    fn add_note(&mut self, ply: usize, tick: u32, duration: u32, mut note: Note) {
+        note.duration = duration;
+        note.channel = self.instrument.channel;
+        self.events.push(Event {ply, tick, duration, note: Some(note)});
+    }
+
+    // Plays the notes one after the other within the ply, keeping their relative durations.
+    // Every note gets at least a tick, notes that no longer fit are left out and the last
+    // one played holds until the end of the ply. A phrase with no length rests.
+    fn add_phrase(&mut self, ply: usize, tick: u32, duration: u32, phrase: &[Note]) {
+        let phrase_length: u64 = phrase.iter().map(|note| note.duration as u64).sum();
+        if duration == 0 {
+            return;
+        }
+        if phrase_length == 0 {
+            return self.add_rest(ply, tick, duration);
+        }
+        let end = tick + duration;
+        let mut start = tick;
+        for (idx, note) in phrase.iter().enumerate() {
+            let note_duration = ((note.duration as u64 * duration as u64 / phrase_length) as u32).max(1);
+            let last = idx + 1 == phrase.len() || start + note_duration >= end;
+            let note_duration = if last {end - start} else {note_duration};
+            self.add_note(ply, start, note_duration, *note);
+            start += note_duration;
+            if last {
+                break;
+            }
+        }
+    }
+
+    // Extends the last note over another ply, or rests if there is nothing to hold.
+    fn sustain(&mut self, ply: usize, tick: u32, duration: u32) {
+        match self.events.last_mut() {
+            Some(event) if event.note.is_some() && event.end() == tick => {
+                event.duration += duration;
+                event.note.as_mut().unwrap().duration = event.duration;
+            },
+            _ => self.add_rest(ply, tick, duration)
+        }
+    }
+
+    pub fn notes(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().filter(|event| event.note.is_some())
+    }
+}
+
+// What a voice needs from a piece, taken off the board so voices can be built in parallel
+struct PieceTrack {
+    name: PieceName,
+    white: bool,
+    role: Role,
+    first_cell: Cell,
+    history: Vec<(Cell, bool)>,
+    live: bool,
+    instrument: Instrument
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+    pub voices: Vec<Voice>,
+    // Start tick of every ply, plus the end of the last one
+    pub ply_ticks: Vec<u32>,
+    // From each tick on, the percent of the playing tempo. Empty keeps the tempo.
+    pub tempo_changes: Vec<(u32, u32)>,
+    pub key: Key
+}
+
+impl Score {
+    pub fn from_game(game: &Game, pieces: &[(PieceName, bool)], config: &ScoreConfig) -> Score {
+        let ply_ticks = Score::ply_ticks(&game.plies, &config.rhythm);
+        let mut voices: Vec<Voice> = crossbeam::scope(|s| {
+            let handles: Vec<_> = pieces.iter().map(|(name, white)| {
+                let piece = game.board.get_piece_with_name(*name, *white);
+                // A promoted pawn keeps the voice it started with
+                let role = piece.get_role_after(0);
+                let track = PieceTrack {
+                    name: *name,
+                    white: *white,
+                    role,
+                    first_cell: piece.first_cell(),
+                    history: piece.get_cell_and_capture_history(),
+                    live: piece.is_live(),
+                    instrument: config.instrument_for(*name, *white, role)
+                };
+                let (plies, ply_ticks) = (&game.plies, &ply_ticks);
+                s.spawn(move |_| Score::piece_voice(&track, plies, ply_ticks, config))
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        }).unwrap();
+
+        if config.harmony {
+            voices.extend(Score::harmony_voices(&game.plies, &ply_ticks, &config.key));
+        }
+        if config.percussion {
+            voices.push(Score::percussion_voice(&game.plies, &ply_ticks));
+        }
+
+        let tempo_changes = match config.time_pressure {
+            Some(threshold) => Score::tempo_changes(&game.plies, &ply_ticks, threshold),
+            None => Vec::new()
+        };
+        let mut score = Score {voices, ply_ticks, tempo_changes, key: config.key.clone()};
+        if let Some(variation) = &config.variation {
+            variation.apply(&mut score, &config.key);
+        }
+        score
+    }
+
+    pub fn tempo_map(&self, tempo: u32) -> TempoMap {
+        let mut tempo_map = TempoMap::constant(tempo);
+        for (tick, percent) in self.tempo_changes.iter() {
+            tempo_map.set_tempo(*tick, tempo * percent / 100);
+        }
+        tempo_map
+    }
+
+    pub fn length(&self) -> u32 {
+        *self.ply_ticks.last().unwrap_or(&0)
+    }
+
+    // Notes sounding while a ply is played, with the voice they belong to
+    pub fn notes_at_ply(&self, ply: usize) -> Vec<(&Voice, &Note)> {
+        let (start, end) = match (self.ply_ticks.get(ply), self.ply_ticks.get(ply + 1)) {
+            (Some(start), Some(end)) => (*start, *end),
+            _ => return Vec::new()
+        };
+        self.voices.iter().flat_map(|voice| {
+            voice.notes()
+                .filter(move |event| event.tick < end && event.end() > start)
+                .map(move |event| (voice, event.note.as_ref().unwrap()))
+        }).collect()
+    }
+
+    fn note_context(ply: &Ply) -> NoteContext {
+        NoteContext {
+            role: ply.the_move.role,
+            move_type: ply.the_move.move_type,
+            check: ply.the_move.is_check(),
+            think_time: ply.think_time
+        }
+    }
+
+    // Each ply lasts as long as the rhythm says the move that was played should.
+    fn ply_ticks(plies: &[Ply], rhythm: &Rhythm) -> Vec<u32> {
+        let mut ticks = vec![0];
+        for ply in plies.iter() {
+            let start = *ticks.last().unwrap();
+            ticks.push(start + rhythm.duration(&Score::note_context(ply)));
+        }
+        ticks
+    }
+
+    fn piece_voice(track: &PieceTrack, plies: &[Ply], ply_ticks: &[u32], config: &ScoreConfig) -> Voice {
+        let (name, white) = (track.name, track.white);
+        let color = if white {"White"} else {"Black"};
+        let mut voice = Voice {role: Some(track.role), ..Voice::new(format!("{} {:?}", color, name), Some((name, white)), track.instrument)};
+
+        // Starting cell first, so phrases[n + 1] is played for ply n
+        let mut history: Vec<(Cell, bool)> = vec![(track.first_cell, false)];
+        history.extend(track.history.iter().cloned());
+        let range = config.range_for(name, white);
+        let phrases: Vec<Vec<Note>> = config.mapping.phrases(&history, track.role, &config.key).iter()
+            .map(|phrase| phrase.iter().map(|note| note.fit_to_range(&range, config.range_policy)).collect())
+            .collect();
+
+        let live_plies = track.history.len();
+        for (ply_idx, ply) in plies.iter().enumerate().take(live_plies) {
+            let (tick, duration) = (ply_ticks[ply_idx], ply_ticks[ply_idx + 1] - ply_ticks[ply_idx]);
+            if ply.moved_piece(name, white) {
+                // A mapping may give fewer or empty phrases, add_phrase rests for those
+                let mut phrase = phrases.get(ply_idx + 1).cloned().unwrap_or_default();
+                if let (Some(accent), Some(first)) = (Melody::accent_for(&Score::note_context(ply)), phrase.first_mut()) {
+                    first.velocity = first.velocity.max(accent);
+                }
+                voice.add_phrase(ply_idx, tick, duration, &phrase);
+            } else if config.rest_policy == RestPolicy::Sustain {
+                voice.sustain(ply_idx, tick, duration);
+            } else {
+                voice.add_rest(ply_idx, tick, duration);
+            }
+        }
+
+        // Captured pieces fall silent with a last gesture squeezed into the capturing ply.
+        if !track.live && live_plies < plies.len() {
+            let (tick, duration) = (ply_ticks[live_plies], ply_ticks[live_plies + 1] - ply_ticks[live_plies]);
+            match phrases.get(live_plies).and_then(|phrase| phrase.last()) {
+                Some(last_note) => {
+                    let mut melody = Melody {notes: vec![*last_note]};
+                    melody.add_falling_gesture(&config.key);
+                    melody.fit_to_range(&range, config.range_policy);
+                    voice.add_phrase(live_plies, tick, duration, &melody.notes[1..]);
+                },
+                None => voice.add_rest(live_plies, tick, duration)
+            }
+        }
+
+        voice
+    }
+
+    // One voice per chord tone, a chord held over several plies is sustained.
+    fn harmony_voices(plies: &[Ply], ply_ticks: &[u32], key: &Key) -> Vec<Voice> {
+        let mut voices: Vec<Voice> = (1..=HARMONY_VOICES)
+            .map(|idx| Voice::new(format!("Harmony {}", idx), None, Instrument::pad()))
+            .collect();
+        let mut previous = (0, None);
+        for (ply_idx, ply) in plies.iter().enumerate() {
+            let (tick, duration) = (ply_ticks[ply_idx], ply_ticks[ply_idx + 1] - ply_ticks[ply_idx]);
+            let chord = Chord::for_position(ply.advantage(), previous.0, key);
+            let notes = chord.notes();
+            for (idx, voice) in voices.iter_mut().enumerate() {
+                match notes.get(idx) {
+                    Some(_) if previous.1 == Some(chord) => voice.sustain(ply_idx, tick, duration),
+                    Some(pitch) => {
+                        let mut note = Note::new(*pitch);
+                        note.velocity = PAD_VELOCITY;
+                        voice.add_note(ply_idx, tick, duration, note);
+                    },
+                    None => voice.add_rest(ply_idx, tick, duration)
+                }
+            }
+            previous = (ply.advantage(), Some(chord));
+        }
+        voices
+    }
+
+    // Below the threshold the tempo rises with the time used, up to MAX_HURRY with the flag about to fall.
+    fn tempo_changes(plies: &[Ply], ply_ticks: &[u32], threshold: Duration) -> Vec<(u32, u32)> {
+        let mut changes = Vec::new();
+        let mut current = 100;
+        for (ply_idx, ply) in plies.iter().enumerate() {
+            let clock = match ply.clock {
+                Some(clock) => clock,
+                None => continue
+            };
+            let pressure = threshold.checked_sub(clock).map_or(0.0, |used| used.as_secs_f64() / threshold.as_secs_f64());
+            let hurry = (pressure * (MAX_HURRY - 100) as f64) as u32 / HURRY_STEP * HURRY_STEP;
+            if 100 + hurry != current {
+                current = 100 + hurry;
+                changes.push((ply_ticks[ply_idx], current));
+            }
+        }
+        changes
+    }
+
+    fn percussion_voice(plies: &[Ply], ply_ticks: &[u32]) -> Voice {
+        let mut voice = Voice::new(String::from("Percussion"), None, Instrument::percussion());
+        for (ply_idx, ply) in plies.iter().enumerate() {
+            let (tick, duration) = (ply_ticks[ply_idx], ply_ticks[ply_idx + 1] - ply_ticks[ply_idx]);
+            match percussion::drum_for_move(&ply.the_move) {
+                Some(drum) => {
+                    let hit = drum.duration.min(duration);
+                    voice.add_note(ply_idx, tick, hit, drum);
+                    if hit < duration {
+                        voice.add_rest(ply_idx, tick + hit, duration - hit);
+                    }
+                },
+                None => voice.add_rest(ply_idx, tick, duration)
+            }
+        }
+        voice
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::rhythm::{EIGHTH, HALF, WHOLE};
+    use super::super::super::chess::{Move, EngineEval};
+
+    fn game_with_moves(moves: &[&str]) -> Game {
+        Game::new_with_moves(&Move::parse_moves(moves))
+    }
+
+    #[test]
+    fn test_voices_follow_plies() {
+        let game = game_with_moves(&["e4", "e5", "Nf3", "Nc6", "d4"]);
+        let config = ScoreConfig {percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Qknight, false)], &config);
+
+        assert_eq!(score.voices.len(), 2);
+        assert_eq!(score.ply_ticks, vec![0, 480, 960, 1440, 1920, 2400]);
+        assert_eq!(score.length(), 2400);
+
+        let pawn = &score.voices[0];
+        assert_eq!(pawn.piece, Some((PieceName::Epawn, true)));
+        assert_eq!(pawn.events.len(), 5);
+        assert!(pawn.events[0].note.is_some());
+        assert!(pawn.events[1..].iter().all(|event| event.note.is_none()));
+        assert_eq!(pawn.events[3].tick, 1440);
+
+        let knight = &score.voices[1];
+        assert_eq!(knight.notes().count(), 1);
+        let knight_move = knight.notes().next().unwrap();
+        assert_eq!(knight_move.ply, 3);
+        assert_eq!(knight_move.tick, 1440);
+    }
+
+    #[test]
+    fn test_notes_follow_key() {
+        let game = game_with_moves(&["e4", "e5"]);
+        let config = ScoreConfig {percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true)], &config);
+        // e-file starts on degree 4 (G), two ranks up is four more degrees
+        let note = score.voices[0].events[0].note.unwrap();
+        assert_eq!(note.as_midi(), config.key.degree_to_midi(8) as u8);
+    }
+
+    #[test]
+    fn test_voice_ranges() {
+        let game = game_with_moves(&["e4", "e5"]);
+        let config = ScoreConfig {
+            percussion: false,
+            voice_ranges: vec![((PieceName::Epawn, true), Range::new(48, 71).unwrap())],
+            ..ScoreConfig::default()
+        };
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Epawn, false)], &config);
+        assert_eq!(score.voices[0].events[0].note.unwrap().as_midi(), 62);
+        assert_eq!(config.range_for(PieceName::Epawn, false), Range::default());
+    }
+
+    #[test]
+    fn test_mapping_phrases_fill_the_ply() {
+        let game = game_with_moves(&["Nf3", "e5"]);
+        let config = ScoreConfig {mapping: &mapping::Motif, percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Kknight, true)], &config);
+        let events = &score.voices[0].events;
+        // The knight's arpeggio shares the first ply, then it rests
+        assert_eq!(events.iter().map(|event| event.note.map(|note| note.pitch())).collect::<Vec<_>>(),
+            vec![Some(76), Some(79), Some(83), None]);
+        assert_eq!(events[0].tick + events[0].duration, events[1].tick);
+        assert_eq!(events[2].end(), score.ply_ticks[1]);
+    }
+
+    #[test]
+    fn test_phrase_longer_than_the_ply() {
+        let mut voice = Voice::new("test".to_string(), None, Instrument::pad());
+        let phrase: Vec<Note> = (60..65).map(Note::new).collect();
+        voice.add_phrase(0, 100, 3, &phrase);
+        // Three one tick notes, the last two don't fit
+        let notes: Vec<(u32, u32, i32)> = voice.events.iter()
+            .map(|event| (event.tick, event.duration, event.note.unwrap().pitch()))
+            .collect();
+        assert_eq!(notes, vec![(100, 1, 60), (101, 1, 61), (102, 1, 62)]);
+
+        let mut voice = Voice::new("test".to_string(), None, Instrument::pad());
+        voice.add_phrase(0, 0, 480, &[]);
+        let silent = Note {duration: 0, ..Note::new(60)};
+        voice.add_phrase(1, 480, 480, &[silent, silent]);
+        voice.add_phrase(2, 960, 0, &phrase);
+        assert_eq!(voice.events.len(), 2);
+        assert!(voice.events.iter().all(|event| event.note.is_none() && event.duration == 480));
+    }
+
+    // Empty phrases for every move, or none at all
+    #[derive(Debug)]
+    struct Silent {
+        phrases: bool
+    }
+
+    impl Mapping for Silent {
+        fn name(&self) -> &'static str {"silent"}
+        fn phrases(&self, history: &[(Cell, bool)], _role: Role, _key: &Key) -> Vec<Vec<Note>> {
+            if self.phrases {vec![Vec::new(); history.len()]} else {Vec::new()}
+        }
+    }
+
+    #[test]
+    fn test_mapping_without_notes() {
+        // Captures and a check are accented, the d pawn and the e pawn are taken
+        let game = game_with_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3", "Qe5+"]);
+        for mapping in [&Silent {phrases: true}, &Silent {phrases: false}].iter() {
+            let config = ScoreConfig {mapping: *mapping, percussion: false, ..ScoreConfig::default()};
+            let score = Score::from_game(&game, &[(PieceName::Dpawn, false), (PieceName::Epawn, true), (PieceName::Queen, false)], &config);
+            for voice in score.voices.iter() {
+                assert!(voice.events.iter().all(|event| event.note.is_none()), "{}", voice.name);
+                assert_eq!(voice.events.last().unwrap().end(), score.ply_ticks[voice.events.last().unwrap().ply + 1]);
+            }
+            assert_eq!(score.voices[2].events.len(), 6);
+        }
+    }
+
+    #[test]
+    fn test_sustain_holds_notes() {
+        let game = game_with_moves(&["e4", "e5", "Nf3", "Nc6", "d4"]);
+        let config = ScoreConfig {rest_policy: RestPolicy::Sustain, percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Qknight, false)], &config);
+
+        let pawn = &score.voices[0];
+        assert_eq!(pawn.events.len(), 1);
+        assert_eq!(pawn.events[0].duration, 2400);
+        assert_eq!(pawn.events[0].note.unwrap().duration, 2400);
+
+        let knight = &score.voices[1];
+        assert_eq!(knight.events.len(), 4);
+        assert!(knight.events[2].note.is_none());
+        assert_eq!(knight.events[3].duration, 960);
+    }
+
+    #[test]
+    fn test_captured_piece_ends_with_gesture() {
+        let game = game_with_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3"]);
+        let config = ScoreConfig {percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Dpawn, false), (PieceName::Epawn, true)], &config);
+
+        let black_pawn = &score.voices[0];
+        let gesture: Vec<&Event> = black_pawn.events.iter().filter(|event| event.ply == 2).collect();
+        assert_eq!(gesture.len(), 3);
+        assert_eq!(gesture[0].tick, 960);
+        assert_eq!(gesture.iter().map(|event| event.duration).sum::<u32>(), 480);
+        assert!(gesture[0].note.unwrap().as_midi() > gesture[2].note.unwrap().as_midi());
+        assert!(black_pawn.events.iter().all(|event| event.ply <= 2));
+
+        let white_pawn = &score.voices[1];
+        let capture = white_pawn.events.iter().find(|event| event.ply == 2).unwrap();
+        assert_eq!(capture.note.unwrap().velocity, 110);
+        assert!(white_pawn.events.iter().all(|event| event.ply <= 3));
+    }
+
+    #[test]
+    fn test_ply_ticks_follow_rhythm() {
+        let mut game = game_with_moves(&["e4", "e5", "Nf3"]);
+        game.set_think_times(&[Some(Duration::from_secs(15)), Some(Duration::from_secs(1)), Some(Duration::from_secs(45))]);
+        let config = ScoreConfig {rhythm: Rhythm::ThinkTime, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Kknight, true)], &config);
+        assert_eq!(score.ply_ticks, vec![0, HALF, HALF + EIGHTH, HALF + EIGHTH + WHOLE]);
+        assert_eq!(score.voices[0].events[2].duration, WHOLE);
+    }
+
+    #[test]
+    fn test_percussion_voice() {
+        let game = game_with_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3", "Qe5+"]);
+        let score = Score::from_game(&game, &[], &ScoreConfig::default());
+        assert_eq!(score.voices.len(), 1);
+
+        let drums = &score.voices[0];
+        assert_eq!(drums.piece, None);
+        let hits: Vec<usize> = drums.notes().map(|event| event.ply).collect();
+        assert_eq!(hits, vec![2, 3, 5]);
+        assert_eq!(drums.events.iter().map(|event| event.duration).sum::<u32>(), score.length());
+    }
+
+    #[test]
+    fn test_harmony_voices() {
+        // White wins a pawn on ply 2, black wins it back and white wins a knight on ply 6
+        let game = game_with_moves(&["e4", "d5", "exd5", "Nf6", "Nc3", "Nxd5", "Nxd5"]);
+        let config = ScoreConfig {harmony: true, percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[], &config);
+        assert_eq!(score.voices.len(), HARMONY_VOICES);
+        assert!(score.voices.iter().all(|voice| voice.instrument == Instrument::pad()));
+
+        let chords: Vec<Vec<i32>> = (0..game.plies.len()).map(|ply| {
+            score.notes_at_ply(ply).iter().map(|(_, note)| note.pitch()).collect()
+        }).collect();
+        // Even after the opening moves, sustained while nothing changes
+        assert_eq!(chords[0], vec![48, 53, 55]);
+        assert_eq!(score.voices[0].events[0].duration, QUARTER * 2);
+        assert_eq!(chords[2], vec![53, 57, 60]);
+        assert_eq!(chords[5], vec![48, 53, 55]);
+        // Winning a knight is a swing, a dominant seventh on G
+        assert_eq!(chords[6], vec![55, 59, 62, 65]);
+        assert!(score.voices[3].events[0].note.is_none());
+    }
+
+    #[test]
+    fn test_engine_evals_drive_harmony() {
+        let mut game = game_with_moves(&["e4", "e5", "Nf3"]);
+        game.set_engine_evals(&[Some(EngineEval::Centipawns(20)), Some(EngineEval::Centipawns(150)), Some(EngineEval::Mate(3))]);
+        let config = ScoreConfig {harmony: true, percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[], &config);
+        let chords: Vec<Vec<i32>> = (0..game.plies.len()).map(|ply| {
+            score.notes_at_ply(ply).iter().map(|(_, note)| note.pitch()).collect()
+        }).collect();
+        assert_eq!(chords[0], vec![48, 53, 55]);
+        assert_eq!(chords[1], vec![53, 57, 60]);
+        // A mate coming is a swing
+        assert_eq!(chords[2], vec![55, 59, 62, 65]);
+    }
+
+    #[test]
+    fn test_time_pressure_speeds_up() {
+        let mut game = game_with_moves(&["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        let clocks: Vec<Option<Duration>> = [90, 80, 45, 20, 0].iter().map(|secs| Some(Duration::from_secs(*secs))).collect();
+        game.set_clocks(&clocks);
+        let config = ScoreConfig {time_pressure: Some(Duration::from_secs(60)), ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[], &config);
+        let ticks = &score.ply_ticks;
+        assert_eq!(score.tempo_changes, vec![(ticks[2], 110), (ticks[3], 130), (ticks[4], 150)]);
+
+        let tempo_map = score.tempo_map(100);
+        assert_eq!(tempo_map.micros_at(ticks[1]), 600_000);
+        assert!(tempo_map.micros_at(ticks[5]) - tempo_map.micros_at(ticks[4]) < 600_000);
+
+        let relaxed = Score::from_game(&game, &[], &ScoreConfig::default());
+        assert!(relaxed.tempo_changes.is_empty());
+        assert_eq!(relaxed.tempo_map(100), TempoMap::constant(100));
+    }
+
+    #[test]
+    fn test_notes_at_ply() {
+        let game = game_with_moves(&["e4", "e5", "Nf3"]);
+        let config = ScoreConfig {rest_policy: RestPolicy::Sustain, percussion: false, ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Epawn, false)], &config);
+        assert_eq!(score.notes_at_ply(0).len(), 1);
+        assert_eq!(score.notes_at_ply(2).len(), 2);
+        assert!(score.notes_at_ply(3).is_empty());
+    }
+}
@@ -0,0 +1,501 @@
+use super::midi;
+use super::rhythm::TICKS_PER_BEAT;
+use super::score::Score;
+
+extern crate crossbeam;
+use crossbeam::channel::{self, Sender, Receiver, RecvTimeoutError};
+
+use std::cmp::Reverse;
+use std::error::Error;
+use std::fmt;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Where the sequencer sends its messages, a midi port or a recording in tests.
+pub trait MidiSink {
+    fn send(&mut self, message: &[u8]);
+}
+
+// Lets sequencers started one after the other share a port.
+impl<S: MidiSink> MidiSink for Arc<Mutex<S>> {
+    fn send(&mut self, message: &[u8]) {
+        self.lock().unwrap().send(message);
+    }
+}
+
+// Tempo in beats per minute from each tick on, the first change is at tick 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    changes: Vec<(u32, u32)>
+}
+
+impl TempoMap {
+    pub fn constant(tempo: u32) -> TempoMap {
+        TempoMap {changes: vec![(0, tempo.max(1))]}
+    }
+
+    // Later changes at the same tick replace earlier ones.
+    pub fn set_tempo(&mut self, tick: u32, tempo: u32) {
+        self.changes.retain(|(change_tick, _)| *change_tick != tick);
+        self.changes.push((tick, tempo.max(1)));
+        self.changes.sort_by_key(|(change_tick, _)| *change_tick);
+    }
+
+    // (tick, beats per minute) sorted by tick
+    pub fn changes(&self) -> &[(u32, u32)] {
+        &self.changes
+    }
+
+    // Microseconds from the start of the score to the tick.
+    pub fn micros_at(&self, tick: u32) -> u64 {
+        let mut micros = 0;
+        for (idx, (start, tempo)) in self.changes.iter().enumerate() {
+            if tick <= *start {
+                break;
+            }
+            let end = self.changes.get(idx + 1).map_or(tick, |(next, _)| tick.min(*next));
+            micros += TempoMap::ticks_to_micros(end - start, *tempo);
+        }
+        micros
+    }
+
+    // Last tick reached by the time the microseconds have passed.
+    pub fn tick_at(&self, micros: u64) -> u32 {
+        let mut elapsed = 0;
+        for (idx, (start, tempo)) in self.changes.iter().enumerate() {
+            let micros_per_beat = 60_000_000 / *tempo as u64;
+            let remaining_ticks = ((micros - elapsed) * TICKS_PER_BEAT as u64 / micros_per_beat) as u32;
+            match self.changes.get(idx + 1) {
+                Some((next, _)) if start + remaining_ticks >= *next => {
+                    elapsed += TempoMap::ticks_to_micros(next - start, *tempo);
+                },
+                _ => return start + remaining_ticks
+            }
+        }
+        0
+    }
+
+    fn ticks_to_micros(ticks: u32, tempo: u32) -> u64 {
+        ticks as u64 * 60_000_000 / (tempo as u64 * TICKS_PER_BEAT as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Pause,
+    Resume,
+    Seek(u32),
+    Stop
+}
+
+// Events at the same tick are sent in the order they were scheduled.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Scheduled {
+    tick: u32,
+    order: usize,
+    message: Vec<u8>
+}
+
+pub struct Sequencer {
+    tempo_map: TempoMap,
+    events: Vec<Scheduled>
+}
+
+impl Sequencer {
+    pub fn new(tempo_map: TempoMap) -> Sequencer {
+        Sequencer {tempo_map, events: Vec::new()}
+    }
+
+    pub fn schedule(&mut self, tick: u32, message: Vec<u8>) {
+        let order = self.events.len();
+        self.events.push(Scheduled {tick, order, message});
+    }
+
+    // Instrument setup first, then every note of the score.
+    pub fn schedule_score(&mut self, score: &Score) {
+        for message in midi::setup_messages(score) {
+            self.schedule(0, message);
+        }
+        for (tick, message) in midi::score_messages(score) {
+            self.schedule(tick, message);
+        }
+    }
+
+    // Instrument setup, then only the notes starting during the ply, from tick 0.
+    // Notes held into the next ply are released when it ends.
+    pub fn schedule_ply(&mut self, score: &Score, ply: usize) {
+        let (start, end) = match (score.ply_ticks.get(ply), score.ply_ticks.get(ply + 1)) {
+            (Some(start), Some(end)) => (*start, *end),
+            _ => return
+        };
+        for message in midi::setup_messages(score) {
+            self.schedule(0, message);
+        }
+        let mut messages = Vec::new();
+        for voice in score.voices.iter() {
+            for event in voice.notes().filter(|event| event.tick >= start && event.tick < end) {
+                let note = event.note.as_ref().unwrap();
+                messages.push((event.tick - start, midi::note_on(note)));
+                messages.push((event.end().min(end) - start, midi::note_off(note)));
+            }
+        }
+        messages.sort_by_key(|(tick, message)| (*tick, message[0] & 0xF0 == midi::NOTE_ON_MSG));
+        for (tick, message) in messages {
+            self.schedule(tick, message);
+        }
+    }
+
+    // Plays the events on a clock thread, the handle controls it while it runs.
+    pub fn start<S: MidiSink + Send + 'static>(self, sink: S) -> SequencerHandle {
+        let (commands, receiver) = channel::unbounded();
+        let clock = thread::spawn(move || Clock::new(self, sink).run(receiver));
+        SequencerHandle {controller: Controller {commands}, clock}
+    }
+}
+
+// Sends commands to a running sequencer, they are ignored once it has finished.
+#[derive(Debug, Clone)]
+pub struct Controller {
+    commands: Sender<Command>
+}
+
+impl Controller {
+    pub fn pause(&self) {
+        let _ = self.commands.send(Command::Pause);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.commands.send(Command::Resume);
+    }
+
+    pub fn seek(&self, tick: u32) {
+        let _ = self.commands.send(Command::Seek(tick));
+    }
+
+    pub fn stop(&self) {
+        let _ = self.commands.send(Command::Stop);
+    }
+}
+
+pub struct SequencerHandle {
+    controller: Controller,
+    clock: JoinHandle<()>
+}
+
+impl SequencerHandle {
+    pub fn controller(&self) -> Controller {
+        self.controller.clone()
+    }
+
+    // Blocks until the score has played or the sequencer was stopped, fails when the clock thread panicked.
+    pub fn wait(self) -> Result<(), ClockPanicked> {
+        self.clock.join().map_err(|panic| {
+            let reason = panic.downcast_ref::<&str>().map(|reason| reason.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            ClockPanicked(reason)
+        })
+    }
+}
+
+// The clock thread panicked, with the panic's message when it had one.
+#[derive(Debug, PartialEq)]
+pub struct ClockPanicked(pub String);
+
+impl fmt::Display for ClockPanicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The sequencer stopped playing: {}", self.0)
+    }
+}
+
+impl Error for ClockPanicked {}
+
+struct Clock<S: MidiSink> {
+    sequencer: Sequencer,
+    sink: S,
+    queue: BinaryHeap<Reverse<Scheduled>>,
+    // Notes that got a note on without a note off yet, by channel and key
+    sounding: Vec<(u8, u8)>,
+    // Wall clock time and tick playback last (re)started from
+    anchor: (Instant, u32)
+}
+
+impl<S: MidiSink> Clock<S> {
+    fn new(sequencer: Sequencer, sink: S) -> Clock<S> {
+        let queue = sequencer.events.iter().cloned().map(Reverse).collect();
+        Clock {sequencer, sink, queue, sounding: Vec::new(), anchor: (Instant::now(), 0)}
+    }
+
+    fn run(mut self, commands: Receiver<Command>) {
+        let mut paused = false;
+        loop {
+            let command = if paused {
+                commands.recv().unwrap_or(Command::Stop)
+            } else {
+                let due = match self.queue.peek() {
+                    Some(Reverse(event)) => self.due(event.tick),
+                    None => break
+                };
+                let now = Instant::now();
+                if due <= now {
+                    let Reverse(event) = self.queue.pop().unwrap();
+                    self.send(&event.message);
+                    continue;
+                }
+                match commands.recv_timeout(due - now) {
+                    Ok(command) => command,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    // Nobody is left to send commands, just play on
+                    Err(RecvTimeoutError::Disconnected) => {
+                        thread::sleep(due - now);
+                        continue;
+                    }
+                }
+            };
+
+            match command {
+                Command::Pause if !paused => {
+                    self.anchor.1 = self.current_tick();
+                    self.silence();
+                    paused = true;
+                },
+                Command::Resume if paused => {
+                    self.anchor.0 = Instant::now();
+                    paused = false;
+                },
+                Command::Seek(tick) => {
+                    self.silence();
+                    self.seek(tick);
+                },
+                Command::Stop => break,
+                _ => {}
+            }
+        }
+        self.silence();
+    }
+
+    fn due(&self, tick: u32) -> Instant {
+        let tempo_map = &self.sequencer.tempo_map;
+        let micros = tempo_map.micros_at(tick).saturating_sub(tempo_map.micros_at(self.anchor.1));
+        self.anchor.0 + Duration::from_micros(micros)
+    }
+
+    fn current_tick(&self) -> u32 {
+        let tempo_map = &self.sequencer.tempo_map;
+        let elapsed = self.anchor.0.elapsed().as_micros() as u64;
+        tempo_map.tick_at(tempo_map.micros_at(self.anchor.1) + elapsed)
+    }
+
+    // Notes before the tick are skipped, but program and controller changes
+    // are sent so the instruments sound as they would have at that point.
+    fn seek(&mut self, tick: u32) {
+        let mut queue = BinaryHeap::new();
+        let mut setup = Vec::new();
+        for event in self.sequencer.events.iter() {
+            if event.tick >= tick {
+                queue.push(Reverse(event.clone()));
+            } else if !Clock::<S>::is_note(&event.message) {
+                setup.push(event.clone());
+            }
+        }
+        setup.sort();
+        for event in setup {
+            self.send(&event.message);
+        }
+        self.queue = queue;
+        self.anchor = (Instant::now(), tick);
+    }
+
+    fn send(&mut self, message: &[u8]) {
+        if Clock::<S>::is_note(message) {
+            let note = (message[0] & 0x0F, message[1]);
+            self.sounding.retain(|sounding| *sounding != note);
+            if message[0] & 0xF0 == midi::NOTE_ON_MSG && message[2] > 0 {
+                self.sounding.push(note);
+            }
+        }
+        self.sink.send(message);
+    }
+
+    // Releases every sounding note, then all notes off on every channel for
+    // synths that missed a note off.
+    fn silence(&mut self) {
+        for (channel, key) in self.sounding.drain(..).collect::<Vec<(u8, u8)>>() {
+            self.sink.send(&[midi::NOTE_OFF_MSG | channel, key, 0]);
+        }
+        for channel in 0..16 {
+            self.sink.send(&midi::all_notes_off(channel));
+        }
+    }
+
+    fn is_note(message: &[u8]) -> bool {
+        let status = message[0] & 0xF0;
+        status == midi::NOTE_ON_MSG || status == midi::NOTE_OFF_MSG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    #[derive(Clone)]
+    struct Recorder {
+        messages: Arc<Mutex<Vec<Vec<u8>>>>
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder {messages: Arc::new(Mutex::new(Vec::new()))}
+        }
+
+        fn messages(&self) -> Vec<Vec<u8>> {
+            self.messages.lock().unwrap().clone()
+        }
+
+        fn notes(&self) -> Vec<Vec<u8>> {
+            self.messages().into_iter().filter(|message| message[0] & 0xF0 != midi::CONTROL_CHANGE_MSG).collect()
+        }
+    }
+
+    impl MidiSink for Recorder {
+        fn send(&mut self, message: &[u8]) {
+            self.messages.lock().unwrap().push(message.to_vec());
+        }
+    }
+
+    // A port that goes away as soon as something is sent
+    struct Broken;
+
+    impl MidiSink for Broken {
+        fn send(&mut self, _message: &[u8]) {
+            panic!("port disconnected");
+        }
+    }
+
+    // Four quarter notes, each quarter lasting 10ms at 6000 bpm
+    fn sequencer(tempo: u32) -> Sequencer {
+        let mut sequencer = Sequencer::new(TempoMap::constant(tempo));
+        sequencer.schedule(0, vec![midi::PROGRAM_CHANGE_MSG, 40]);
+        for (beat, key) in [60, 62, 64, 65].iter().enumerate() {
+            let tick = beat as u32 * TICKS_PER_BEAT;
+            sequencer.schedule(tick, vec![midi::NOTE_ON_MSG, *key, 100]);
+            sequencer.schedule(tick + TICKS_PER_BEAT, vec![midi::NOTE_OFF_MSG, *key, 64]);
+        }
+        sequencer
+    }
+
+    #[test]
+    fn test_tempo_map() {
+        let mut tempo_map = TempoMap::constant(120);
+        assert_eq!(tempo_map.micros_at(TICKS_PER_BEAT), 500_000);
+        assert_eq!(tempo_map.tick_at(250_000), TICKS_PER_BEAT / 2);
+
+        tempo_map.set_tempo(TICKS_PER_BEAT * 2, 60);
+        assert_eq!(tempo_map.micros_at(TICKS_PER_BEAT * 3), 2_000_000);
+        assert_eq!(tempo_map.tick_at(2_000_000), TICKS_PER_BEAT * 3);
+        assert_eq!(tempo_map.tick_at(500_000), TICKS_PER_BEAT);
+
+        tempo_map.set_tempo(TICKS_PER_BEAT * 2, 240);
+        assert_eq!(tempo_map.micros_at(TICKS_PER_BEAT * 3), 1_250_000);
+    }
+
+    #[test]
+    fn test_plays_in_order() {
+        let recorder = Recorder::new();
+        let started = Instant::now();
+        sequencer(6000).start(recorder.clone()).wait().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(40));
+
+        let notes = recorder.notes();
+        assert_eq!(notes.len(), 9);
+        assert_eq!(notes[0], vec![midi::PROGRAM_CHANGE_MSG, 40]);
+        assert_eq!(notes[1], vec![midi::NOTE_ON_MSG, 60, 100]);
+        // The note off for 60 comes before the note on for 62 at the same tick
+        assert_eq!(notes[2], vec![midi::NOTE_OFF_MSG, 60, 64]);
+        assert_eq!(notes[8], vec![midi::NOTE_OFF_MSG, 65, 64]);
+
+        let messages = recorder.messages();
+        assert_eq!(messages.len(), 9 + 16);
+        assert_eq!(messages.last().unwrap(), &midi::all_notes_off(15));
+    }
+
+    #[test]
+    fn test_wait_reports_a_panicked_clock() {
+        let error = sequencer(6000).start(Broken).wait().unwrap_err();
+        assert_eq!(error, ClockPanicked("port disconnected".to_string()));
+        assert_eq!(error.to_string(), "The sequencer stopped playing: port disconnected");
+    }
+
+    #[test]
+    fn test_stop_releases_sounding_notes() {
+        let recorder = Recorder::new();
+        let handle = sequencer(60).start(recorder.clone());
+        thread::sleep(Duration::from_millis(50));
+        handle.controller().stop();
+        handle.wait().unwrap();
+
+        let notes = recorder.notes();
+        assert_eq!(notes, vec![
+            vec![midi::PROGRAM_CHANGE_MSG, 40],
+            vec![midi::NOTE_ON_MSG, 60, 100],
+            vec![midi::NOTE_OFF_MSG, 60, 0]
+        ]);
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let recorder = Recorder::new();
+        let handle = sequencer(600).start(recorder.clone());
+        let controller = handle.controller();
+        controller.pause();
+        thread::sleep(Duration::from_millis(50));
+        // Paused straight away, the first note was sent at most
+        assert!(recorder.notes().len() <= 3);
+
+        controller.resume();
+        handle.wait().unwrap();
+        assert_eq!(recorder.notes().iter().filter(|message| message[0] == midi::NOTE_ON_MSG).count(), 4);
+    }
+
+    #[test]
+    fn test_seek() {
+        let recorder = Recorder::new();
+        let handle = sequencer(60).start(recorder.clone());
+        handle.controller().seek(TICKS_PER_BEAT * 3);
+        thread::sleep(Duration::from_millis(50));
+        handle.controller().stop();
+        handle.wait().unwrap();
+
+        // The program change is sent again, the notes before the last one are skipped
+        let notes = recorder.notes();
+        assert_eq!(notes.last().unwrap(), &vec![midi::NOTE_OFF_MSG, 65, 0]);
+        assert!(notes.contains(&vec![midi::NOTE_ON_MSG, 65, 100]));
+        assert!(!notes.contains(&vec![midi::NOTE_ON_MSG, 62, 100]));
+        assert!(!notes.contains(&vec![midi::NOTE_ON_MSG, 64, 100]));
+    }
+
+    #[test]
+    fn test_schedule_ply() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "e5", "Nf3"]));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Kknight, true)], &ScoreConfig::default());
+        let mut sequencer = Sequencer::new(TempoMap::constant(100));
+        sequencer.schedule_ply(&score, 2);
+
+        let length = score.ply_ticks[3] - score.ply_ticks[2];
+        assert!(sequencer.events.iter().all(|event| event.tick <= length));
+        let knight = score.voices[1].notes().find(|event| event.tick == score.ply_ticks[2]).unwrap();
+        let note_on = midi::note_on(knight.note.as_ref().unwrap());
+        assert_eq!(sequencer.events.iter().filter(|event| event.message == note_on).count(), 1);
+        let note_ons = sequencer.events.iter().filter(|event| event.message[0] & 0xF0 == midi::NOTE_ON_MSG).count();
+        let note_offs = sequencer.events.iter().filter(|event| event.message[0] & 0xF0 == midi::NOTE_OFF_MSG).count();
+        assert_eq!(note_ons, note_offs);
+
+        let mut past_the_end = Sequencer::new(TempoMap::constant(100));
+        past_the_end.schedule_ply(&score, 3);
+        assert!(past_the_end.events.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use super::midi;
+use super::rhythm::TICKS_PER_BEAT;
+use super::score::{Score, Voice};
+use super::sequencer::TempoMap;
+
+use std::error::Error;
+use std::fs;
+
+const TEMPO_META: u8 = 0x51;
+const TRACK_NAME_META: u8 = 0x03;
+const END_OF_TRACK_META: u8 = 0x2F;
+
+// Standard MIDI file, format 1: a tempo track followed by one track per voice.
+// The tempo is the score's starting tempo, the score's tempo changes are relative to it.
+pub fn to_bytes(score: &Score, tempo: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let tracks = score.voices.len() + 1;
+    bytes.extend(b"MThd");
+    bytes.extend(&6u32.to_be_bytes());
+    bytes.extend(&1u16.to_be_bytes());
+    bytes.extend(&(tracks as u16).to_be_bytes());
+    bytes.extend(&(TICKS_PER_BEAT as u16).to_be_bytes());
+
+    bytes.extend(chunk(&tempo_track(&score.tempo_map(tempo))));
+    for voice in score.voices.iter() {
+        bytes.extend(chunk(&voice_track(voice)));
+    }
+    bytes
+}
+
+pub fn save(score: &Score, tempo: u32, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_bytes(score, tempo))?;
+    Ok(())
+}
+
+fn tempo_track(tempo_map: &TempoMap) -> Vec<u8> {
+    let mut track = Vec::new();
+    let mut last_tick = 0;
+    for (tick, tempo) in tempo_map.changes() {
+        let micros_per_beat = 60_000_000 / tempo.max(&1);
+        write_meta(&mut track, tick - last_tick, TEMPO_META, &micros_per_beat.to_be_bytes()[1..]);
+        last_tick = *tick;
+    }
+    write_meta(&mut track, 0, END_OF_TRACK_META, &[]);
+    track
+}
+
+// The voice's instrument setup at tick 0, then its notes.
+fn voice_track(voice: &Voice) -> Vec<u8> {
+    let mut track = Vec::new();
+    write_meta(&mut track, 0, TRACK_NAME_META, voice.name.as_bytes());
+    for message in midi::instrument_messages(&voice.instrument) {
+        write_event(&mut track, 0, &message);
+    }
+    let mut last_tick = 0;
+    for (tick, message) in midi::voice_messages(&[voice]) {
+        write_event(&mut track, tick - last_tick, &message);
+        last_tick = tick;
+    }
+    write_meta(&mut track, 0, END_OF_TRACK_META, &[]);
+    track
+}
+
+fn chunk(track: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(b"MTrk");
+    bytes.extend(&(track.len() as u32).to_be_bytes());
+    bytes.extend(track);
+    bytes
+}
+
+fn write_event(track: &mut Vec<u8>, delta: u32, message: &[u8]) {
+    write_variable_length(track, delta);
+    track.extend(message);
+}
+
+fn write_meta(track: &mut Vec<u8>, delta: u32, meta_type: u8, data: &[u8]) {
+    write_variable_length(track, delta);
+    track.extend(&[0xFF, meta_type]);
+    write_variable_length(track, data.len() as u32);
+    track.extend(data);
+}
+
+// Seven bits per byte, most significant first, the high bit set on all but the last.
+fn write_variable_length(track: &mut Vec<u8>, value: u32) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    track.extend(groups.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    fn variable_length(value: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_variable_length(&mut bytes, value);
+        bytes
+    }
+
+    #[test]
+    fn test_variable_length() {
+        assert_eq!(variable_length(0), vec![0x00]);
+        assert_eq!(variable_length(0x7F), vec![0x7F]);
+        assert_eq!(variable_length(0x80), vec![0x81, 0x00]);
+        assert_eq!(variable_length(480), vec![0x83, 0x60]);
+        assert_eq!(variable_length(0x0FFF_FFFF), vec![0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "d5", "exd5"]));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Dpawn, false)], &ScoreConfig::default());
+        let bytes = to_bytes(&score, 100);
+
+        assert_eq!(&bytes[0..4], b"MThd");
+        // Format 1, tempo track plus two pieces and percussion, 480 ticks per beat
+        assert_eq!(&bytes[8..14], &[0, 1, 0, 4, 0x01, 0xE0]);
+        assert_eq!(&bytes[14..18], b"MTrk");
+        // 600000 microseconds per beat at 100 bpm
+        assert_eq!(&bytes[22..29], &[0x00, 0xFF, 0x51, 0x03, 0x09, 0x27, 0xC0]);
+        assert_eq!(bytes.windows(4).filter(|window| window == b"MTrk").count(), 4);
+        assert_eq!(&bytes[bytes.len() - 3..], &[0xFF, 0x2F, 0x00]);
+
+        let program_change = [0xC1, 0x00];
+        assert!(bytes.windows(2).any(|window| window == program_change));
+    }
+
+    #[test]
+    fn test_tempo_changes() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "d5"]));
+        let mut score = Score::from_game(&game, &[(PieceName::Epawn, true)], &ScoreConfig::default());
+        score.tempo_changes = vec![(480, 150)];
+        let bytes = to_bytes(&score, 100);
+        // 400000 microseconds per beat a quarter note in
+        let change = [0x83, 0x60, 0xFF, 0x51, 0x03, 0x06, 0x1A, 0x80];
+        assert!(bytes.windows(change.len()).any(|window| window == change));
+    }
+}
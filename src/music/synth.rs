@@ -0,0 +1,161 @@
+use super::Note;
+use super::instrument::Instrument;
+use super::score::Score;
+use super::sequencer::TempoMap;
+
+use std::error::Error;
+use std::f32::consts::PI;
+use std::fs;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+const CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+// Seconds
+const ATTACK: f32 = 0.005;
+const RELEASE: f32 = 0.08;
+const PLUCK_DECAY: f32 = 0.6;
+const DRUM_DECAY: f32 = 0.12;
+// Leaves room for a few voices at once before the mix has to be scaled down
+const NOTE_GAIN: f32 = 0.25;
+
+// A small software synthesizer, enough to hear a score without a MIDI device. Every note is
+// a handful of sine partials shaped by its General MIDI family, drums are decaying noise.
+// Returns interleaved stereo samples between -1 and 1.
+pub fn render(score: &Score, tempo: u32) -> Vec<f32> {
+    let tempo_map = score.tempo_map(tempo);
+    let frames = seconds_to_frames(seconds_at(&tempo_map, score.length()) + RELEASE);
+    let mut samples = vec![0.0; frames * CHANNELS as usize];
+    for voice in score.voices.iter() {
+        for event in voice.notes() {
+            let start = seconds_at(&tempo_map, event.tick);
+            let length = seconds_at(&tempo_map, event.end()) - start;
+            add_note(&mut samples, &voice.instrument, event.note.as_ref().unwrap(), start, length);
+        }
+    }
+    let peak = samples.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    if peak > 1.0 {
+        samples.iter_mut().for_each(|sample| *sample /= peak);
+    }
+    samples
+}
+
+// 16 bit PCM WAVE file.
+pub fn to_bytes(score: &Score, tempo: u32) -> Vec<u8> {
+    let samples = render(score, tempo);
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let data_len = (samples.len() * 2) as u32;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend(b"RIFF");
+    bytes.extend(&(36 + data_len).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(&16u32.to_le_bytes());
+    bytes.extend(&1u16.to_le_bytes());
+    bytes.extend(&CHANNELS.to_le_bytes());
+    bytes.extend(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend(&(SAMPLE_RATE * block_align as u32).to_le_bytes());
+    bytes.extend(&block_align.to_le_bytes());
+    bytes.extend(&BITS_PER_SAMPLE.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend(&((sample * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    bytes
+}
+
+pub fn save(score: &Score, tempo: u32, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_bytes(score, tempo))?;
+    Ok(())
+}
+
+fn seconds_at(tempo_map: &TempoMap, tick: u32) -> f32 {
+    tempo_map.micros_at(tick) as f32 / 1_000_000.0
+}
+
+fn seconds_to_frames(seconds: f32) -> usize {
+    (seconds * SAMPLE_RATE as f32).ceil() as usize
+}
+
+// Relative strength of the first partials, and whether the sound dies away on its own.
+fn timbre(program: u8) -> (&'static [f32], bool) {
+    match program {
+        0..=7 => (&[1.0, 0.5, 0.25, 0.12], true),     // Pianos
+        8..=15 => (&[1.0, 0.0, 0.3, 0.0, 0.1], true), // Chromatic percussion
+        40..=47 => (&[1.0, 0.6, 0.4, 0.3, 0.2], false), // Strings
+        56..=63 => (&[1.0, 0.7, 0.5, 0.3], false),    // Brass
+        72..=79 => (&[1.0, 0.1, 0.05], false),        // Pipes
+        88..=95 => (&[1.0, 0.3, 0.1], false),         // Pads
+        _ => (&[1.0, 0.4, 0.2], false)
+    }
+}
+
+fn add_note(samples: &mut [f32], instrument: &Instrument, note: &Note, start: f32, length: f32) {
+    let gain = NOTE_GAIN * note.velocity.clamp(0, 127) as f32 / 127.0 * instrument.volume as f32 / 127.0;
+    // Equal power panning
+    let angle = instrument.pan as f32 / 127.0 * PI / 2.0;
+    let (left, right) = (angle.cos(), angle.sin());
+    let frequency = 440.0 * 2f32.powf((note.as_midi() as f32 - 69.0) / 12.0);
+    let (partials, plucked) = timbre(instrument.program);
+    let mut noise: u32 = 0x1234_5678 ^ note.as_midi() as u32;
+
+    let first = seconds_to_frames(start);
+    let frames = seconds_to_frames(length + RELEASE);
+    for frame in 0..frames {
+        let idx = (first + frame) * CHANNELS as usize;
+        if idx + 1 >= samples.len() {
+            break;
+        }
+        let time = frame as f32 / SAMPLE_RATE as f32;
+        let envelope = (time / ATTACK).min(1.0) * if time > length {(1.0 - (time - length) / RELEASE).max(0.0)} else {1.0};
+        let value = if instrument.is_percussion() {
+            noise ^= noise << 13;
+            noise ^= noise >> 17;
+            noise ^= noise << 5;
+            let hit = noise as f32 / u32::MAX as f32 * 2.0 - 1.0;
+            hit * (-time / DRUM_DECAY).exp()
+        } else {
+            let decay = if plucked {(-time / PLUCK_DECAY).exp()} else {1.0};
+            let tone: f32 = partials.iter().enumerate()
+                .map(|(partial, strength)| strength * (2.0 * PI * frequency * (partial + 1) as f32 * time).sin())
+                .sum();
+            tone * decay
+        };
+        samples[idx] += value * envelope * gain * left;
+        samples[idx + 1] += value * envelope * gain * right;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    #[test]
+    fn test_wav_header() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "e5"]));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Epawn, false)], &ScoreConfig::default());
+        let bytes = to_bytes(&score, 120);
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]), SAMPLE_RATE);
+        assert_eq!(&bytes[36..40], b"data");
+        let data_len = u32::from_le_bytes([bytes[40], bytes[41], bytes[42], bytes[43]]) as usize;
+        assert_eq!(bytes.len(), 44 + data_len);
+        assert_eq!(u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize, bytes.len() - 8);
+    }
+
+    #[test]
+    fn test_render() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "e5", "Nf3"]));
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Kknight, true)], &ScoreConfig::default());
+        let samples = render(&score, 120);
+        let seconds = score.tempo_map(120).micros_at(score.length()) as f32 / 1_000_000.0;
+        assert_eq!(samples.len(), seconds_to_frames(seconds + RELEASE) * 2);
+        assert!(samples.iter().any(|sample| sample.abs() > 0.01));
+        assert!(samples.iter().all(|sample| sample.abs() <= 1.0));
+        // White sits on the left
+        let (left, right) = samples.chunks(2).fold((0.0, 0.0), |(left, right), frame| (left + frame[0].abs(), right + frame[1].abs()));
+        assert!(left > right);
+    }
+}
@@ -0,0 +1,259 @@
+use super::Key;
+use super::instrument::Instrument;
+use super::rhythm::{SIXTEENTH, QUARTER};
+use super::score::{Score, Voice, Event};
+use super::super::chess::types::Role;
+
+// SplitMix64's increment, also spreads the seeds of different voices apart
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+const GRACE_NOTE: u32 = SIXTEENTH / 2;
+const GRACE_SOFTER: i32 = 20;
+const ARPEGGIO_STEP: u32 = SIXTEENTH / 2;
+
+// SplitMix64, small and the same on every platform, so a seed always renders the same music.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng {state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(GOLDEN_GAMMA);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Inclusive on both ends
+    pub fn range(&mut self, low: i32, high: i32) -> i32 {
+        let span = (high - low + 1) as u64;
+        low + (self.next_u64() % span) as i32
+    }
+
+    pub fn percent(&mut self, chance: u32) -> bool {
+        (self.next_u64() % 100) < chance as u64
+    }
+
+    pub fn pick<'a, T>(&mut self, choices: &'a [T]) -> &'a T {
+        &choices[(self.next_u64() % choices.len() as u64) as usize]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Variation {
+    pub seed: u64,
+    // Percent of notes of a quarter or longer that get a grace note from above
+    pub ornament_chance: u32,
+    // Notes start up to this many ticks late
+    pub humanize_ticks: u32,
+    pub humanize_velocity: i32,
+    // Harmony chords are rolled up or down instead of struck at once
+    pub arpeggiate: bool,
+    // Piece voices pick their program from a family of similar instruments
+    pub vary_instruments: bool
+}
+
+impl Variation {
+    pub fn new(seed: u64) -> Variation {
+        Variation {
+            seed,
+            ornament_chance: 15,
+            humanize_ticks: 20,
+            humanize_velocity: 8,
+            arpeggiate: true,
+            vary_instruments: true
+        }
+    }
+
+    // Voices get their own generator so changing one voice doesn't change the others.
+    pub fn apply(&self, score: &mut Score, key: &Key) {
+        if self.arpeggiate {
+            self.arpeggiate_harmony(score);
+        }
+        for (idx, voice) in score.voices.iter_mut().enumerate() {
+            let mut rng = Rng::new(self.seed.wrapping_add(GOLDEN_GAMMA.wrapping_mul(idx as u64 + 1)));
+            if voice.piece.is_some() {
+                if let Some(role) = voice.role.filter(|_| self.vary_instruments) {
+                    voice.instrument.program = self.program_for(role, voice.instrument.channel);
+                }
+                self.ornament(voice, key, &mut rng);
+            }
+            self.humanize(voice, &mut rng);
+        }
+    }
+
+    // Picked by channel rather than by voice, so voices sharing a channel agree on its program.
+    fn program_for(&self, role: Role, channel: u8) -> u8 {
+        let mut rng = Rng::new(self.seed.wrapping_sub(GOLDEN_GAMMA.wrapping_mul(channel as u64 + 1)));
+        *rng.pick(Variation::instrument_family(role))
+    }
+
+    // General MIDI programs close to the one Instrument::for_piece gives the role.
+    fn instrument_family(role: Role) -> &'static [u8] {
+        match role {
+            Role::Pawn => &[0, 2, 4, 8],
+            Role::Knight => &[11, 12, 13, 9],
+            Role::Bishop => &[73, 72, 74, 71],
+            Role::Rook => &[42, 43, 32, 58],
+            Role::Queen => &[40, 41, 68, 48],
+            Role::King => &[60, 56, 57, 61]
+        }
+    }
+
+    fn ornament(&self, voice: &mut Voice, key: &Key, rng: &mut Rng) {
+        let mut events = Vec::with_capacity(voice.events.len());
+        for event in voice.events.drain(..) {
+            let note = match event.note {
+                Some(note) if event.duration >= QUARTER && rng.percent(self.ornament_chance) => note,
+                _ => {
+                    events.push(event);
+                    continue;
+                }
+            };
+            let mut grace = note;
+            grace.base_midi = key.degree_to_midi(key.nearest_degree(note.pitch()) + 1);
+            grace.adjustment = 0;
+            grace.duration = GRACE_NOTE;
+            grace.velocity = (note.velocity - GRACE_SOFTER).max(1);
+            events.push(Event {ply: event.ply, tick: event.tick, duration: GRACE_NOTE, note: Some(grace)});
+
+            let mut main = note;
+            main.duration = event.duration - GRACE_NOTE;
+            events.push(Event {ply: event.ply, tick: event.tick + GRACE_NOTE, duration: main.duration, note: Some(main)});
+        }
+        voice.events = events;
+    }
+
+    fn humanize(&self, voice: &mut Voice, rng: &mut Rng) {
+        for idx in 0..voice.events.len() {
+            let event = &mut voice.events[idx];
+            let note = match event.note.as_mut() {
+                Some(note) => note,
+                None => continue
+            };
+            note.velocity = (note.velocity + rng.range(-self.humanize_velocity, self.humanize_velocity)).clamp(1, 127);
+            let delay = rng.range(0, self.humanize_ticks.min(event.duration / 4) as i32) as u32;
+            Variation::delay(&mut voice.events, idx, delay);
+        }
+    }
+
+    // Rolls the chords of the pad, each chord picking its direction.
+    fn arpeggiate_harmony(&self, score: &mut Score) {
+        let mut rng = Rng::new(self.seed);
+        let mut pad_voices: Vec<&mut Voice> = score.voices.iter_mut()
+            .filter(|voice| voice.instrument == Instrument::pad())
+            .collect();
+        let chord_ticks: Vec<u32> = match pad_voices.first() {
+            Some(voice) => voice.notes().map(|event| event.tick).collect(),
+            None => return
+        };
+        for tick in chord_ticks {
+            let upwards = rng.percent(50);
+            let count = pad_voices.len() as u32;
+            for (position, voice) in pad_voices.iter_mut().enumerate() {
+                if let Some(idx) = voice.events.iter().position(|event| event.tick == tick && event.note.is_some()) {
+                    let step = if upwards {position as u32} else {count - 1 - position as u32};
+                    let delay = (step * ARPEGGIO_STEP).min(voice.events[idx].duration / 2);
+                    Variation::delay(&mut voice.events, idx, delay);
+                }
+            }
+        }
+    }
+
+    // Starts the event later, the event before it takes up the gap so the voice stays contiguous.
+    fn delay(events: &mut [Event], idx: usize, delay: u32) {
+        if idx == 0 || delay == 0 || delay >= events[idx].duration {
+            return;
+        }
+        events[idx - 1].duration += delay;
+        if let Some(note) = events[idx - 1].note.as_mut() {
+            note.duration += delay;
+        }
+        let event = &mut events[idx];
+        event.tick += delay;
+        event.duration -= delay;
+        if let Some(note) = event.note.as_mut() {
+            note.duration -= delay;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "smf")]
+    use super::super::smf;
+    use super::super::score::ScoreConfig;
+    use super::super::super::chess::{Game, Move, PieceName};
+
+    #[cfg(feature = "smf")]
+    fn render(variation: Option<Variation>) -> Vec<u8> {
+        let moves = ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O", "f6"];
+        let game = Game::new_with_moves(&Move::parse_moves(&moves));
+        let config = ScoreConfig {harmony: true, variation, ..ScoreConfig::default()};
+        let pieces = [(PieceName::Epawn, true), (PieceName::Kbishop, true), (PieceName::Qknight, false), (PieceName::Dpawn, false)];
+        smf::to_bytes(&Score::from_game(&game, &pieces, &config), 100)
+    }
+
+    #[test]
+    fn test_rng() {
+        let mut first = Rng::new(42);
+        let mut second = Rng::new(42);
+        let values: Vec<u64> = (0..5).map(|_| first.next_u64()).collect();
+        assert_eq!(values, (0..5).map(|_| second.next_u64()).collect::<Vec<u64>>());
+        assert_ne!(values[0], Rng::new(43).next_u64());
+        // SplitMix64 reference output for seed 0
+        assert_eq!(Rng::new(0).next_u64(), 0xE220_A839_7B1D_CDAF);
+
+        let mut rng = Rng::new(7);
+        assert!((0..1000).map(|_| rng.range(-3, 3)).all(|value| (-3..=3).contains(&value)));
+    }
+
+    #[cfg(feature = "smf")]
+    #[test]
+    fn test_same_seed_same_bytes() {
+        assert_eq!(render(Some(Variation::new(7))), render(Some(Variation::new(7))));
+        assert_ne!(render(Some(Variation::new(7))), render(Some(Variation::new(8))));
+        assert_ne!(render(Some(Variation::new(7))), render(None));
+    }
+
+    #[test]
+    fn test_voices_sharing_a_channel_share_a_program() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "e5", "d4", "d5"]));
+        let pieces: Vec<(PieceName, bool)> = PieceName::ALL.iter().map(|name| (*name, true)).collect();
+        for seed in 0..20 {
+            let config = ScoreConfig {variation: Some(Variation::new(seed)), percussion: false, ..ScoreConfig::default()};
+            let score = Score::from_game(&game, &pieces, &config);
+            for voice in score.voices.iter() {
+                assert!(score.voices.iter()
+                    .filter(|other| other.instrument.channel == voice.instrument.channel)
+                    .all(|other| other.instrument.program == voice.instrument.program), "seed {}", seed);
+                if let Some(role) = voice.role {
+                    assert!(Variation::instrument_family(role).contains(&voice.instrument.program), "{}", voice.name);
+                }
+            }
+            assert_eq!(score.voices.iter().filter(|voice| voice.role == Some(Role::Knight)).count(), 2);
+        }
+    }
+
+    #[test]
+    fn test_voices_stay_contiguous() {
+        let game = Game::new_with_moves(&Move::parse_moves(&["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]));
+        let mut variation = Variation::new(3);
+        variation.ornament_chance = 100;
+        let config = ScoreConfig {harmony: true, variation: Some(variation), ..ScoreConfig::default()};
+        let score = Score::from_game(&game, &[(PieceName::Epawn, true), (PieceName::Kknight, true)], &config);
+        for voice in score.voices.iter() {
+            assert!(voice.events.windows(2).all(|pair| pair[0].end() == pair[1].tick), "{}", voice.name);
+            assert_eq!(voice.events.last().unwrap().end(), score.length(), "{}", voice.name);
+            assert!(voice.notes().all(|event| event.note.unwrap().duration == event.duration));
+        }
+        // The pawn's only move got a grace note
+        assert_eq!(score.voices[0].notes().count(), 2);
+    }
+}
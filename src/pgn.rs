@@ -1,41 +1,158 @@
-pub fn parse_moves(game: &str) -> Vec<&str> {
-    let lines = game.lines();
+use super::chess::EngineEval;
 
-    // Assumed format for moves_line 1. d4 Nf6 2. Bf4 Nc6 3. e3 d5 etc...
-    let moves_line = lines.filter(|&line| (*line).starts_with("1")).next().unwrap(); 
+use std::time::Duration;
 
-    let re = regex::Regex::new(r"\d+\. ").unwrap();
-    let mut split = re.split(moves_line).collect::<Vec<_>>();
+enum Token<'a> {
+    Move(&'a str),
+    Comment(&'a str)
+}
 
-    // The first item is always an empty string, because the game line starts with the split regex
-    split.remove(0);
+// Splits the movetext into SAN moves and {comments}, skipping tag pairs,
+// move numbers (1. and 1...) and the game result.
+fn tokenize(game: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut word_start: Option<usize> = None;
+    let mut comment_start: Option<usize> = None;
+    let mut in_tag = false;
+    let mut line_start = true;
+    for (idx, c) in game.char_indices() {
+        if let Some(begin) = comment_start {
+            if c == '}' {
+                tokens.push(Token::Comment(game[begin..idx].trim()));
+                comment_start = None;
+            }
+        } else if in_tag {
+            in_tag = c != '\n';
+        } else if line_start && c == '[' {
+            in_tag = true;
+        } else if c == '{' || c.is_whitespace() {
+            if let Some(begin) = word_start.take() {
+                push_move_token(&game[begin..idx], &mut tokens);
+            }
+            if c == '{' {
+                comment_start = Some(idx + 1);
+            }
+        } else if word_start.is_none() {
+            word_start = Some(idx);
+        }
+        line_start = c == '\n';
+    }
+    if let Some(begin) = word_start {
+        push_move_token(&game[begin..], &mut tokens);
+    }
+    tokens
+}
 
-    let move_pairs = split.iter()
-        .map(|the_move| parse_move_pair(the_move))
-        .collect::<Vec<(&str, Option<&str>)>>();
+fn push_move_token<'a>(word: &'a str, tokens: &mut Vec<Token<'a>>) {
+    // Move numbers may be attached to the move, as in "1.e4"
+    let the_move = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let is_result = ["1-0", "0-1", "1/2-1/2", "*"].contains(&word);
+    if !the_move.is_empty() && !is_result && !the_move.starts_with('$') {
+        tokens.push(Token::Move(the_move));
+    }
+}
+
+pub fn parse_moves(game: &str) -> Vec<&str> {
+    tokenize(game).iter().filter_map(|token| match token {
+        Token::Move(the_move) => Some(*the_move),
+        Token::Comment(_) => None
+    }).collect()
+}
 
-    let mut moves = Vec::new();
-    for (white_move, black_move_opt) in move_pairs.iter() {
-        moves.push(*white_move);
-        if let Some(black_move) = black_move_opt {
-            moves.push(*black_move);
-        } else {
-            break;
+// One entry per ply with the clock left after the move, from Lichess' [%clk 0:09:58] comments
+pub fn parse_clocks(game: &str) -> Vec<Option<Duration>> {
+    let re = regex::Regex::new(r"\[%clk (\d+):(\d+):(\d+(?:\.\d+)?)\]").unwrap();
+    let mut clocks: Vec<Option<Duration>> = Vec::new();
+    for token in tokenize(game) {
+        match token {
+            Token::Move(_) => clocks.push(None),
+            Token::Comment(comment) => {
+                if let (Some(caps), Some(clock)) = (re.captures(comment), clocks.last_mut()) {
+                    let hours = caps[1].parse::<f64>().unwrap();
+                    let minutes = caps[2].parse::<f64>().unwrap();
+                    let seconds = caps[3].parse::<f64>().unwrap();
+                    *clock = Some(Duration::from_secs_f64(hours * 3600.0 + minutes * 60.0 + seconds));
+                }
+            }
         }
     }
-    assert!(moves.len() == move_pairs.len() * 2 || moves.len() == move_pairs.len() * 2 - 1);
-    moves
+    clocks
 }
 
-fn parse_move_pair(move_str: &str) -> (&str, Option<&str>) {
-    let moves = move_str.split(" ").collect::<Vec<&str>>();
-    let white_move: &str = moves[0].trim();
-    let black_move: Option<&str> = match moves.len() {
-        2 => None,
-        3 => Some(moves[1].trim()),
-        _ => panic!("Did not expect to get here")
-    };
-    (white_move, black_move)
+// One entry per ply with the engine evaluation after the move, from Lichess' [%eval 0.17]
+// and [%eval #-3] comments. Pawns become centipawns.
+pub fn parse_evals(game: &str) -> Vec<Option<EngineEval>> {
+    let re = regex::Regex::new(r"\[%eval (#)?(-?\d+(?:\.\d+)?)").unwrap();
+    let mut evals: Vec<Option<EngineEval>> = Vec::new();
+    for token in tokenize(game) {
+        match token {
+            Token::Move(_) => evals.push(None),
+            Token::Comment(comment) => {
+                if let (Some(caps), Some(eval)) = (re.captures(comment), evals.last_mut()) {
+                    let value = caps[2].parse::<f64>().unwrap();
+                    *eval = Some(match caps.get(1) {
+                        Some(_) => EngineEval::Mate(value as i32),
+                        None => EngineEval::Centipawns((value * 100.0).round() as i32)
+                    });
+                }
+            }
+        }
+    }
+    evals
+}
+
+// TimeControl tags look like [TimeControl "600+5"], correspondence games use "-"
+pub fn parse_time_control(game: &str) -> Option<(Duration, Duration)> {
+    let mut split = parse_tag(game, "TimeControl")?.split('+');
+    let base = split.next()?.parse::<u64>().ok()?;
+    let increment = split.next().map_or(Some(0), |inc| inc.parse::<u64>().ok())?;
+    Some((Duration::from_secs(base), Duration::from_secs(increment)))
+}
+
+// Time each ply spent on the clock, derived from consecutive clocks of the same side.
+pub fn parse_think_times(game: &str) -> Vec<Option<Duration>> {
+    let clocks = parse_clocks(game);
+    let time_control = parse_time_control(game);
+    let increment = time_control.map_or(Duration::from_secs(0), |(_, inc)| inc);
+    (0..clocks.len()).map(|ply| {
+        let previous = if ply >= 2 {clocks[ply - 2]} else {time_control.map(|(base, _)| base)};
+        match (previous, clocks[ply]) {
+            (Some(previous), Some(current)) => Some((previous + increment).checked_sub(current).unwrap_or_default()),
+            _ => None
+        }
+    }).collect()
+}
+
+// Tag pairs look like [ECO "B01"]
+pub fn parse_tag<'a>(game: &'a str, tag_name: &str) -> Option<&'a str> {
+    let prefix = format!("[{} \"", tag_name);
+    game.lines()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with(&prefix))
+        .and_then(|line| line[prefix.len()..].split('"').next())
+}
+
+// The games of a multi-game PGN, each with its tags. A game ends where tags follow its moves.
+pub fn split_games(text: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let (mut start, mut offset) = (0, 0);
+    let mut in_moves = false;
+    for line in text.split_inclusive('\n') {
+        let line_start = line.trim_start();
+        if line_start.starts_with('[') && in_moves {
+            games.push(text[start..offset].trim());
+            start = offset;
+            in_moves = false;
+        } else if !line_start.is_empty() && !line_start.starts_with('[') {
+            in_moves = true;
+        }
+        offset += line.len();
+    }
+    let last = text[start..].trim();
+    if !last.is_empty() {
+        games.push(last);
+    }
+    games
 }
 
 #[cfg(test)]
@@ -57,18 +174,66 @@ mod tests {
 
     #[test]
     fn test_parse_move_with_both_sides() {
-        let (white_move, black_move) = parse_move_pair("Bg2 Qxg2# 0-1");
+        let moves = parse_moves("28. Bg2 Qxg2# 0-1");
 
-        assert_eq!(white_move, "Bg2");
-        assert_eq!(black_move.unwrap(), "Qxg2#");
+        assert_eq!(moves, vec!["Bg2", "Qxg2#"]);
     }
 
     #[test]
     fn test_parse_move_with_one_side() {
-        let (white_move, black_move) = parse_move_pair("Qxc4 1-0");
+        let moves = parse_moves("19. Qxc4 1-0");
 
-        assert_eq!(white_move, "Qxc4");
-        assert!(black_move.is_none());
+        assert_eq!(moves, vec!["Qxc4"]);
+    }
+
+    #[test]
+    fn test_parse_moves_with_clock_comments() {
+        let moves_str = "[TimeControl \"600+5\"]\n\n1. e4 { [%clk 0:10:00] } 1... c5 { [%clk 0:10:05] } 2. Nf3 { [%clk 0:09:58] } 2... d6 1-0";
+        let moves = parse_moves(moves_str);
+        assert_eq!(moves, vec!["e4", "c5", "Nf3", "d6"]);
+
+        let clocks = parse_clocks(moves_str);
+        assert_eq!(clocks, vec![
+            Some(Duration::from_secs(600)),
+            Some(Duration::from_secs(605)),
+            Some(Duration::from_secs(598)),
+            None
+        ]);
+    }
+
+    #[test]
+    fn test_parse_moves_across_lines() {
+        let moves = parse_moves("[Event \"?\"]\n\n1.d4 Nf6 2. Bf4\nNc6 { a\ncomment } 3. e3 *");
+        assert_eq!(moves, vec!["d4", "Nf6", "Bf4", "Nc6", "e3"]);
+    }
+
+    #[test]
+    fn test_parse_time_control() {
+        assert_eq!(parse_time_control("[TimeControl \"600+5\"]"), Some((Duration::from_secs(600), Duration::from_secs(5))));
+        assert_eq!(parse_time_control("[TimeControl \"300\"]"), Some((Duration::from_secs(300), Duration::from_secs(0))));
+        assert_eq!(parse_time_control("[TimeControl \"-\"]"), None);
+    }
+
+    #[test]
+    fn test_parse_think_times() {
+        let game_str = "[TimeControl \"600+5\"]\n\n1. e4 { [%clk 0:10:00] } 1... c5 { [%clk 0:09:55] } 2. Nf3 { [%clk 0:09:58.5] } 2... d6 { [%clk 0:09:20] } 3. d4 1-0";
+        let think_times = parse_think_times(game_str);
+        assert_eq!(think_times, vec![
+            Some(Duration::from_secs(5)),
+            Some(Duration::from_secs(10)),
+            Some(Duration::from_millis(6500)),
+            Some(Duration::from_secs(40)),
+            None
+        ]);
+    }
+
+    #[test]
+    fn test_parse_tag() {
+        let game_str = "[Result \"0-1\"]\n[ECO \"A45\"]\n[Opening \"Indian Game\"]\n\n1. d4 Nf6 0-1";
+        assert_eq!(parse_tag(game_str, "ECO"), Some("A45"));
+        assert_eq!(parse_tag(game_str, "Opening"), Some("Indian Game"));
+        assert_eq!(parse_tag(game_str, "Result"), Some("0-1"));
+        assert_eq!(parse_tag(game_str, "White"), None);
     }
 
     #[test]
@@ -130,4 +295,30 @@ mod tests {
         let last_move = str_moves[36];
         assert_eq!(last_move, "Qxc4");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_split_games() {
+        let text = "[Event \"First\"]\n[Result \"1-0\"]\n\n1. e4 e5\n2. Qh5 1-0\n\n[Event \"Second\"]\r\n\r\n1. d4 d5 *\n";
+        let games = split_games(text);
+        assert_eq!(games.len(), 2);
+        assert_eq!(parse_tag(games[0], "Event"), Some("First"));
+        assert_eq!(parse_moves(games[0]), vec!["e4", "e5", "Qh5"]);
+        assert_eq!(parse_moves(games[1]), vec!["d4", "d5"]);
+
+        assert_eq!(split_games("1. e4 *"), vec!["1. e4 *"]);
+        assert!(split_games("\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_parse_evals() {
+        let game = "1. e4 { [%eval 0.17] [%clk 0:03:00] } 1... e5 { [%eval -0.3,25] } 2. Qh5 2... Ke7 { [%eval #4] } 3. Qxe5+ { [%eval #-1] } 1-0";
+        assert_eq!(parse_evals(game), vec![
+            Some(EngineEval::Centipawns(17)),
+            Some(EngineEval::Centipawns(-30)),
+            None,
+            Some(EngineEval::Mate(4)),
+            Some(EngineEval::Mate(-1))
+        ]);
+        assert_eq!(parse_clocks(game)[0], Some(Duration::from_secs(180)));
+    }
+}
@@ -0,0 +1,242 @@
+// Pictures of games: the paths pieces took across the board and board diagrams after a ply.
+// A picture is a list of shapes, svg and png draw the same shapes.
+pub mod svg;
+pub mod png;
+
+use super::chess::{Cell, Game, PieceName};
+use super::chess::types::Role;
+use super::chess::render::Position;
+use super::music::Score;
+
+use std::error::Error;
+
+pub const SQUARE: f32 = 64.0;
+// Room around the board for the coordinates
+pub const MARGIN: f32 = 24.0;
+const LEGEND_ROW: f32 = 22.0;
+const LEGEND_COLUMNS: usize = 4;
+
+const LIGHT: Color = Color(240, 217, 181);
+const DARK: Color = Color(181, 136, 99);
+const HIGHLIGHT: Color = Color(205, 210, 106);
+const INK: Color = Color(40, 40, 40);
+const PAPER: Color = Color(255, 255, 255);
+// One hue per piece of a side, black's paths are drawn darker
+const PALETTE: [Color; 16] = [
+    Color(230, 25, 75), Color(60, 180, 75), Color(255, 225, 25), Color(0, 130, 200),
+    Color(245, 130, 48), Color(145, 30, 180), Color(70, 240, 240), Color(240, 50, 230),
+    Color(60, 60, 200), Color(0, 0, 128), Color(0, 128, 128), Color(102, 0, 204),
+    Color(170, 110, 40), Color(128, 128, 0), Color(128, 0, 0), Color(0, 100, 0)
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    fn darker(&self) -> Color {
+        Color(self.0 / 2, self.1 / 2, self.2 / 2)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rect {x: f32, y: f32, width: f32, height: f32, color: Color},
+    // Joined line segments through the points
+    Path {points: Vec<(f32, f32)>, width: f32, color: Color},
+    Circle {x: f32, y: f32, radius: f32, fill: Option<Color>, stroke: Option<Color>},
+    // Centered on x, y is the baseline
+    Text {x: f32, y: f32, size: f32, text: String, color: Color},
+    // Centered on x and y
+    Piece {x: f32, y: f32, size: f32, role: Role, white: bool}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Picture {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub shapes: Vec<Shape>
+}
+
+// What a picture is saved as, picked by the file extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Svg,
+    Png
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &str) -> Option<ImageFormat> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "svg" => Some(ImageFormat::Svg),
+            "png" => Some(ImageFormat::Png),
+            _ => None
+        }
+    }
+}
+
+impl Picture {
+    // Each piece's journey as a colored path from its first cell, a dot where it stopped,
+    // a ring where it captured and, with a score, the pitch its voice played on arrival.
+    pub fn paths(game: &Game, pieces: &[(PieceName, bool)], score: Option<&Score>) -> Picture {
+        let board_size = 8.0 * SQUARE + 2.0 * MARGIN;
+        let legend_rows = pieces.len().div_ceil(LEGEND_COLUMNS);
+        let mut picture = Picture::new(board_size, board_size + legend_rows as f32 * LEGEND_ROW, "Piece paths");
+        picture.board(&[]);
+
+        for (idx, (name, white)) in pieces.iter().enumerate() {
+            let color = piece_color(*name, *white);
+            // Paths through the same squares are kept apart
+            let offset = (idx % 5) as f32 * 4.0 - 8.0;
+            let at = |cell: &Cell| {
+                let (x, y) = center(cell);
+                (x + offset, y + offset)
+            };
+            let piece = game.board.get_piece_with_name(*name, *white);
+            let voice = score.and_then(|score| score.voices.iter().find(|voice| voice.piece == Some((*name, *white))));
+
+            let mut stops = vec![(piece.first_cell(), false, None)];
+            for (ply, (cell, capture)) in piece.get_cell_and_capture_history().iter().enumerate() {
+                if *cell == stops.last().unwrap().0 {
+                    continue;
+                }
+                let pitch = voice.and_then(|voice| voice.notes().find(|event| event.ply == ply))
+                    .map(|event| event.note.as_ref().unwrap().name());
+                stops.push((*cell, *capture, pitch));
+            }
+
+            picture.shapes.push(Shape::Path {points: stops.iter().map(|(cell, _, _)| at(cell)).collect(), width: 4.0, color});
+            let (x, y) = at(&stops[0].0);
+            picture.shapes.push(Shape::Circle {x, y, radius: 6.0, fill: Some(PAPER), stroke: Some(color)});
+            for (cell, capture, pitch) in stops.iter().skip(1) {
+                let (x, y) = at(cell);
+                picture.shapes.push(Shape::Circle {x, y, radius: 5.0, fill: Some(color), stroke: None});
+                if *capture {
+                    picture.shapes.push(Shape::Circle {x, y, radius: 10.0, fill: None, stroke: Some(color)});
+                }
+                if let Some(pitch) = pitch {
+                    picture.shapes.push(Shape::Text {x: x + 14.0, y: y - 8.0, size: 10.0, text: pitch.clone(), color: INK});
+                }
+            }
+
+            let x = MARGIN + (idx % LEGEND_COLUMNS) as f32 * (8.0 * SQUARE / LEGEND_COLUMNS as f32);
+            let y = board_size + (idx / LEGEND_COLUMNS) as f32 * LEGEND_ROW;
+            let side = if *white {"White"} else {"Black"};
+            let label = format!("{} {:?}", side, name);
+            picture.shapes.push(Shape::Rect {x, y: y + 4.0, width: 12.0, height: 12.0, color});
+            picture.shapes.push(Shape::Text {x: x + 20.0 + label.len() as f32 * 3.0, y: y + 15.0, size: 10.0, text: label, color: INK});
+        }
+        picture
+    }
+
+    // The board after a number of plies with the last move highlighted.
+    pub fn position(game: &Game, plies: usize) -> Picture {
+        let size = 8.0 * SQUARE + 2.0 * MARGIN;
+        let position = Position::after(game, plies);
+        let mut picture = Picture::new(size, size, &format!("Position after ply {}", position.plies));
+        let highlight: Vec<Cell> = position.last_move.iter().flat_map(|(from, to)| vec![*from, *to]).collect();
+        picture.board(&highlight);
+        for (cell, role, white) in position.pieces.iter() {
+            let (x, y) = center(cell);
+            picture.shapes.push(Shape::Piece {x, y, size: SQUARE * 0.8, role: *role, white: *white});
+        }
+        picture
+    }
+
+    fn new(width: f32, height: f32, title: &str) -> Picture {
+        let shapes = vec![Shape::Rect {x: 0.0, y: 0.0, width, height, color: PAPER}];
+        Picture {width: width as u32, height: height as u32, title: title.to_string(), shapes}
+    }
+
+    // Squares with white at the bottom, and the files and rows around them.
+    fn board(&mut self, highlight: &[Cell]) {
+        for (idx, file) in ['a', 'b', 'c', 'd', 'e', 'f', 'g', 'h'].iter().enumerate() {
+            for row in 1..=8 {
+                let cell = Cell {file: *file, row};
+                let color = if highlight.contains(&cell) {HIGHLIGHT} else if (idx as i32 + row) % 2 == 0 {LIGHT} else {DARK};
+                let (x, y) = center(&cell);
+                self.shapes.push(Shape::Rect {x: x - SQUARE / 2.0, y: y - SQUARE / 2.0, width: SQUARE, height: SQUARE, color});
+            }
+            let x = MARGIN + (idx as f32 + 0.5) * SQUARE;
+            self.shapes.push(Shape::Text {x, y: MARGIN - 7.0, size: 12.0, text: file.to_string(), color: INK});
+            self.shapes.push(Shape::Text {x, y: MARGIN + 8.0 * SQUARE + 17.0, size: 12.0, text: file.to_string(), color: INK});
+        }
+        for row in 1..=8 {
+            let y = MARGIN + (8.5 - row as f32) * SQUARE + 5.0;
+            self.shapes.push(Shape::Text {x: MARGIN / 2.0, y, size: 12.0, text: row.to_string(), color: INK});
+            self.shapes.push(Shape::Text {x: MARGIN * 1.5 + 8.0 * SQUARE, y, size: 12.0, text: row.to_string(), color: INK});
+        }
+    }
+
+    pub fn save(&self, format: ImageFormat, path: &str) -> Result<(), Box<dyn Error>> {
+        match format {
+            ImageFormat::Svg => svg::save(self, path),
+            ImageFormat::Png => png::save(self, path)
+        }
+    }
+}
+
+fn center(cell: &Cell) -> (f32, f32) {
+    let file = (cell.file as u8 - b'a') as f32;
+    (MARGIN + (file + 0.5) * SQUARE, MARGIN + (8.5 - cell.row as f32) * SQUARE)
+}
+
+pub fn piece_color(name: PieceName, white: bool) -> Color {
+    let color = PALETTE[PieceName::ALL.iter().position(|piece| *piece == name).unwrap()];
+    if white {color} else {color.darker()}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::chess::Move;
+    use super::super::music::score::ScoreConfig;
+
+    fn game() -> Game {
+        Game::new_with_moves(&Move::parse_moves(&["e4", "d5", "exd5", "Qxd5", "Nc3"]))
+    }
+
+    #[test]
+    fn test_paths() {
+        let game = game();
+        let pieces = [(PieceName::Epawn, true), (PieceName::Queen, false)];
+        let score = Score::from_game(&game, &pieces, &ScoreConfig::default());
+        let picture = Picture::paths(&game, &pieces, Some(&score));
+        let paths: Vec<&Vec<(f32, f32)>> = picture.shapes.iter().filter_map(|shape| match shape {
+            Shape::Path {points, ..} => Some(points),
+            _ => None
+        }).collect();
+        // e2 e4 d5 for the pawn, d8 d5 for the queen
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].len(), 3);
+        assert_eq!(paths[1].len(), 2);
+        // Both captured, so both get a ring
+        let rings = picture.shapes.iter().filter(|shape| matches!(shape, Shape::Circle {fill: None, ..})).count();
+        assert_eq!(rings, 2);
+        let labels = picture.shapes.iter().filter(|shape| matches!(shape, Shape::Text {size, ..} if *size == 10.0)).count();
+        // A pitch per move and a legend entry per piece
+        assert_eq!(labels, 3 + 2);
+        assert_eq!(picture.height, picture.width + LEGEND_ROW as u32);
+    }
+
+    #[test]
+    fn test_position() {
+        let picture = Picture::position(&game(), 3);
+        let pieces = picture.shapes.iter().filter(|shape| matches!(shape, Shape::Piece {..})).count();
+        assert_eq!(pieces, 31);
+        let highlighted = picture.shapes.iter().filter(|shape| matches!(shape, Shape::Rect {color, ..} if *color == HIGHLIGHT)).count();
+        assert_eq!(highlighted, 2);
+    }
+
+    #[test]
+    fn test_image_format() {
+        assert_eq!(ImageFormat::from_path("paths.SVG"), Some(ImageFormat::Svg));
+        assert_eq!(ImageFormat::from_path("out/board.png"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("board.jpg"), None);
+    }
+}
@@ -0,0 +1,262 @@
+// A small software rasterizer for pictures, written out as PNG. Shapes are drawn with
+// coverage based anti-aliasing, text with a 3x5 pixel font scaled up.
+use super::{Color, Picture, Shape};
+use super::super::chess::render::{self, Style};
+
+use std::error::Error;
+use std::fs;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// Largest block deflate can store uncompressed
+const STORED_BLOCK: usize = 65535;
+
+// Rows from the top, three bits per row with the leftmost pixel in the high bit
+const FONT: [(char, [u8; 5]); 38] = [
+    ('A', [2, 5, 7, 5, 5]), ('B', [6, 5, 6, 5, 6]), ('C', [3, 4, 4, 4, 3]), ('D', [6, 5, 5, 5, 6]),
+    ('E', [7, 4, 6, 4, 7]), ('F', [7, 4, 6, 4, 4]), ('G', [3, 4, 5, 5, 3]), ('H', [5, 5, 7, 5, 5]),
+    ('I', [7, 2, 2, 2, 7]), ('J', [1, 1, 1, 5, 2]), ('K', [5, 5, 6, 5, 5]), ('L', [4, 4, 4, 4, 7]),
+    ('M', [5, 7, 7, 5, 5]), ('N', [6, 5, 5, 5, 5]), ('O', [2, 5, 5, 5, 2]), ('P', [6, 5, 6, 4, 4]),
+    ('Q', [2, 5, 5, 6, 3]), ('R', [6, 5, 6, 5, 5]), ('S', [3, 4, 2, 1, 6]), ('T', [7, 2, 2, 2, 2]),
+    ('U', [5, 5, 5, 5, 7]), ('V', [5, 5, 5, 5, 2]), ('W', [5, 5, 7, 7, 5]), ('X', [5, 5, 2, 5, 5]),
+    ('Y', [5, 5, 2, 2, 2]), ('Z', [7, 1, 2, 4, 7]),
+    ('0', [7, 5, 5, 5, 7]), ('1', [2, 6, 2, 2, 7]), ('2', [6, 1, 2, 4, 7]), ('3', [6, 1, 2, 1, 6]),
+    ('4', [5, 5, 7, 1, 1]), ('5', [7, 4, 6, 1, 6]), ('6', [3, 4, 6, 5, 2]), ('7', [7, 1, 2, 2, 2]),
+    ('8', [2, 5, 2, 5, 2]), ('9', [2, 5, 3, 1, 6]), ('#', [5, 7, 5, 7, 5]), ('-', [0, 0, 7, 0, 0])
+];
+
+const WHITE: Color = Color(255, 255, 255);
+const BLACK: Color = Color(0, 0, 0);
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {width: width as usize, height: height as usize, pixels: vec![WHITE; width as usize * height as usize]}
+    }
+
+    fn blend(&mut self, x: i64, y: i64, color: Color, coverage: f32) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height || coverage <= 0.0 {
+            return;
+        }
+        let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * coverage.min(1.0)).round() as u8;
+        *pixel = Color(mix(pixel.0, color.0), mix(pixel.1, color.1), mix(pixel.2, color.2));
+    }
+
+    // Calls coverage for the center of every pixel in the box, and blends by what it returns.
+    fn fill(&mut self, left: f32, top: f32, right: f32, bottom: f32, color: Color, coverage: impl Fn(f32, f32) -> f32) {
+        for y in top.floor() as i64..bottom.ceil() as i64 {
+            for x in left.floor() as i64..right.ceil() as i64 {
+                self.blend(x, y, color, coverage(x as f32 + 0.5, y as f32 + 0.5));
+            }
+        }
+    }
+
+    fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let span = |from: f32, to: f32, at: f32| (to.min(at + 0.5) - from.max(at - 0.5)).clamp(0.0, 1.0);
+        self.fill(x, y, x + width, y + height, color, |px, py| span(x, x + width, px) * span(y, y + height, py));
+    }
+
+    fn segment(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+        let half = width / 2.0;
+        let (left, right) = (from.0.min(to.0) - half - 1.0, from.0.max(to.0) + half + 1.0);
+        let (top, bottom) = (from.1.min(to.1) - half - 1.0, from.1.max(to.1) + half + 1.0);
+        self.fill(left, top, right, bottom, color, |px, py| half + 0.5 - distance_to_segment((px, py), from, to));
+    }
+
+    fn circle(&mut self, x: f32, y: f32, radius: f32, fill: Option<Color>, stroke: Option<Color>) {
+        let reach = radius + 2.0;
+        let distance = |px: f32, py: f32| ((px - x).powi(2) + (py - y).powi(2)).sqrt();
+        if let Some(color) = fill {
+            self.fill(x - reach, y - reach, x + reach, y + reach, color, |px, py| radius + 0.5 - distance(px, py));
+        }
+        if let Some(color) = stroke {
+            self.fill(x - reach, y - reach, x + reach, y + reach, color, |px, py| 1.5 - (distance(px, py) - radius).abs());
+        }
+    }
+
+    // Upper case only, other characters are left blank.
+    fn text(&mut self, x: f32, baseline: f32, size: f32, text: &str, color: Color) {
+        let scale = (size / 6.0).round().max(1.0);
+        let width = text.chars().count() as f32 * 4.0 * scale - scale;
+        let (left, top) = ((x - width / 2.0).round(), baseline - 5.0 * scale);
+        for (idx, character) in text.chars().enumerate() {
+            let rows = match FONT.iter().find(|(glyph, _)| *glyph == character.to_ascii_uppercase()) {
+                Some((_, rows)) => rows,
+                None => continue
+            };
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (4 >> column) != 0 {
+                        let px = left + (idx as f32 * 4.0 + column as f32) * scale;
+                        self.rect(px, top + row as f32 * scale, scale, scale, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&mut self, shape: &Shape) {
+        match shape {
+            Shape::Rect {x, y, width, height, color} => self.rect(*x, *y, *width, *height, *color),
+            Shape::Path {points, width, color} => {
+                for pair in points.windows(2) {
+                    self.segment(pair[0], pair[1], *width, *color);
+                }
+            },
+            Shape::Circle {x, y, radius, fill, stroke} => self.circle(*x, *y, *radius, *fill, *stroke),
+            Shape::Text {x, y, size, text, color} => self.text(*x, *y, *size, text, *color),
+            // A disc in the side's color with the role's letter on it
+            Shape::Piece {x, y, size, role, white} => {
+                let (body, ink) = if *white {(WHITE, BLACK)} else {(BLACK, WHITE)};
+                self.circle(*x, *y, size * 0.4, Some(body), Some(BLACK));
+                let letter = render::glyph(*role, true, Style::ASCII);
+                let letter_size = size * 0.4;
+                self.text(*x, *y + letter_size / 2.0, letter_size, &letter.to_string(), ink);
+            }
+        }
+    }
+}
+
+fn distance_to_segment(point: (f32, f32), from: (f32, f32), to: (f32, f32)) -> f32 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {0.0} else {(((point.0 - from.0) * dx + (point.1 - from.1) * dy) / length).clamp(0.0, 1.0)};
+    ((point.0 - from.0 - t * dx).powi(2) + (point.1 - from.1 - t * dy).powi(2)).sqrt()
+}
+
+pub fn to_bytes(picture: &Picture) -> Vec<u8> {
+    let mut canvas = Canvas::new(picture.width, picture.height);
+    for shape in picture.shapes.iter() {
+        canvas.draw(shape);
+    }
+
+    // Every row starts with filter type 0, no filtering
+    let mut raw = Vec::with_capacity(canvas.height * (canvas.width * 3 + 1));
+    for row in canvas.pixels.chunks(canvas.width) {
+        raw.push(0);
+        for pixel in row.iter() {
+            raw.extend(&[pixel.0, pixel.1, pixel.2]);
+        }
+    }
+
+    let mut header = Vec::new();
+    header.extend(&picture.width.to_be_bytes());
+    header.extend(&picture.height.to_be_bytes());
+    // 8 bit RGB, deflate, no filter method or interlacing
+    header.extend(&[8, 2, 0, 0, 0]);
+
+    let mut bytes = SIGNATURE.to_vec();
+    chunk(&mut bytes, b"IHDR", &header);
+    chunk(&mut bytes, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut bytes, b"IEND", &[]);
+    bytes
+}
+
+pub fn save(picture: &Picture, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_bytes(picture))?;
+    Ok(())
+}
+
+fn chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend(&(data.len() as u32).to_be_bytes());
+    let start = bytes.len();
+    bytes.extend(kind);
+    bytes.extend(data);
+    let crc = crc32(&bytes[start..]);
+    bytes.extend(&crc.to_be_bytes());
+}
+
+// A zlib stream of uncompressed deflate blocks, larger files but nothing to get wrong.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if data.is_empty() {vec![&[]]} else {data.chunks(STORED_BLOCK).collect()};
+    for (idx, block) in blocks.iter().enumerate() {
+        bytes.push(if idx + 1 == blocks.len() {1} else {0});
+        let length = block.len() as u16;
+        bytes.extend(&length.to_le_bytes());
+        bytes.extend(&(!length).to_le_bytes());
+        bytes.extend(*block);
+    }
+    bytes.extend(&adler32(data).to_be_bytes());
+    bytes
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xedb8_8320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data.iter() {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn test_zlib_stored() {
+        let data = vec![7u8; STORED_BLOCK + 10];
+        let bytes = zlib_stored(&data);
+        assert_eq!(&bytes[..2], &[0x78, 0x01]);
+        // Two blocks, five header bytes each, and the checksum
+        assert_eq!(bytes.len(), 2 + data.len() + 2 * 5 + 4);
+        assert_eq!(bytes[2], 0);
+        assert_eq!(bytes[2 + 5 + STORED_BLOCK], 1);
+    }
+
+    #[test]
+    fn test_to_bytes() {
+        let picture = Picture {width: 20, height: 10, title: String::new(), shapes: vec![
+            Shape::Rect {x: 0.0, y: 0.0, width: 10.0, height: 10.0, color: Color(255, 0, 0)},
+            Shape::Path {points: vec![(12.0, 5.0), (19.0, 5.0)], width: 2.0, color: Color(0, 0, 255)}
+        ]};
+        let bytes = to_bytes(&picture);
+        assert_eq!(&bytes[..8], &SIGNATURE);
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert_eq!(&bytes[16..24], &[0, 0, 0, 20, 0, 0, 0, 10]);
+        assert_eq!(&bytes[bytes.len() - 8..bytes.len() - 4], b"IEND");
+
+        let mut canvas = Canvas::new(20, 10);
+        for shape in picture.shapes.iter() {
+            canvas.draw(shape);
+        }
+        assert_eq!(canvas.pixels[3 * 20 + 3], Color(255, 0, 0));
+        assert_eq!(canvas.pixels[4 * 20 + 15], Color(0, 0, 255));
+        assert_eq!(canvas.pixels[20 + 15], Color(255, 255, 255));
+    }
+
+    #[test]
+    fn test_text() {
+        let mut canvas = Canvas::new(12, 6);
+        canvas.text(6.0, 5.0, 6.0, "C#", BLACK);
+        let row = |y: usize| -> String {
+            canvas.pixels[y * 12..(y + 1) * 12].iter().map(|pixel| if *pixel == BLACK {'#'} else {'.'}).collect()
+        };
+        assert_eq!(row(0), "....##.#.#..");
+        assert_eq!(row(1), "...#...###..");
+    }
+}
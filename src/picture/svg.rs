@@ -0,0 +1,77 @@
+use super::{Picture, Shape};
+use super::super::chess::render::{self, Style};
+
+use std::error::Error;
+use std::fs;
+
+pub fn to_string(picture: &Picture) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n<title>{}</title>\n",
+        picture.width, picture.height, picture.width, picture.height, escape(&picture.title));
+    for shape in picture.shapes.iter() {
+        svg.push_str(&element(shape));
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn save(picture: &Picture, path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_string(picture))?;
+    Ok(())
+}
+
+fn element(shape: &Shape) -> String {
+    match shape {
+        Shape::Rect {x, y, width, height, color} =>
+            format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>", x, y, width, height, color.hex()),
+        Shape::Path {points, width, color} => {
+            let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+            format!("<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\" stroke-linecap=\"round\"/>",
+                points.join(" "), color.hex(), width)
+        },
+        Shape::Circle {x, y, radius, fill, stroke} => {
+            let fill = fill.map_or_else(|| "none".to_string(), |color| color.hex());
+            let stroke = stroke.map_or_else(String::new, |color| format!(" stroke=\"{}\" stroke-width=\"2\"", color.hex()));
+            format!("<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"{}\"{}/>", x, y, radius, fill, stroke)
+        },
+        Shape::Text {x, y, size, text, color} =>
+            format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\" text-anchor=\"middle\" fill=\"{}\">{}</text>",
+                x, y, size, color.hex(), escape(text)),
+        // The chess glyphs, the board font centers them on the baseline a third down
+        Shape::Piece {x, y, size, role, white} =>
+            format!("<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"serif\" text-anchor=\"middle\">{}</text>",
+                x, y + size / 3.0, size, render::glyph(*role, *white, Style::UNICODE))
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Color;
+    use super::super::super::chess::types::Role;
+
+    #[test]
+    fn test_to_string() {
+        let picture = Picture {width: 100, height: 50, title: "Tom & Jerry".to_string(), shapes: vec![
+            Shape::Rect {x: 0.0, y: 0.0, width: 100.0, height: 50.0, color: Color(255, 255, 255)},
+            Shape::Path {points: vec![(10.0, 10.0), (20.5, 30.0)], width: 4.0, color: Color(230, 25, 75)},
+            Shape::Circle {x: 5.0, y: 5.0, radius: 3.0, fill: None, stroke: Some(Color(0, 0, 0))},
+            Shape::Text {x: 50.0, y: 20.0, size: 10.0, text: "C#4".to_string(), color: Color(40, 40, 40)},
+            Shape::Piece {x: 30.0, y: 30.0, size: 30.0, role: Role::Knight, white: false}
+        ]};
+        let svg = to_string(&picture);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"50\""));
+        assert!(svg.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(svg.contains("<rect x=\"0\" y=\"0\" width=\"100\" height=\"50\" fill=\"#ffffff\"/>"));
+        assert!(svg.contains("points=\"10,10 20.5,30\" fill=\"none\" stroke=\"#e6194b\""));
+        assert!(svg.contains("<circle cx=\"5\" cy=\"5\" r=\"3\" fill=\"none\" stroke=\"#000000\" stroke-width=\"2\"/>"));
+        assert!(svg.contains(">C#4</text>"));
+        assert!(svg.contains(">♞</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,223 @@
+use super::lichess::LichessClient;
+use super::pgn;
+
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+use tokio::io::AsyncReadExt;
+
+const LICHESS_URL: &str = "https://lichess.org";
+const CHESS_COM_URL: &str = "https://api.chess.com";
+
+pub type GamesFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<String>, Box<dyn Error>>> + 'a>>;
+
+// Somewhere games come from, each game as PGN.
+pub trait GameSource {
+    fn describe(&self) -> String;
+    fn games(&self) -> GamesFuture<'_>;
+}
+
+// Picks the source from what was given on the command line:
+//   "-"                                          stdin
+//   "file:game.pgn", "games/game.pgn"            a local PGN file, several games are fine
+//   "https://lichess.org/tzUJbFEX", "tzUJbFEX"   a Lichess game
+//   "chesscom:hikaru/2024/05"                    a month of a Chess.com player's games
+//   "https://api.chess.com/pub/player/hikaru/games/2024/05"
+// Lichess games come with engine evaluations when evals is set and the game was analysed.
+pub fn from_arg(arg: &str, evals: bool) -> Result<Box<dyn GameSource>, Box<dyn Error>> {
+    if arg == "-" {
+        return Ok(Box::new(StdinSource));
+    }
+    if let Some(path) = arg.strip_prefix("file:") {
+        return Ok(Box::new(FileSource {path: path.to_string()}));
+    }
+    if let Some(archive) = arg.strip_prefix("chesscom:") {
+        return ChessComSource::from_archive(CHESS_COM_URL, archive).map(|source| Box::new(source) as Box<dyn GameSource>);
+    }
+    if let Some(idx) = arg.find("/pub/player/") {
+        let archive = arg[idx + "/pub/player/".len()..].replacen("/games", "", 1);
+        return ChessComSource::from_archive(&arg[..idx], &archive).map(|source| Box::new(source) as Box<dyn GameSource>);
+    }
+    if let Some(path) = arg.strip_prefix(LICHESS_URL) {
+        // Links to a player's side of the game add four characters, or /white and /black
+        let id: String = path.trim_start_matches('/').chars().take_while(|c| c.is_ascii_alphanumeric()).take(8).collect();
+        return LichessSource::new(LichessClient::new(), &id, evals).map(|source| Box::new(source) as Box<dyn GameSource>);
+    }
+    if arg.ends_with(".pgn") || std::path::Path::new(arg).is_file() {
+        return Ok(Box::new(FileSource {path: arg.to_string()}));
+    }
+    LichessSource::new(LichessClient::new(), arg, evals).map(|source| Box::new(source) as Box<dyn GameSource>)
+}
+
+pub struct FileSource {
+    path: String
+}
+
+impl GameSource for FileSource {
+    fn describe(&self) -> String {
+        format!("file {}", self.path)
+    }
+
+    fn games(&self) -> GamesFuture<'_> {
+        Box::pin(async move {
+            let text = tokio::fs::read_to_string(&self.path).await
+                .map_err(|error| format!("Could not read {}: {}", self.path, error))?;
+            Ok(pgn::split_games(&text).into_iter().map(String::from).collect())
+        })
+    }
+}
+
+pub struct StdinSource;
+
+impl GameSource for StdinSource {
+    fn describe(&self) -> String {
+        "standard input".to_string()
+    }
+
+    fn games(&self) -> GamesFuture<'_> {
+        Box::pin(async move {
+            let mut text = String::new();
+            tokio::io::stdin().read_to_string(&mut text).await?;
+            Ok(pgn::split_games(&text).into_iter().map(String::from).collect())
+        })
+    }
+}
+
+pub struct LichessSource {
+    client: LichessClient,
+    id: String,
+    evals: bool
+}
+
+impl LichessSource {
+    pub fn new(client: LichessClient, id: &str, evals: bool) -> Result<LichessSource, Box<dyn Error>> {
+        if id.len() != 8 || !id.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Err(format!("{} is not a Lichess game id, a PGN file or a game URL", id))?;
+        }
+        Ok(LichessSource {client, id: id.to_string(), evals})
+    }
+}
+
+impl GameSource for LichessSource {
+    fn describe(&self) -> String {
+        format!("Lichess game {}", self.id)
+    }
+
+    fn games(&self) -> GamesFuture<'_> {
+        Box::pin(async move {
+            Ok(vec![self.client.get_game(&self.id, self.evals).await?])
+        })
+    }
+}
+
+// A month of a player's games from Chess.com's public archive.
+pub struct ChessComSource {
+    base_url: String,
+    user: String,
+    year: u32,
+    month: u32
+}
+
+impl ChessComSource {
+    // "user/yyyy/mm"
+    fn from_archive(base_url: &str, archive: &str) -> Result<ChessComSource, Box<dyn Error>> {
+        let parts: Vec<&str> = archive.trim_matches('/').split('/').collect();
+        let invalid = || format!("Expected a Chess.com archive as user/yyyy/mm, got {}", archive);
+        if parts.len() != 3 || parts[0].is_empty() {
+            Err(invalid())?;
+        }
+        let year = parts[1].parse().map_err(|_| invalid())?;
+        let month = parts[2].parse().map_err(|_| invalid())?;
+        if !(1..=12).contains(&month) {
+            Err(invalid())?;
+        }
+        Ok(ChessComSource {base_url: base_url.to_string(), user: parts[0].to_lowercase(), year, month})
+    }
+}
+
+impl GameSource for ChessComSource {
+    fn describe(&self) -> String {
+        format!("Chess.com games of {} in {}-{:02}", self.user, self.year, self.month)
+    }
+
+    fn games(&self) -> GamesFuture<'_> {
+        Box::pin(async move {
+            let url = format!("{}/pub/player/{}/games/{}/{:02}", self.base_url, self.user, self.year, self.month);
+            // Chess.com turns away requests without a user agent
+            let response = reqwest::Client::new()
+                .get(&url)
+                .header(reqwest::header::USER_AGENT, "chessmusic")
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                Err(format!("Could not get {}: {}", self.describe(), response.status()))?;
+            }
+            let archive: serde_json::Value = serde_json::from_str(&response.text().await?)?;
+            let games = archive["games"].as_array().ok_or("Chess.com archive has no games list")?;
+            // Daily games that are still going have no PGN yet
+            Ok(games.iter().filter_map(|game| game["pgn"].as_str()).map(String::from).collect())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::mock_server::{MockServer, MockResponse};
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_from_arg() {
+        let describe = |arg| from_arg(arg, false).unwrap().describe();
+        assert_eq!(describe("-"), "standard input");
+        assert_eq!(describe("file:game"), "file game");
+        assert_eq!(describe("games/opera.pgn"), "file games/opera.pgn");
+        assert_eq!(describe("tzUJbFEX"), "Lichess game tzUJbFEX");
+        assert_eq!(describe("https://lichess.org/tzUJbFEXk2Lp"), "Lichess game tzUJbFEX");
+        assert_eq!(describe("https://lichess.org/tzUJbFEX/black"), "Lichess game tzUJbFEX");
+        assert_eq!(describe("chesscom:Hikaru/2024/5"), "Chess.com games of hikaru in 2024-05");
+        assert_eq!(describe("https://api.chess.com/pub/player/hikaru/games/2024/05"), "Chess.com games of hikaru in 2024-05");
+
+        assert!(from_arg("chesscom:hikaru/2024/13", false).is_err());
+        assert!(from_arg("chesscom:hikaru", false).is_err());
+        assert!(from_arg("not a game", false).is_err());
+    }
+
+    #[test]
+    fn test_file_source() {
+        let path = std::env::temp_dir().join("chessmusic_test_file_source.pgn");
+        std::fs::write(&path, "[Event \"One\"]\n\n1. e4 e5 *\n\n[Event \"Two\"]\n\n1. d4 *\n").unwrap();
+        let source = from_arg(&format!("file:{}", path.display()), false).unwrap();
+        let games = Runtime::new().unwrap().block_on(source.games()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(games, vec!["[Event \"One\"]\n\n1. e4 e5 *", "[Event \"Two\"]\n\n1. d4 *"]);
+
+        assert!(Runtime::new().unwrap().block_on(FileSource {path: "no/such/game.pgn".to_string()}.games()).is_err());
+    }
+
+    #[test]
+    fn test_lichess_source() {
+        Runtime::new().unwrap().block_on(async {
+            let server = MockServer::start(vec![MockResponse::ok("[Event \"Casual\"]\n\n1. e4 *\n")]).await;
+            let games = LichessSource::new(LichessClient::new().with_base_url(&server.url), "abcd1234", true).unwrap().games().await.unwrap();
+            assert_eq!(games, vec!["[Event \"Casual\"]\n\n1. e4 *\n"]);
+            assert!(server.requests()[0].starts_with("GET /game/export/abcd1234?clocks=true&evals=true "));
+        });
+    }
+
+    #[test]
+    fn test_chess_com_source() {
+        Runtime::new().unwrap().block_on(async {
+            let archive = "{\"games\":[{\"url\":\"https://www.chess.com/game/live/1\",\"pgn\":\"[Event \\\"Live Chess\\\"]\\n\\n1. e4 e5 1-0\"},{\"url\":\"https://www.chess.com/game/daily/2\"}]}";
+            let server = MockServer::start(vec![MockResponse::ok(archive), MockResponse::status(404, "{}")]).await;
+            let source = ChessComSource::from_archive(&server.url, "Hikaru/2024/05").unwrap();
+            assert_eq!(source.games().await.unwrap(), vec!["[Event \"Live Chess\"]\n\n1. e4 e5 1-0"]);
+
+            let request = &server.requests()[0];
+            assert!(request.starts_with("GET /pub/player/hikaru/games/2024/05 "), "{}", request);
+            assert!(request.to_lowercase().contains("user-agent: chessmusic"), "{}", request);
+            assert!(source.games().await.is_err());
+        });
+    }
+}